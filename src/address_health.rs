@@ -0,0 +1,106 @@
+//! Memory of the addresses which recently failed to accept a connection.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_PENALTY: Duration = Duration::from_secs(60);
+
+/// A store of the addresses which recently failed to accept a connection, shared between requests.
+///
+/// When a host resolves to several addresses, they are tried in order until one accepts the connection.
+/// When a `Request` uses an `AddressHealth`, the addresses which failed are remembered for a while, 60
+/// seconds by default, and further requests try them after the healthy addresses of the host. An address
+/// is considered healthy again as soon as a connection to it succeeds.
+///
+/// Cloning an `AddressHealth` is cheap, the clones share the same store.
+#[derive(Debug, Clone)]
+pub struct AddressHealth {
+    failed: Arc<Mutex<HashMap<SocketAddr, Instant>>>,
+    penalty: Duration,
+}
+
+impl Default for AddressHealth {
+    fn default() -> AddressHealth {
+        AddressHealth {
+            failed: Arc::default(),
+            penalty: DEFAULT_PENALTY,
+        }
+    }
+}
+
+impl AddressHealth {
+    /// Create a new, empty `AddressHealth`.
+    pub fn new() -> AddressHealth {
+        AddressHealth::default()
+    }
+
+    /// Set how long a failed address is tried after the healthy ones.
+    ///
+    /// This value defaults to 60 seconds.
+    pub fn penalty(mut self, penalty: Duration) -> AddressHealth {
+        self.penalty = penalty;
+        self
+    }
+
+    /// Forget all the failed addresses.
+    pub fn clear(&self) {
+        self.failed.lock().unwrap().clear();
+    }
+
+    /// Check if the given address did not fail recently.
+    pub fn is_healthy(&self, addr: &SocketAddr) -> bool {
+        let failed = self.failed.lock().unwrap();
+        match failed.get(addr) {
+            Some(when) => when.elapsed() >= self.penalty,
+            None => true,
+        }
+    }
+
+    pub(crate) fn mark_failed(&self, addr: SocketAddr) {
+        self.failed.lock().unwrap().insert(addr, Instant::now());
+    }
+
+    pub(crate) fn mark_healthy(&self, addr: &SocketAddr) {
+        self.failed.lock().unwrap().remove(addr);
+    }
+
+    /// Move the addresses which failed recently to the end, keeping the order of the others.
+    pub(crate) fn sort(&self, addrs: &mut [SocketAddr]) {
+        let mut failed = self.failed.lock().unwrap();
+        let penalty = self.penalty;
+        failed.retain(|_, when| when.elapsed() < penalty);
+        addrs.sort_by_key(|addr| failed.get(addr).copied());
+    }
+}
+
+#[test]
+fn test_sort_failed_last() {
+    let a: SocketAddr = "10.0.0.1:80".parse().unwrap();
+    let b: SocketAddr = "10.0.0.2:80".parse().unwrap();
+    let c: SocketAddr = "10.0.0.3:80".parse().unwrap();
+
+    let health = AddressHealth::new();
+    health.mark_failed(a);
+    health.mark_failed(b);
+    assert!(!health.is_healthy(&a));
+    assert!(health.is_healthy(&c));
+
+    let mut addrs = vec![a, b, c];
+    health.sort(&mut addrs);
+    assert_eq!(addrs, vec![c, a, b]);
+
+    health.mark_healthy(&a);
+    let mut addrs = vec![a, b, c];
+    health.sort(&mut addrs);
+    assert_eq!(addrs, vec![a, c, b]);
+}
+
+#[test]
+fn test_penalty_expires() {
+    let a: SocketAddr = "10.0.0.1:80".parse().unwrap();
+    let health = AddressHealth::new().penalty(Duration::from_secs(0));
+    health.mark_failed(a);
+    assert!(health.is_healthy(&a));
+}
@@ -0,0 +1,230 @@
+//! Support for alternative services, as described in RFC 7838.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::{HeaderMap, ALT_SVC};
+use url::Url;
+
+/// Protocol identifier of the only protocol this crate can use on an alternative service.
+const HTTP_1_1: &str = "http/1.1";
+
+/// Default lifetime of an alternative service, when the `ma` parameter is absent.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An alternative service advertised by a server in an `Alt-Svc` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltSvc {
+    /// ALPN protocol identifier of the alternative service, such as `h2` or `http/1.1`.
+    pub protocol: String,
+    /// Host of the alternative service. `None` means that it's the same host as the origin.
+    pub host: Option<String>,
+    /// Port of the alternative service.
+    pub port: u16,
+    /// How long the alternative service can be used.
+    pub max_age: Duration,
+}
+
+fn percent_decode(s: &str) -> String {
+    url::percent_encoding::percent_decode(s.as_bytes())
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+fn parse_alt_value(value: &str) -> Option<AltSvc> {
+    let mut parts = value.split(';').map(|s| s.trim());
+
+    let (protocol, authority) = {
+        let mut kv = parts.next()?.splitn(2, '=');
+        (kv.next()?.trim(), kv.next()?.trim())
+    };
+    if authority.len() < 2 || !authority.starts_with('"') || !authority.ends_with('"') {
+        return None;
+    }
+    let authority = &authority[1..authority.len() - 1];
+    let colon = authority.rfind(':')?;
+    let host = &authority[..colon];
+    let port = authority[colon + 1..].parse().ok()?;
+
+    let mut max_age = DEFAULT_MAX_AGE;
+    for param in parts {
+        let mut kv = param.splitn(2, '=');
+        if let (Some(key), Some(val)) = (kv.next(), kv.next()) {
+            if key.trim().eq_ignore_ascii_case("ma") {
+                max_age = Duration::from_secs(val.trim().trim_matches('"').parse().ok()?);
+            }
+        }
+    }
+
+    Some(AltSvc {
+        protocol: percent_decode(protocol),
+        host: if host.is_empty() { None } else { Some(host.to_string()) },
+        port,
+        max_age,
+    })
+}
+
+/// Parse the value of an `Alt-Svc` header.
+///
+/// Returns `None` if the header is the special `clear` value, which invalidates all the alternative
+/// services of the origin. Invalid alternatives are skipped.
+pub fn parse_alt_svc(value: &str) -> Option<Vec<AltSvc>> {
+    if value.trim() == "clear" {
+        return None;
+    }
+    Some(value.split(',').filter_map(parse_alt_value).collect())
+}
+
+#[derive(Debug)]
+struct Entry {
+    host: String,
+    port: u16,
+    expires: Instant,
+}
+
+/// A cache of alternative services, shared between requests.
+///
+/// When a `Request` uses an `AltSvcCache`, the `Alt-Svc` headers of the responses are recorded in the cache,
+/// and further requests to the same origin are sent to the alternative service, as long as it has not
+/// expired. Only alternative services for `https` origins that speak HTTP/1.1 are used, since the TLS
+/// certificate of the origin authenticates the alternative service. If the alternative service cannot be
+/// reached, the origin is used and the alternative service is forgotten.
+///
+/// Cloning an `AltSvcCache` is cheap, the clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct AltSvcCache {
+    entries: Arc<Mutex<HashMap<String, Vec<Entry>>>>,
+}
+
+fn origin_key(url: &Url) -> Option<String> {
+    if url.scheme() != "https" {
+        return None;
+    }
+    Some(url.origin().ascii_serialization())
+}
+
+impl AltSvcCache {
+    /// Create a new, empty `AltSvcCache`.
+    pub fn new() -> AltSvcCache {
+        AltSvcCache::default()
+    }
+
+    /// Forget all the alternative services in this cache.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Record the alternative services advertised in the headers of a response from the given URL.
+    pub(crate) fn update(&self, url: &Url, headers: &HeaderMap) {
+        let key = match origin_key(url) {
+            Some(key) => key,
+            None => return,
+        };
+        let origin_host = match url.host_str() {
+            Some(host) => host,
+            None => return,
+        };
+
+        for value in headers.get_all(ALT_SVC).iter().filter_map(|v| v.to_str().ok()) {
+            let mut entries = self.entries.lock().unwrap();
+            match parse_alt_svc(value) {
+                None => {
+                    debug!("clearing alternative services of {}", key);
+                    entries.remove(&key);
+                }
+                Some(services) => {
+                    let now = Instant::now();
+                    let usable: Vec<Entry> = services
+                        .into_iter()
+                        .filter(|svc| svc.protocol == HTTP_1_1)
+                        .map(|svc| Entry {
+                            host: svc.host.unwrap_or_else(|| origin_host.to_string()),
+                            port: svc.port,
+                            expires: now + svc.max_age,
+                        })
+                        .collect();
+                    debug!("recording {} alternative services for {}", usable.len(), key);
+                    entries.insert(key.clone(), usable);
+                }
+            }
+        }
+    }
+
+    /// Find an alternative service for the origin of the given URL.
+    pub(crate) fn lookup(&self, url: &Url) -> Option<(String, u16)> {
+        let key = origin_key(url)?;
+        let mut entries = self.entries.lock().unwrap();
+        let services = entries.get_mut(&key)?;
+        let now = Instant::now();
+        services.retain(|entry| entry.expires > now);
+        services.first().map(|entry| (entry.host.clone(), entry.port))
+    }
+
+    /// Forget an alternative service of the origin of the given URL, because it could not be reached.
+    pub(crate) fn remove(&self, url: &Url, host: &str, port: u16) {
+        if let Some(key) = origin_key(url) {
+            if let Some(services) = self.entries.lock().unwrap().get_mut(&key) {
+                services.retain(|entry| entry.host != host || entry.port != port);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_alt_svc() {
+    let services = parse_alt_svc(r#"h3=":443"; ma=3600, http%2F1.1="alt.foo.bar:8443"; persist=1"#).unwrap();
+    assert_eq!(
+        services,
+        vec![
+            AltSvc {
+                protocol: "h3".into(),
+                host: None,
+                port: 443,
+                max_age: Duration::from_secs(3600),
+            },
+            AltSvc {
+                protocol: "http/1.1".into(),
+                host: Some("alt.foo.bar".into()),
+                port: 8443,
+                max_age: DEFAULT_MAX_AGE,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_alt_svc_clear_and_invalid() {
+    assert_eq!(parse_alt_svc("clear"), None);
+    assert_eq!(parse_alt_svc(r#"h2=foo:443, h2=":abc""#), Some(vec![]));
+}
+
+#[test]
+fn test_alt_svc_cache() {
+    use http::header::HeaderValue;
+
+    let cache = AltSvcCache::new();
+    let url = Url::parse("https://foo.bar/path").unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ALT_SVC,
+        HeaderValue::from_static(r#"h2=":443", http%2F1.1=":8443"; ma=60"#),
+    );
+
+    cache.update(&url, &headers);
+    assert_eq!(cache.lookup(&url), Some(("foo.bar".into(), 8443)));
+    assert_eq!(cache.lookup(&Url::parse("https://qux.bar/").unwrap()), None);
+
+    // Plain HTTP origins cannot be authenticated, their alternative services are ignored.
+    let http_url = Url::parse("http://foo.bar/").unwrap();
+    cache.update(&http_url, &headers);
+    assert_eq!(cache.lookup(&http_url), None);
+
+    cache.remove(&url, "foo.bar", 8443);
+    assert_eq!(cache.lookup(&url), None);
+
+    cache.update(&url, &headers);
+    headers.insert(ALT_SVC, HeaderValue::from_static("clear"));
+    cache.update(&url, &headers);
+    assert_eq!(cache.lookup(&url), None);
+}
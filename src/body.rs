@@ -0,0 +1,637 @@
+//! Bodies of the requests.
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(feature = "compress")]
+use libflate::gzip;
+
+use crate::multipart::Multipart;
+#[cfg(feature = "json")]
+use crate::query::to_query_pairs;
+#[cfg(feature = "json")]
+use crate::request::to_json_vec;
+#[cfg(feature = "json")]
+use crate::Result;
+
+/// How many times a `Body` can be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    /// The body can be written again, to retry the request on a new connection or to follow a `307` or `308`
+    /// redirection.
+    Replayable,
+    /// The body can only be written once.
+    Once,
+}
+
+/// A request body, which can be implemented outside of this crate to send custom payloads.
+///
+/// A `Body` is sent with `RequestBuilder::custom_body` or `try_custom_body`. When its length is known, it is
+/// sent in the `Content-Length` header and exactly that many bytes must be written. Otherwise the body is sent
+/// with the chunked transfer coding. The data is sent while it is written, and writing fails once the server
+/// responded before the end of the body.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> attohttpc::Result {
+/// use std::io::{self, Write};
+///
+/// use attohttpc::{Body, BodyKind};
+///
+/// struct Lines(Vec<String>);
+///
+/// impl Body for Lines {
+///     fn kind(&self) -> BodyKind {
+///         BodyKind::Replayable
+///     }
+///
+///     fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+///         for line in &self.0 {
+///             writeln!(writer, "{}", line)?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let body = Lines(vec!["hello".into(), "world".into()]);
+/// let resp = attohttpc::post("https://example.com/lines").custom_body(body).send()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Body: Send {
+    /// Get how many times this body can be sent. The default is `BodyKind::Once`.
+    fn kind(&self) -> BodyKind {
+        BodyKind::Once
+    }
+
+    /// Get the length of this body, if it is known. The default is `None`.
+    fn len(&self) -> Option<u64> {
+        None
+    }
+
+    /// Check if this body is known to be empty.
+    fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Get the media type sent in the `Content-Type` header when it is unset. The default is `None`, which
+    /// sends `application/octet-stream`.
+    fn content_type(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Write this body to the writer.
+    ///
+    /// A `BodyKind::Replayable` body must write the same data each time this method is called.
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+impl Body for Vec<u8> {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(Vec::len(self) as u64)
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self)
+    }
+}
+
+impl Body for &'static [u8] {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(<[u8]>::len(self) as u64)
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self)
+    }
+}
+
+impl Body for String {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(String::len(self) as u64)
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+impl Body for &'static str {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(str::len(self) as u64)
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+}
+
+/// A file is written from its start each time it is sent.
+impl Body for File {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        self.metadata().ok().map(|metadata| metadata.len())
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        self.seek(SeekFrom::Start(0))?;
+        io::copy(self, writer)?;
+        Ok(())
+    }
+}
+
+impl Body for Multipart {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(Multipart::len(self))
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        io::copy(&mut self.reader(), writer)?;
+        Ok(())
+    }
+}
+
+/// A body which is read from a reader, with its length if it is known.
+///
+/// When the length is known, exactly that many bytes are read. The reader can only be sent once.
+pub struct ReaderBody<R> {
+    reader: R,
+    len: Option<u64>,
+}
+
+impl<R: Read + Send> ReaderBody<R> {
+    /// Create a new `ReaderBody` reading the given reader.
+    pub fn new(reader: R, len: Option<u64>) -> ReaderBody<R> {
+        ReaderBody { reader, len }
+    }
+}
+
+impl<R: Read + Send> Body for ReaderBody<R> {
+    fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut reader = (&mut self.reader).take(self.len.unwrap_or(u64::MAX));
+        io::copy(&mut reader, writer)?;
+        Ok(())
+    }
+}
+
+/// A body which is the JSON representation of a value.
+///
+/// This type only exists when the `json` feature is enabled.
+#[cfg(feature = "json")]
+pub struct Json(Vec<u8>);
+
+#[cfg(feature = "json")]
+impl Json {
+    /// Create a new `Json` body with the JSON representation of the given value.
+    pub fn new<T: serde::Serialize>(value: &T) -> Result<Json> {
+        Ok(Json(to_json_vec(value, false)?))
+    }
+
+    /// Create a new `Json` body with the pretty-printed JSON representation of the given value.
+    pub fn pretty<T: serde::Serialize>(value: &T) -> Result<Json> {
+        Ok(Json(to_json_vec(value, true)?))
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(feature = "json")]
+impl Body for Json {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(self.0.len() as u64)
+    }
+
+    fn content_type(&self) -> Option<&'static str> {
+        Some("application/json")
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+/// A body which is the `application/x-www-form-urlencoded` encoding of pairs of keys and values.
+pub struct Form(Vec<u8>);
+
+impl Form {
+    /// Create a new `Form` body with the given pairs, encoded in UTF-8.
+    pub fn new<K, V>(pairs: &[(K, V)]) -> Form
+    where
+        K: AsRef<str>,
+        V: Display,
+    {
+        Form::encode(pairs, |s| url::form_urlencoded::byte_serialize(s.as_bytes()).collect())
+    }
+
+    /// Create a new `Form` body with the fields of the given map or struct.
+    ///
+    /// An error is returned if the value is not a map or a struct, or if one of its fields is a map or a
+    /// struct. This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn from_value<T>(value: &T) -> Result<Form>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Ok(Form::new(&to_query_pairs(value)?))
+    }
+
+    /// Encode the pairs with the given function, which percent-encodes the keys and the values.
+    pub(crate) fn encode<K, V, F>(pairs: &[(K, V)], urlencode: F) -> Form
+    where
+        K: AsRef<str>,
+        V: Display,
+        F: Fn(&str) -> String,
+    {
+        let mut body = String::new();
+        for (key, value) in pairs {
+            if !body.is_empty() {
+                body.push('&');
+            }
+            body += &urlencode(key.as_ref());
+            body.push('=');
+            body += &urlencode(&value.to_string());
+        }
+        Form(body.into_bytes())
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Body for Form {
+    fn kind(&self) -> BodyKind {
+        BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(self.0.len() as u64)
+    }
+
+    fn content_type(&self) -> Option<&'static str> {
+        Some("application/x-www-form-urlencoded")
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+}
+
+/// The body of a request.
+pub(crate) enum Payload {
+    Bytes(Vec<u8>),
+    Multipart(Multipart),
+    /// A file, with its length. The file is opened when the body is sent.
+    File(PathBuf, u64),
+    /// A reader, with its length if it is known. The reader is consumed when the body is sent.
+    Reader(Mutex<Box<dyn Read + Send>>, Option<u64>),
+    /// A body implemented outside of this crate.
+    Custom(Mutex<Box<dyn Body>>),
+    /// A body which is compressed with gzip while it is sent.
+    #[cfg(feature = "compress")]
+    Gzip(Box<Payload>),
+}
+
+impl Default for Payload {
+    fn default() -> Payload {
+        Payload::Bytes(Vec::new())
+    }
+}
+
+impl Payload {
+    /// Get the length of the body, if it is known.
+    pub(crate) fn len(&self) -> Option<u64> {
+        match self {
+            Payload::Bytes(bytes) => Some(bytes.len() as u64),
+            Payload::Multipart(multipart) => Some(multipart.len()),
+            Payload::File(_, len) => Some(*len),
+            Payload::Reader(_, len) => *len,
+            Payload::Custom(body) => body.lock().unwrap().len(),
+            #[cfg(feature = "compress")]
+            Payload::Gzip(_) => None,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Check if the body can be sent more than once.
+    pub(crate) fn is_replayable(&self) -> bool {
+        match self {
+            Payload::Bytes(_) | Payload::Multipart(_) | Payload::File(..) => true,
+            Payload::Reader(..) => false,
+            Payload::Custom(body) => body.lock().unwrap().kind() == BodyKind::Replayable,
+            #[cfg(feature = "compress")]
+            Payload::Gzip(body) => body.is_replayable(),
+        }
+    }
+
+    /// Copy the body, if it can be sent more than once.
+    pub(crate) fn try_clone(&self) -> Option<Payload> {
+        match self {
+            Payload::Bytes(bytes) => Some(Payload::Bytes(bytes.clone())),
+            Payload::Multipart(multipart) => Some(Payload::Multipart(multipart.clone())),
+            Payload::File(path, len) => Some(Payload::File(path.clone(), *len)),
+            Payload::Reader(..) | Payload::Custom(_) => None,
+            #[cfg(feature = "compress")]
+            Payload::Gzip(body) => Some(Payload::Gzip(Box::new(body.try_clone()?))),
+        }
+    }
+
+    /// Get the bytes of the body, if it is held in memory.
+    pub(crate) fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Payload::Bytes(bytes) => Some(bytes),
+            Payload::Multipart(_) | Payload::File(..) | Payload::Reader(..) | Payload::Custom(_) => None,
+            #[cfg(feature = "compress")]
+            Payload::Gzip(_) => None,
+        }
+    }
+
+    /// Get a reader of the body.
+    ///
+    /// Custom bodies are written in memory to be read, use `write` to stream them when `is_written` is true.
+    pub(crate) fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(match self {
+            Payload::Bytes(bytes) => Box::new(&bytes[..]),
+            Payload::Multipart(multipart) => Box::new(multipart.reader()),
+            Payload::File(path, len) => Box::new(FileReader::new(path, *len)),
+            Payload::Reader(reader, _) => Box::new(LockedReader(reader.lock().unwrap())),
+            Payload::Custom(body) => {
+                let mut buf = Vec::new();
+                body.lock().unwrap().write(&mut buf)?;
+                Box::new(Cursor::new(buf))
+            }
+            #[cfg(feature = "compress")]
+            Payload::Gzip(body) => Box::new(GzipReader::new(body.reader()?)),
+        })
+    }
+
+    /// Check if the body must be written to be streamed, rather than read: custom bodies, possibly compressed.
+    pub(crate) fn is_written(&self) -> bool {
+        match self {
+            Payload::Custom(_) => true,
+            #[cfg(feature = "compress")]
+            Payload::Gzip(body) => body.is_written(),
+            _ => false,
+        }
+    }
+
+    /// Write the body to the writer.
+    pub(crate) fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Payload::Custom(body) => body.lock().unwrap().write(writer),
+            #[cfg(feature = "compress")]
+            Payload::Gzip(body) if body.is_written() => {
+                let mut encoder = gzip::Encoder::new(writer)?;
+                body.write(&mut encoder)?;
+                encoder.finish().into_result()?;
+                Ok(())
+            }
+            body => {
+                io::copy(&mut body.reader()?, writer)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Compress the body with gzip. Bytes are compressed right away, other bodies are compressed while
+    /// they are read.
+    #[cfg(feature = "compress")]
+    pub(crate) fn gzip(self) -> io::Result<Payload> {
+        match self {
+            Payload::Bytes(bytes) => {
+                let mut encoder = gzip::Encoder::new(Vec::new())?;
+                io::Write::write_all(&mut encoder, &bytes)?;
+                Ok(Payload::Bytes(encoder.finish().into_result()?))
+            }
+            body => Ok(Payload::Gzip(Box::new(body))),
+        }
+    }
+}
+
+/// Reader of a file body, which opens the file on the first read and reads exactly its length.
+struct FileReader<'a> {
+    path: &'a Path,
+    file: Option<File>,
+    remaining: u64,
+}
+
+impl FileReader<'_> {
+    fn new(path: &Path, len: u64) -> FileReader<'_> {
+        FileReader {
+            path,
+            file: None,
+            remaining: len,
+        }
+    }
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.remaining == 0 {
+            return Ok(0);
+        }
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(File::open(self.path)?),
+        };
+        let max = buf.len().min(self.remaining.min(usize::MAX as u64) as usize);
+        match file.read(&mut buf[..max])? {
+            0 => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file of the body is shorter than its length",
+            )),
+            n => {
+                self.remaining -= n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
+
+struct LockedReader<'a>(MutexGuard<'a, Box<dyn Read + Send>>);
+
+impl Read for LockedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+/// Reader which compresses the data of another reader with gzip.
+#[cfg(feature = "compress")]
+struct GzipReader<R> {
+    inner: R,
+    encoder: Option<gzip::Encoder<Vec<u8>>>,
+    // Compressed data which has not been read yet.
+    output: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+#[cfg(feature = "compress")]
+impl<R: Read> GzipReader<R> {
+    fn new(inner: R) -> GzipReader<R> {
+        GzipReader {
+            inner,
+            encoder: None,
+            output: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Compress the next chunk of the inner reader into the output.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut encoder = match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => gzip::Encoder::new(Vec::new())?,
+        };
+        let mut buf = vec![0; GZIP_CHUNK_SIZE];
+        let n = read_full(&mut self.inner, &mut buf)?;
+        self.output.clear();
+        self.pos = 0;
+        if n == 0 {
+            self.output = encoder.finish().into_result()?;
+            self.done = true;
+        } else {
+            io::Write::write_all(&mut encoder, &buf[..n])?;
+            std::mem::swap(&mut self.output, encoder.as_inner_mut());
+            self.encoder = Some(encoder);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<R: Read> Read for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = (&self.output[self.pos..]).read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "compress")]
+const GZIP_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Read from the reader until the buffer is full or the end of the reader is reached.
+pub(crate) fn read_full<R>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize>
+where
+    R: Read + ?Sized,
+{
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+#[cfg(feature = "compress")]
+mod tests {
+    use std::io::{Cursor, Read};
+    use std::sync::Mutex;
+
+    use libflate::gzip;
+
+    use super::Payload;
+
+    fn decompress(body: &Payload) -> Vec<u8> {
+        let mut decoded = Vec::new();
+        gzip::Decoder::new(body.reader().unwrap())
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_gzip_bytes() {
+        let body = Payload::Bytes(b"hello hello hello".to_vec()).gzip().unwrap();
+        assert!(body.as_bytes().is_some());
+        assert!(body.is_replayable());
+        assert_eq!(decompress(&body), b"hello hello hello");
+    }
+
+    #[test]
+    fn test_gzip_reader() {
+        let contents: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let reader = Cursor::new(contents.clone());
+        let body = Payload::Reader(Mutex::new(Box::new(reader)), None).gzip().unwrap();
+        assert_eq!(body.len(), None);
+        assert!(!body.is_replayable());
+        assert_eq!(decompress(&body), contents);
+    }
+
+    #[test]
+    fn test_gzip_custom() {
+        let contents = "abc".repeat(100_000);
+        let body = Payload::Custom(Mutex::new(Box::new(contents.clone()))).gzip().unwrap();
+        assert!(body.is_written());
+        let mut encoded = Vec::new();
+        body.write(&mut encoded).unwrap();
+        let mut decoded = Vec::new();
+        gzip::Decoder::new(&encoded[..])
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, contents.as_bytes());
+    }
+
+    #[test]
+    fn test_gzip_empty_reader() {
+        let body = Payload::Reader(Mutex::new(Box::new(Cursor::new(Vec::new()))), None)
+            .gzip()
+            .unwrap();
+        assert_eq!(decompress(&body), b"");
+    }
+}
@@ -0,0 +1,189 @@
+//! Caching of responses, revalidated with their `ETag` and `Last-Modified` headers.
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+
+use http::header::{HeaderMap, CACHE_CONTROL, CONTENT_LENGTH, ETAG, LAST_MODIFIED, VARY};
+use http::StatusCode;
+use url::Url;
+
+/// A store of cached responses, used by requests set up with `RequestBuilder::cache`.
+///
+/// The responses are stored under a key computed from their URL. Implement this trait to keep the
+/// responses somewhere else than in memory, for instance on disk.
+pub trait CacheStore: Debug + Send + Sync {
+    /// Get the response stored under the given key.
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store the response under the given key, replacing the previous one.
+    fn put(&self, key: &str, response: CachedResponse);
+
+    /// Remove the response stored under the given key.
+    fn remove(&self, key: &str);
+}
+
+/// A response kept in a `CacheStore`.
+///
+/// The body is kept decoded, the `Content-Encoding` header is removed when the body was decompressed.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl CachedResponse {
+    /// Create a new `CachedResponse`.
+    pub fn new(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> CachedResponse {
+        CachedResponse { status, headers, body }
+    }
+
+    /// Get the status code of this response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of this response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of this response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Get the value of the `ETag` header of this response.
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get(ETAG).and_then(|value| value.to_str().ok())
+    }
+
+    /// Get the value of the `Last-Modified` header of this response.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.headers.get(LAST_MODIFIED).and_then(|value| value.to_str().ok())
+    }
+
+    /// Update the headers with the headers of a `304 Not Modified` response, as described in RFC 7232.
+    pub(crate) fn update(&mut self, headers: &HeaderMap) {
+        for name in headers.keys() {
+            if name == CONTENT_LENGTH {
+                continue;
+            }
+            self.headers.remove(name);
+            for value in headers.get_all(name) {
+                self.headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (StatusCode, HeaderMap, Vec<u8>) {
+        (self.status, self.headers, self.body)
+    }
+}
+
+/// A `CacheStore` which keeps the responses in memory.
+///
+/// Cloning a `MemoryCache` is cheap, the clones share the same responses.
+#[derive(Clone, Default)]
+pub struct MemoryCache {
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+impl MemoryCache {
+    /// Create a new, empty `MemoryCache`.
+    pub fn new() -> MemoryCache {
+        MemoryCache::default()
+    }
+
+    /// Get the number of responses in this cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Check if this cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all the responses in this cache.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl CacheStore for MemoryCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        self.entries.lock().unwrap().insert(key.to_string(), response);
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+impl Debug for MemoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryCache").field("len", &self.len()).finish()
+    }
+}
+
+/// Compute the key of the responses of the given URL: the URL without its fragment.
+pub(crate) fn cache_key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.as_str().to_string()
+}
+
+/// Check if a response with the given headers can be stored: it must have a validator, and must not
+/// forbid storing or vary on every header.
+pub(crate) fn is_storable(headers: &HeaderMap) -> bool {
+    let has_validator = headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED);
+    let no_store = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"));
+    let vary_all = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|field| field.trim() == "*");
+    has_validator && !no_store && !vary_all
+}
+
+#[test]
+fn test_is_storable() {
+    let mut headers = HeaderMap::new();
+    assert!(!is_storable(&headers));
+    headers.insert(ETAG, "\"abc\"".parse().unwrap());
+    assert!(is_storable(&headers));
+    headers.insert(CACHE_CONTROL, "max-age=0, No-Store".parse().unwrap());
+    assert!(!is_storable(&headers));
+    headers.remove(CACHE_CONTROL);
+    headers.insert(VARY, "*".parse().unwrap());
+    assert!(!is_storable(&headers));
+}
+
+#[test]
+fn test_update() {
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, "\"v1\"".parse().unwrap());
+    headers.insert(CACHE_CONTROL, "max-age=60".parse().unwrap());
+    let mut cached = CachedResponse::new(StatusCode::OK, headers, b"hello".to_vec());
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, "\"v2\"".parse().unwrap());
+    headers.insert(CONTENT_LENGTH, "0".parse().unwrap());
+    cached.update(&headers);
+    assert_eq!(cached.etag(), Some("\"v2\""));
+    assert_eq!(cached.headers()[CACHE_CONTROL], "max-age=60");
+    assert!(!cached.headers().contains_key(CONTENT_LENGTH));
+    assert_eq!(cached.body(), b"hello");
+}
@@ -0,0 +1,476 @@
+//! Checksums of response bodies, and the MD5 and SHA-256 hash functions.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::io;
+
+use http::header::{HeaderMap, HeaderName};
+
+use crate::error::{Error, ErrorKind};
+
+/// A digest which the body of a response must match.
+///
+/// The digest is computed while the body is read. Once the end of the body is reached, a `ChecksumMismatch`
+/// error is returned if it does not match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    /// An MD5 digest.
+    Md5([u8; 16]),
+    /// A SHA-256 digest.
+    Sha256([u8; 32]),
+}
+
+impl Checksum {
+    /// Create an MD5 checksum from its hexadecimal representation. `None` is returned if it is invalid.
+    pub fn md5_hex(digest: &str) -> Option<Checksum> {
+        let mut bytes = [0; 16];
+        from_hex(digest, &mut bytes)?;
+        Some(Checksum::Md5(bytes))
+    }
+
+    /// Create a SHA-256 checksum from its hexadecimal representation. `None` is returned if it is invalid.
+    pub fn sha256_hex(digest: &str) -> Option<Checksum> {
+        let mut bytes = [0; 32];
+        from_hex(digest, &mut bytes)?;
+        Some(Checksum::Sha256(bytes))
+    }
+
+    /// Get the bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Checksum::Md5(digest) => digest,
+            Checksum::Sha256(digest) => digest,
+        }
+    }
+
+    /// Create a checksum from the base64 encoding of a digest, if it has the right length.
+    fn from_base64(algorithm: &str, digest: &str) -> Option<Checksum> {
+        let bytes = base64::decode(digest.trim()).ok()?;
+        if algorithm.eq_ignore_ascii_case("md5") {
+            Some(Checksum::Md5(bytes.try_into().ok()?))
+        } else if algorithm.eq_ignore_ascii_case("sha-256") {
+            Some(Checksum::Sha256(bytes.try_into().ok()?))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let algorithm = match self {
+            Checksum::Md5(_) => "md5",
+            Checksum::Sha256(_) => "sha-256",
+        };
+        write!(f, "{}:{}", algorithm, hex(self.as_bytes()))
+    }
+}
+
+fn from_hex(digest: &str, bytes: &mut [u8]) -> Option<()> {
+    let digest = digest.trim().as_bytes();
+    // `from_str_radix` also accepts a sign, such as in `+f`.
+    if digest.len() != bytes.len() * 2 || !digest.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    for (byte, pair) in bytes.iter_mut().zip(digest.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(())
+}
+
+/// Find the checksum sent by the server in the `Content-Digest`, `Digest` or `Content-MD5` headers.
+///
+/// SHA-256 digests are preferred over MD5 digests.
+pub(crate) fn checksum_from_headers(headers: &HeaderMap) -> Option<Checksum> {
+    // `Content-Digest` values are byte sequences, enclosed in colons.
+    let digests = [("content-digest", true), ("digest", false)]
+        .iter()
+        .flat_map(|(name, colons)| {
+            headers
+                .get_all(HeaderName::from_static(name))
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .filter_map(move |item| {
+                    let (algorithm, digest) = item.split_once('=')?;
+                    let digest = match colons {
+                        true => digest.trim().strip_prefix(':')?.strip_suffix(':')?,
+                        false => digest,
+                    };
+                    Checksum::from_base64(algorithm.trim(), digest)
+                })
+        })
+        .chain(
+            headers
+                .get_all(HeaderName::from_static("content-md5"))
+                .iter()
+                .filter_map(|value| Checksum::from_base64("md5", value.to_str().ok()?)),
+        );
+    digests.max_by_key(|checksum| matches!(checksum, Checksum::Sha256(_)))
+}
+
+/// Computes the digest of a body while it is read, and compares it to the expected checksum at the end.
+pub(crate) struct ChecksumVerifier {
+    expected: Checksum,
+    hasher: Hasher,
+    done: bool,
+}
+
+impl ChecksumVerifier {
+    pub(crate) fn new(expected: Checksum) -> ChecksumVerifier {
+        let hasher = match expected {
+            Checksum::Md5(_) => Hasher::Md5(Md5::new()),
+            Checksum::Sha256(_) => Hasher::Sha256(Sha256::new()),
+        };
+        ChecksumVerifier {
+            expected,
+            hasher,
+            done: false,
+        }
+    }
+
+    /// Add data of the body to the digest.
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match &mut self.hasher {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Compare the digest to the expected checksum, once the end of the body is reached.
+    pub(crate) fn verify(&mut self) -> io::Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        self.done = true;
+        let actual = match &self.hasher {
+            Hasher::Md5(hasher) => Checksum::Md5(hasher.clone().finish()),
+            Hasher::Sha256(hasher) => Checksum::Sha256(hasher.clone().finish()),
+        };
+        if actual != self.expected {
+            debug!("checksum mismatch: expected {}, got {}", self.expected, actual);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::from(ErrorKind::ChecksumMismatch),
+            ));
+        }
+        Ok(())
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The data of an MD5 or SHA-256 hash which was not processed yet, and the total length of the data.
+#[derive(Clone)]
+struct Blocks {
+    pending: Vec<u8>,
+    len: u64,
+}
+
+impl Blocks {
+    fn new() -> Blocks {
+        Blocks {
+            pending: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+
+    /// Add data, and call `process` with each complete 64-byte block.
+    fn update(&mut self, mut data: &[u8], mut process: impl FnMut(&[u8])) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+        if !self.pending.is_empty() {
+            let n = data.len().min(64 - self.pending.len());
+            self.pending.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.pending.len() < 64 {
+                return;
+            }
+            process(&self.pending);
+            self.pending.clear();
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            process(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    /// Add the padding and the bit length, encoded with the given function, and process the last blocks.
+    fn finish(mut self, encode_len: fn(u64) -> [u8; 8], mut process: impl FnMut(&[u8])) {
+        let len = self.len.wrapping_mul(8);
+        self.pending.push(0x80);
+        while self.pending.len() % 64 != 56 {
+            self.pending.push(0);
+        }
+        self.pending.extend_from_slice(&encode_len(len));
+        for block in self.pending.chunks(64) {
+            process(block);
+        }
+    }
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15,
+    21,
+];
+
+const MD5_CONSTANTS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501, 0x698098d8,
+    0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340,
+    0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87,
+    0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c,
+    0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039,
+    0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92,
+    0xffeff47d, 0x85845dd1, 0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+    0xeb86d391,
+];
+
+/// Incremental MD5 hash.
+#[derive(Clone)]
+struct Md5 {
+    state: [u32; 4],
+    blocks: Blocks,
+}
+
+impl Md5 {
+    fn new() -> Md5 {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            blocks: Blocks::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks.update(data, |block| md5_block(state, block));
+    }
+
+    fn finish(mut self) -> [u8; 16] {
+        let state = &mut self.state;
+        self.blocks.finish(u64::to_le_bytes, |block| md5_block(state, block));
+        let mut digest = [0u8; 16];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+fn md5_block(state: &mut [u32; 4], block: &[u8]) {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let rotated = a
+            .wrapping_add(f)
+            .wrapping_add(MD5_CONSTANTS[i])
+            .wrapping_add(words[g])
+            .rotate_left(MD5_SHIFTS[i]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+    for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+pub(crate) fn md5(data: &[u8]) -> [u8; 16] {
+    let mut hasher = Md5::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+const SHA256_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98,
+    0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+    0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8,
+    0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819,
+    0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+    0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+    0xc67178f2,
+];
+
+/// Incremental SHA-256 hash.
+#[derive(Clone)]
+struct Sha256 {
+    state: [u32; 8],
+    blocks: Blocks,
+}
+
+impl Sha256 {
+    fn new() -> Sha256 {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+            ],
+            blocks: Blocks::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks.update(data, |block| sha256_block(state, block));
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let state = &mut self.state;
+        self.blocks.finish(u64::to_be_bytes, |block| sha256_block(state, block));
+        let mut digest = [0u8; 32];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+fn sha256_block(state: &mut [u32; 8], block: &[u8]) {
+    let mut words = [0u32; 64];
+    for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for i in 16..64 {
+        let s0 = words[i - 15].rotate_right(7) ^ words[i - 15].rotate_right(18) ^ (words[i - 15] >> 3);
+        let s1 = words[i - 2].rotate_right(17) ^ words[i - 2].rotate_right(19) ^ (words[i - 2] >> 10);
+        words[i] = words[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(words[i - 7])
+            .wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(SHA256_CONSTANTS[i])
+            .wrapping_add(words[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{HeaderMap, HeaderValue};
+
+    use super::{checksum_from_headers, hex, md5, sha256, Checksum, ChecksumVerifier, Sha256};
+
+    #[test]
+    fn test_md5() {
+        assert_eq!(hex(&md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex(&md5(b"The quick brown fox jumps over the lazy dog")),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        assert_eq!(hex(&md5(&[b'a'; 200])), "887f30b43b2867f4a9accceee7d16e6c");
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn test_incremental() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let mut hasher = Sha256::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finish(), sha256(&data));
+    }
+
+    #[test]
+    fn test_verifier() {
+        let checksum = Checksum::md5_hex("9e107d9d372bb6826bd81d3542a419d6").unwrap();
+        let mut verifier = ChecksumVerifier::new(checksum.clone());
+        verifier.update(b"The quick brown fox ");
+        verifier.update(b"jumps over the lazy dog");
+        assert!(verifier.verify().is_ok());
+
+        let mut verifier = ChecksumVerifier::new(checksum);
+        verifier.update(b"The quick brown fox");
+        assert!(verifier.verify().is_err());
+
+        assert_eq!(Checksum::md5_hex("9e107d9d"), None);
+        assert_eq!(Checksum::sha256_hex(&"z".repeat(64)), None);
+        assert_eq!(Checksum::md5_hex("+e107d9d372bb6826bd81d3542a419d6"), None);
+        assert_eq!(Checksum::md5_hex("9e107d9d372bb6826bd81d3542a419+6"), None);
+    }
+
+    #[test]
+    fn test_checksum_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(checksum_from_headers(&headers), None);
+
+        headers.insert("Content-MD5", HeaderValue::from_static("nhB9nTcrtoJr2B01QqQZ1g=="));
+        assert_eq!(
+            checksum_from_headers(&headers),
+            Checksum::md5_hex("9e107d9d372bb6826bd81d3542a419d6")
+        );
+
+        headers.insert(
+            "Digest",
+            HeaderValue::from_static("unixsum=30637, SHA-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="),
+        );
+        assert_eq!(
+            checksum_from_headers(&headers),
+            Checksum::sha256_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Content-Digest",
+            HeaderValue::from_static("sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:"),
+        );
+        assert_eq!(
+            checksum_from_headers(&headers),
+            Checksum::sha256_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+}
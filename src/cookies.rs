@@ -0,0 +1,336 @@
+//! Storage of the cookies received from the servers, as described in RFC 6265.
+
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use http::header::{HeaderMap, SET_COOKIE};
+use url::{Host, Url};
+
+use crate::date::parse_http_date;
+use crate::error::{ErrorKind, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url, host: &str) -> bool {
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain_ok && path_match(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Check if the host is the domain or one of its subdomains.
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.')
+}
+
+/// Check if the request path is the cookie path or one of its subdirectories.
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path) && (cookie_path.ends_with('/') || path.as_bytes()[cookie_path.len()] == b'/'))
+}
+
+/// Get the default path of a cookie: the directory of the request path.
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+/// Get the host of the URL in the form used to match cookies.
+fn cookie_host(url: &Url) -> Option<String> {
+    match url.host()? {
+        Host::Domain(domain) => Some(domain.trim_end_matches('.').to_ascii_lowercase()),
+        Host::Ipv4(ip) => Some(ip.to_string()),
+        Host::Ipv6(ip) => Some(ip.to_string()),
+    }
+}
+
+/// Parse a `Set-Cookie` header received in a response from the given URL.
+///
+/// Returns `None` if the cookie is invalid or may not be set by this URL.
+fn parse_set_cookie(value: &str, url: &Url, now: SystemTime) -> Option<Cookie> {
+    let host = cookie_host(url)?;
+    let mut parts = value.split(';');
+    let pair = parts.next()?;
+    let (name, value) = pair.split_at(pair.find('=')?);
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value[1..].trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url),
+        secure: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    let mut expires = None;
+
+    for attribute in parts {
+        let mut kv = attribute.splitn(2, '=');
+        let key = kv.next().unwrap_or("").trim();
+        let value = kv.next().unwrap_or("").trim();
+        if key.eq_ignore_ascii_case("expires") {
+            expires = parse_http_date(value).or(expires);
+        } else if key.eq_ignore_ascii_case("max-age") {
+            if let Ok(secs) = value.parse::<i64>() {
+                max_age = Some(match secs {
+                    secs if secs <= 0 => SystemTime::UNIX_EPOCH,
+                    secs => now + Duration::from_secs(secs as u64),
+                });
+            }
+        } else if key.eq_ignore_ascii_case("domain") {
+            let domain = value.trim_start_matches('.').to_ascii_lowercase();
+            if !domain.is_empty() {
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+        } else if key.eq_ignore_ascii_case("path") {
+            if value.starts_with('/') {
+                cookie.path = value.to_string();
+            }
+        } else if key.eq_ignore_ascii_case("secure") {
+            cookie.secure = true;
+        }
+    }
+    // Max-Age takes precedence over Expires.
+    cookie.expires = max_age.or(expires);
+
+    if !cookie.host_only {
+        // The domain must contain the host, and cannot be a top-level domain. Domains of IP addresses must
+        // be the address itself.
+        let is_ip = !matches!(url.host(), Some(Host::Domain(_)));
+        if !domain_match(&host, &cookie.domain)
+            || (is_ip && cookie.domain != host)
+            || (cookie.domain != host && !cookie.domain.contains('.'))
+        {
+            debug!(
+                "rejecting cookie {} for domain {} from {}",
+                cookie.name, cookie.domain, host
+            );
+            return None;
+        }
+        cookie.host_only = is_ip;
+    }
+    // Secure cookies can only be set over secure connections.
+    if cookie.secure && url.scheme() != "https" {
+        return None;
+    }
+    Some(cookie)
+}
+
+/// A store of the cookies received from the servers, shared between requests.
+///
+/// When a `Request` uses a `CookieJar`, the cookies set by the `Set-Cookie` headers of the responses are
+/// recorded in the jar, including the responses received while following redirections. Further requests,
+/// and the redirections of the request itself, send the cookies of the jar which match their URL in a
+/// `Cookie` header, after any `Cookie` header of the request.
+///
+/// The `Expires`, `Max-Age`, `Domain`, `Path` and `Secure` attributes of the cookies are honored, while
+/// `HttpOnly` and `SameSite` are only relevant to browsers. Cookies without an expiry date are kept as long
+/// as the jar exists. Since there is no list of public suffixes, the only domains rejected are the
+/// top-level domains.
+///
+/// Cloning a `CookieJar` is cheap, the clones share the same cookies.
+///
+/// This type only exists when the `cookies` feature is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+impl CookieJar {
+    /// Create a new, empty `CookieJar`.
+    pub fn new() -> CookieJar {
+        CookieJar::default()
+    }
+
+    /// Remove all the cookies of this jar.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Get the number of cookies in this jar, including the expired cookies which were not removed yet.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    /// Check if this jar is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Store a cookie as if it was received in a `Set-Cookie` header of a response from the given URL.
+    ///
+    /// The cookie is ignored if it is invalid, or if it may not be set by this URL.
+    pub fn set_cookie<U>(&self, url: U, set_cookie: &str) -> Result
+    where
+        U: AsRef<str>,
+    {
+        let url = Url::parse(url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        self.insert(&url, set_cookie);
+        Ok(())
+    }
+
+    /// Get the value of the `Cookie` header which would be sent with a request to the given URL.
+    pub fn cookie_header<U>(&self, url: U) -> Result<Option<String>>
+    where
+        U: AsRef<str>,
+    {
+        let url = Url::parse(url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        Ok(self.header_for(&url))
+    }
+
+    fn insert(&self, url: &Url, set_cookie: &str) {
+        let now = SystemTime::now();
+        let cookie = match parse_set_cookie(set_cookie, url, now) {
+            Some(cookie) => cookie,
+            None => return,
+        };
+
+        let mut cookies = self.cookies.lock().unwrap();
+        let existing = cookies
+            .iter()
+            .position(|c| c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path);
+        // An expired cookie removes the stored cookie. A replaced cookie keeps its place, which is its order
+        // of creation.
+        match (existing, cookie.is_expired(now)) {
+            (Some(i), true) => {
+                cookies.remove(i);
+            }
+            (Some(i), false) => cookies[i] = cookie,
+            (None, true) => {}
+            (None, false) => {
+                debug!("storing cookie {} for {}{}", cookie.name, cookie.domain, cookie.path);
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    /// Record the cookies set by the headers of a response from the given URL.
+    pub(crate) fn update(&self, url: &Url, headers: &HeaderMap) {
+        for value in headers.get_all(SET_COOKIE) {
+            if let Ok(value) = value.to_str() {
+                self.insert(url, value);
+            }
+        }
+    }
+
+    /// Get the value of the `Cookie` header for a request to the given URL.
+    ///
+    /// The cookies with the longest paths come first, then the oldest cookies, as recommended by RFC 6265.
+    pub(crate) fn header_for(&self, url: &Url) -> Option<String> {
+        let host = cookie_host(url)?;
+        let now = SystemTime::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<&Cookie> = cookies.iter().filter(|cookie| cookie.matches(url, &host)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // The sort is stable, so cookies with paths of the same length stay in their order of creation.
+        matching.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        let pairs: Vec<String> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+}
+
+#[test]
+fn test_parse_set_cookie() {
+    let url = Url::parse("https://www.example.com/foo/bar").unwrap();
+    let now = SystemTime::UNIX_EPOCH;
+
+    let cookie = parse_set_cookie("id=a3fWa; Max-Age=60; Secure; HttpOnly", &url, now).unwrap();
+    assert!(cookie.secure);
+    assert_eq!(cookie.name, "id");
+    assert_eq!(cookie.value, "a3fWa");
+    assert_eq!(cookie.domain, "www.example.com");
+    assert!(cookie.host_only);
+    assert_eq!(cookie.path, "/foo");
+    assert_eq!(cookie.expires, Some(now + Duration::from_secs(60)));
+
+    let cookie = parse_set_cookie("a=b; Domain=.Example.com; Path=/", &url, now).unwrap();
+    assert_eq!(cookie.domain, "example.com");
+    assert!(!cookie.host_only);
+    assert_eq!(cookie.path, "/");
+
+    assert!(parse_set_cookie("a=b; Domain=other.com", &url, now).is_none());
+    assert!(parse_set_cookie("a=b; Domain=com", &url, now).is_none());
+    assert!(parse_set_cookie("noequals", &url, now).is_none());
+    assert!(parse_set_cookie("noequals; Path=/", &url, now).is_none());
+    let http = Url::parse("http://www.example.com/").unwrap();
+    assert!(parse_set_cookie("a=b; Secure", &http, now).is_none());
+}
+
+#[test]
+fn test_path_match() {
+    assert!(path_match("/foo", "/foo"));
+    assert!(path_match("/foo/bar", "/foo"));
+    assert!(path_match("/foo/bar", "/foo/"));
+    assert!(!path_match("/foobar", "/foo"));
+    assert!(path_match("/anything", "/"));
+}
+
+#[test]
+fn test_cookie_jar() {
+    let jar = CookieJar::new();
+    jar.set_cookie("https://www.example.com/", "a=1; Domain=example.com")
+        .unwrap();
+    jar.set_cookie("https://www.example.com/", "b=2; Path=/docs").unwrap();
+    jar.set_cookie("https://www.example.com/", "c=3; Secure").unwrap();
+    assert_eq!(jar.len(), 3);
+
+    assert_eq!(
+        jar.cookie_header("https://www.example.com/docs/index.html").unwrap(),
+        Some("b=2; a=1; c=3".to_string())
+    );
+    assert_eq!(
+        jar.cookie_header("http://www.example.com/").unwrap(),
+        Some("a=1".to_string())
+    );
+    assert_eq!(
+        jar.cookie_header("https://api.example.com/").unwrap(),
+        Some("a=1".to_string())
+    );
+    assert_eq!(jar.cookie_header("https://example.org/").unwrap(), None);
+
+    // Replacing and deleting cookies.
+    jar.set_cookie("https://www.example.com/", "a=4; Domain=example.com")
+        .unwrap();
+    jar.set_cookie("https://www.example.com/", "c=; Max-Age=0").unwrap();
+    assert_eq!(
+        jar.cookie_header("https://www.example.com/").unwrap(),
+        Some("a=4".to_string())
+    );
+}
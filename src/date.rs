@@ -0,0 +1,150 @@
+//! Formatting of dates used by HTTP and by the archives.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Convert a number of days since the Unix epoch to a civil date `(year, month, day)`.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Convert a civil date to a number of days since the Unix epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse the numbers at the start of a token, up to `max` digits, which must not be followed by a digit.
+fn leading_number(token: &str, min: usize, max: usize) -> Option<(i64, &str)> {
+    let len = token.bytes().take_while(u8::is_ascii_digit).count();
+    if len < min || len > max {
+        return None;
+    }
+    Some((token[..len].parse().ok()?, &token[len..]))
+}
+
+/// Parse an HTTP date, or a date in one of the formats found in the `Expires` attribute of cookies.
+///
+/// This is the lenient algorithm of RFC 6265, which accepts the HTTP date formats as well as their common
+/// variations. Dates before the Unix epoch are returned as the epoch.
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+    use std::time::Duration;
+
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+
+    let tokens = value
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == ':'))
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        if time.is_none() {
+            let hms = (|| {
+                let (h, rest) = leading_number(token, 1, 2)?;
+                let (m, rest) = leading_number(rest.strip_prefix(':')?, 1, 2)?;
+                let (s, _) = leading_number(rest.strip_prefix(':')?, 1, 2)?;
+                Some((h, m, s))
+            })();
+            if hms.is_some() {
+                time = hms;
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some((d, _)) = leading_number(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            if let Some(m) = MONTHS.iter().position(|&name| name == prefix) {
+                month = Some(m as i64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some((y, _)) = leading_number(token, 2, 4) {
+                year = Some(y);
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if (70..=99).contains(&year) {
+        year += 1900;
+    } else if (0..=69).contains(&year) {
+        year += 2000;
+    }
+    if !(1..=31).contains(&day) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let secs = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64))
+}
+
+/// Format a time as an HTTP date, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub(crate) fn format_http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[test]
+fn test_format_http_date() {
+    use std::time::Duration;
+
+    assert_eq!(format_http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+    assert_eq!(
+        format_http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+        "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+}
+
+#[test]
+fn test_parse_http_date() {
+    use std::time::Duration;
+
+    let expected = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+    assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+    assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), expected);
+    assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(UNIX_EPOCH));
+    assert_eq!(parse_http_date("Wed, 01 Jan 1969 00:00:00 GMT"), Some(UNIX_EPOCH));
+    assert_eq!(parse_http_date("Sun, 32 Nov 1994 08:49:37 GMT"), None);
+    assert_eq!(parse_http_date("tomorrow"), None);
+}
@@ -0,0 +1,343 @@
+//! Digest access authentication, as described in RFC 7616.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use http::header::{HeaderMap, WWW_AUTHENTICATE};
+use http::Method;
+
+use crate::checksum::{hex, md5, sha256};
+
+static CNONCE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Hash algorithms of the Digest scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Algorithm {
+    Md5,
+    Sha256,
+}
+
+impl Algorithm {
+    fn hash(self, data: &str) -> String {
+        match self {
+            Algorithm::Md5 => hex(&md5(data.as_bytes())),
+            Algorithm::Sha256 => hex(&sha256(data.as_bytes())),
+        }
+    }
+}
+
+/// A `Digest` challenge sent by the server in a `WWW-Authenticate` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: Algorithm,
+    // Name of the algorithm as it was sent by the server.
+    algorithm_name: Option<String>,
+    session: bool,
+    qop_auth: bool,
+}
+
+impl Challenge {
+    fn from_params(params: &[(String, String)]) -> Option<Challenge> {
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let algorithm_name = param("algorithm");
+        let (algorithm, session) = match algorithm_name.map(|name| name.to_ascii_uppercase()).as_deref() {
+            None | Some("MD5") => (Algorithm::Md5, false),
+            Some("MD5-SESS") => (Algorithm::Md5, true),
+            Some("SHA-256") => (Algorithm::Sha256, false),
+            Some("SHA-256-SESS") => (Algorithm::Sha256, true),
+            Some(_) => return None,
+        };
+        // Without a qop parameter, the challenge uses the compatibility mode of RFC 2069. Only the `auth`
+        // quality of protection is supported, `auth-int` requires hashing the body.
+        let qop_auth = match param("qop") {
+            Some(qop) => {
+                if !qop.split(',').any(|qop| qop.trim().eq_ignore_ascii_case("auth")) {
+                    return None;
+                }
+                true
+            }
+            None => false,
+        };
+
+        Some(Challenge {
+            realm: param("realm")?.to_string(),
+            nonce: param("nonce")?.to_string(),
+            opaque: param("opaque").map(str::to_string),
+            algorithm,
+            algorithm_name: algorithm_name.map(str::to_string),
+            session,
+            qop_auth,
+        })
+    }
+}
+
+/// Find the strongest supported `Digest` challenge in the `WWW-Authenticate` headers of a response.
+pub(crate) fn find_challenge(headers: &HeaderMap) -> Option<Challenge> {
+    headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(parse_challenges)
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Digest"))
+        .filter_map(|(_, params)| Challenge::from_params(&params))
+        .max_by_key(|challenge| challenge.algorithm)
+}
+
+/// Split a list of values on the commas which are not in a quoted string.
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"') {
+        Some(value) => {
+            let mut unquoted = String::new();
+            let mut chars = value.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => unquoted.extend(chars.next()),
+                    c => unquoted.push(c),
+                }
+            }
+            unquoted
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Parse the challenges of a `WWW-Authenticate` header, as a list of schemes with their parameters.
+///
+/// The parameter names are lowercased, and the challenges using a `token68` are returned without parameters.
+fn parse_challenges(value: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut challenges: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for item in split_list(value) {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let token_end = item
+            .find(|c: char| c == '=' || c.is_ascii_whitespace())
+            .unwrap_or(item.len());
+        let rest = item[token_end..].trim_start();
+        let param = if rest.starts_with('=') {
+            item
+        } else {
+            challenges.push((item[..token_end].to_string(), Vec::new()));
+            rest
+        };
+        if let (Some((name, value)), Some((_, params))) = (param.split_once('='), challenges.last_mut()) {
+            if !name.trim().is_empty() && !value.trim().is_empty() {
+                params.push((name.trim().to_ascii_lowercase(), unquote(value.trim())));
+            }
+        }
+    }
+    challenges
+}
+
+/// Quote a value in a quoted string.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Generate a client nonce which is very unlikely to be reused.
+fn generate_cnonce() -> String {
+    // Each `RandomState` is seeded with random keys, which makes the hashes unpredictable.
+    let count = CNONCE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let now = SystemTime::now();
+    format!(
+        "{:016x}{:016x}",
+        RandomState::new().hash_one((count, now)),
+        RandomState::new().hash_one((count, now))
+    )
+}
+
+/// Credentials used to answer `Digest` challenges.
+#[derive(Clone)]
+pub(crate) struct DigestCredentials {
+    username: String,
+    password: String,
+}
+
+impl fmt::Debug for DigestCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DigestCredentials")
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .finish()
+    }
+}
+
+impl DigestCredentials {
+    pub(crate) fn new(username: String, password: String) -> DigestCredentials {
+        DigestCredentials { username, password }
+    }
+
+    /// Compute the value of the `Authorization` header which answers the challenge, for a request with the
+    /// given method and request target.
+    pub(crate) fn authorization(&self, challenge: &Challenge, method: &Method, uri: &str) -> String {
+        self.authorization_with_cnonce(challenge, method, uri, &generate_cnonce())
+    }
+
+    fn authorization_with_cnonce(&self, challenge: &Challenge, method: &Method, uri: &str, cnonce: &str) -> String {
+        // Each challenge is only answered once, the nonce count is always 1.
+        let nc = "00000001";
+        let algorithm = challenge.algorithm;
+
+        let mut ha1 = algorithm.hash(&format!("{}:{}:{}", self.username, challenge.realm, self.password));
+        if challenge.session {
+            ha1 = algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, cnonce));
+        }
+        let ha2 = algorithm.hash(&format!("{}:{}", method.as_str(), uri));
+        let response = if challenge.qop_auth {
+            algorithm.hash(&format!("{}:{}:{}:{}:auth:{}", ha1, challenge.nonce, nc, cnonce, ha2))
+        } else {
+            algorithm.hash(&format!("{}:{}:{}", ha1, challenge.nonce, ha2))
+        };
+
+        let mut value = format!(
+            "Digest username={}, realm={}, nonce={}, uri={}, response={}",
+            quote(&self.username),
+            quote(&challenge.realm),
+            quote(&challenge.nonce),
+            quote(uri),
+            quote(&response)
+        );
+        if let Some(algorithm_name) = &challenge.algorithm_name {
+            value += &format!(", algorithm={}", algorithm_name);
+        }
+        if challenge.qop_auth {
+            value += &format!(", qop=auth, nc={}, cnonce={}", nc, quote(cnonce));
+        }
+        if let Some(opaque) = &challenge.opaque {
+            value += &format!(", opaque={}", quote(opaque));
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+    use http::Method;
+
+    use super::{find_challenge, parse_challenges, Algorithm, DigestCredentials};
+
+    #[test]
+    fn test_parse_challenges() {
+        let challenges = parse_challenges(r#"Basic realm="a, b", Digest realm = "x\"y", qop="auth,auth-int", Bearer"#);
+        assert_eq!(
+            challenges,
+            vec![
+                ("Basic".to_string(), vec![("realm".to_string(), "a, b".to_string())]),
+                (
+                    "Digest".to_string(),
+                    vec![
+                        ("realm".to_string(), "x\"y".to_string()),
+                        ("qop".to_string(), "auth,auth-int".to_string())
+                    ]
+                ),
+                ("Bearer".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_challenge() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Digest realm="r", nonce="n", algorithm=MD5, qop="auth""#),
+        );
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Digest realm="r", nonce="n", algorithm=SHA-256, qop="auth""#),
+        );
+        headers.append(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Digest realm="r", nonce="n", algorithm=SHA-512-256"#),
+        );
+        assert_eq!(find_challenge(&headers).unwrap().algorithm, Algorithm::Sha256);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            WWW_AUTHENTICATE,
+            HeaderValue::from_static(r#"Digest realm="r", nonce="n", qop="auth-int""#),
+        );
+        assert_eq!(find_challenge(&headers), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(r#"Basic realm="r""#));
+        assert_eq!(find_challenge(&headers), None);
+    }
+
+    fn challenge(value: &'static str) -> super::Challenge {
+        let mut headers = HeaderMap::new();
+        headers.insert(WWW_AUTHENTICATE, HeaderValue::from_static(value));
+        find_challenge(&headers).unwrap()
+    }
+
+    #[test]
+    fn test_authorization_rfc_7616() {
+        // The example of section 3.9.1 of RFC 7616.
+        let credentials = DigestCredentials::new("Mufasa".into(), "Circle of Life".into());
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        let header = r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+        let value = credentials.authorization_with_cnonce(&challenge(header), &Method::GET, "/dir/index.html", cnonce);
+        assert_eq!(
+            value,
+            "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", uri=\"/dir/index.html\", \
+             response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\", algorithm=SHA-256, \
+             qop=auth, nc=00000001, cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        );
+
+        let header = r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm=MD5, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#;
+        let value = credentials.authorization_with_cnonce(&challenge(header), &Method::GET, "/dir/index.html", cnonce);
+        assert!(value.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+    }
+
+    #[test]
+    fn test_authorization_rfc_2069() {
+        // Without qop, the response only depends on the nonce.
+        let credentials = DigestCredentials::new("Mufasa".into(), "CircleOfLife".into());
+        let header = r#"Digest realm="testrealm@host.com", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093""#;
+        let value = credentials.authorization_with_cnonce(&challenge(header), &Method::GET, "/dir/index.html", "x");
+        assert_eq!(
+            value,
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+             response=\"1949323746fe6a43ef61f9606e7febea\""
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Resolution of host names using DNS-over-HTTPS (RFC 8484).
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use http::header::{ACCEPT, CONTENT_TYPE};
+use url::Url;
+
+use crate::error::{ErrorKind, Result};
+use crate::resolver::Resolve;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// A resolver which sends DNS queries over HTTPS to the given endpoint, as described by RFC 8484.
+///
+/// The endpoint is itself a host name which must be resolved. By default it is resolved by the resolver of
+/// the system. Bootstrap addresses can be given to connect to the endpoint without any other resolver,
+/// for instance `DohResolver::new("https://1.1.1.1/dns-query")` or
+/// `DohResolver::new("https://cloudflare-dns.com/dns-query").bootstrap(vec![[1, 1, 1, 1].into()])`.
+///
+/// Both `A` and `AAAA` records are queried, and the IPv4 addresses are returned first.
+///
+/// This type only exists when the `doh` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    endpoint: Url,
+    bootstrap: Vec<IpAddr>,
+}
+
+impl DohResolver {
+    /// Create a new `DohResolver` which sends its queries to the given endpoint.
+    ///
+    /// # Panics
+    /// This method will panic if the endpoint is not a valid https URL.
+    pub fn new<U>(endpoint: U) -> DohResolver
+    where
+        U: AsRef<str>,
+    {
+        DohResolver::try_new(endpoint).expect("invalid doh endpoint")
+    }
+
+    /// Create a new `DohResolver` which sends its queries to the given endpoint.
+    pub fn try_new<U>(endpoint: U) -> Result<DohResolver>
+    where
+        U: AsRef<str>,
+    {
+        let endpoint = Url::parse(endpoint.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        if endpoint.scheme() != "https" || endpoint.host_str().is_none() {
+            return Err(ErrorKind::InvalidBaseUrl.into());
+        }
+        Ok(DohResolver {
+            endpoint,
+            bootstrap: Vec::new(),
+        })
+    }
+
+    /// Connect to the endpoint using the given addresses instead of resolving its host.
+    pub fn bootstrap(mut self, addrs: Vec<IpAddr>) -> DohResolver {
+        self.bootstrap = addrs;
+        self
+    }
+
+    fn query(&self, host: &str, qtype: u16) -> io::Result<Vec<IpAddr>> {
+        let mut request = crate::post(self.endpoint.as_str())
+            .header(CONTENT_TYPE, DNS_MESSAGE)
+            .header(ACCEPT, DNS_MESSAGE)
+            .bytes(encode_query(host, qtype)?);
+        if !self.bootstrap.is_empty() {
+            request = request.resolver(Bootstrap(self.bootstrap.clone()));
+        }
+
+        let resp = request.send().map_err(other)?;
+        if !resp.is_success() {
+            return Err(other(format!("doh endpoint returned status {}", resp.status())));
+        }
+        parse_response(&resp.bytes().map_err(other)?, qtype)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        debug!("resolving {} using {}", host, self.endpoint);
+        let v4 = self.query(host, TYPE_A);
+        let v6 = self.query(host, TYPE_AAAA);
+        let ips = match (v4, v6) {
+            (Err(err), Err(_)) => return Err(err),
+            (v4, v6) => v4.into_iter().chain(v6).flatten(),
+        };
+        Ok(ips.map(|ip| SocketAddr::new(ip, port)).collect())
+    }
+}
+
+/// Resolver used to reach the endpoint, which always returns the bootstrap addresses.
+#[derive(Debug)]
+struct Bootstrap(Vec<IpAddr>);
+
+impl Resolve for Bootstrap {
+    fn resolve(&self, _: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok(self.0.iter().map(|&ip| SocketAddr::new(ip, port)).collect())
+    }
+}
+
+fn other<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::other(err)
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid dns message")
+}
+
+/// Encode a recursive query for the given host and record type in the DNS wire format.
+///
+/// The id is 0, as recommended by RFC 8484 to make the responses cacheable.
+fn encode_query(host: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let host = host.trim_end_matches('.');
+    if host.is_empty() || host.len() > 253 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid host name"));
+    }
+
+    // id, flags with recursion desired, one question, no other records.
+    let mut buf = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid host name"));
+        }
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&qtype.to_be_bytes());
+    buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(buf)
+}
+
+/// Cursor over a DNS message which checks its bounds.
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let data = self.buf.get(self.pos..self.pos + len).ok_or_else(invalid_data)?;
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let data = self.take(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    /// Skip a name, which ends with an empty label or a compression pointer.
+    fn skip_name(&mut self) -> io::Result<()> {
+        loop {
+            let len = self.u8()?;
+            match len {
+                0 => return Ok(()),
+                len if len & 0xc0 == 0xc0 => {
+                    self.take(1)?;
+                    return Ok(());
+                }
+                len if len & 0xc0 == 0 => {
+                    self.take(len as usize)?;
+                }
+                _ => return Err(invalid_data()),
+            }
+        }
+    }
+}
+
+/// Parse a DNS response and return the addresses of the records of the given type.
+fn parse_response(buf: &[u8], qtype: u16) -> io::Result<Vec<IpAddr>> {
+    let mut cursor = Cursor { buf, pos: 0 };
+    let _id = cursor.u16()?;
+    let flags = cursor.u16()?;
+    if flags & 0x8000 == 0 {
+        return Err(invalid_data());
+    }
+    match flags & 0x000f {
+        0 => {}
+        RCODE_NXDOMAIN => return Err(io::Error::new(io::ErrorKind::NotFound, "host not found")),
+        rcode => return Err(other(format!("dns query failed with rcode {}", rcode))),
+    }
+
+    let qdcount = cursor.u16()?;
+    let ancount = cursor.u16()?;
+    cursor.take(4)?;
+
+    for _ in 0..qdcount {
+        cursor.skip_name()?;
+        cursor.take(4)?;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..ancount {
+        cursor.skip_name()?;
+        let rtype = cursor.u16()?;
+        let class = cursor.u16()?;
+        cursor.take(4)?;
+        let rdlength = cursor.u16()? as usize;
+        let rdata = cursor.take(rdlength)?;
+
+        // Answers may also contain the CNAME records leading to the addresses, which are skipped.
+        if rtype != qtype || class != CLASS_IN {
+            continue;
+        }
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => addrs.push(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).into()),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(rdata);
+                addrs.push(Ipv6Addr::from(octets).into());
+            }
+            _ => return Err(invalid_data()),
+        }
+    }
+    Ok(addrs)
+}
+
+#[test]
+fn test_encode_query() {
+    let query = encode_query("example.com.", TYPE_A).unwrap();
+    assert_eq!(
+        query,
+        b"\x00\x00\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01"
+    );
+    assert!(encode_query("foo..com", TYPE_A).is_err());
+    assert!(encode_query(&"a".repeat(64), TYPE_A).is_err());
+}
+
+#[test]
+fn test_parse_response() {
+    let mut resp =
+        b"\x00\x00\x81\x80\x00\x01\x00\x02\x00\x00\x00\x00\x03www\x07example\x03com\x00\x00\x01\x00\x01".to_vec();
+    // CNAME from www.example.com to example.com, using compression pointers.
+    resp.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x02\xc0\x10");
+    // A record for example.com.
+    resp.extend_from_slice(b"\xc0\x10\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x5d\xb8\xd8\x22");
+
+    let addrs = parse_response(&resp, TYPE_A).unwrap();
+    assert_eq!(addrs, vec![IpAddr::from([93, 184, 216, 34])]);
+    assert!(parse_response(&resp, TYPE_AAAA).unwrap().is_empty());
+    assert!(parse_response(&resp[..resp.len() - 1], TYPE_A).is_err());
+}
+
+#[test]
+fn test_parse_response_nxdomain() {
+    let resp = b"\x00\x00\x81\x83\x00\x00\x00\x00\x00\x00\x00\x00";
+    let err = parse_response(resp, TYPE_A).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_endpoint_must_be_https() {
+    assert!(DohResolver::try_new("http://1.1.1.1/dns-query").is_err());
+    assert!(DohResolver::try_new("https://1.1.1.1/dns-query").is_ok());
+}
@@ -0,0 +1,87 @@
+//! Content codings of the bodies, as found in the `Content-Encoding` and `Accept-Encoding` headers.
+
+use std::fmt;
+
+use http::header::{HeaderMap, CONTENT_ENCODING};
+
+/// A content coding, which tells how the body of a message is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// No compression.
+    Identity,
+    /// The `gzip` format.
+    Gzip,
+    /// The `zlib` format, named `deflate` in HTTP.
+    Deflate,
+    /// The Brotli format, named `br` in HTTP. It can only be decoded when the `compress-br` feature is enabled.
+    Brotli,
+}
+
+impl Encoding {
+    /// Parse the name of a content coding, without regard to case. The deprecated `x-gzip` alias is accepted.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let name = name.trim();
+        [Encoding::Identity, Encoding::Gzip, Encoding::Deflate, Encoding::Brotli]
+            .iter()
+            .copied()
+            .find(|encoding| encoding.as_str().eq_ignore_ascii_case(name))
+            .or_else(|| Some(Encoding::Gzip).filter(|_| name.eq_ignore_ascii_case("x-gzip")))
+    }
+
+    /// Get the name of this content coding, as used in the HTTP headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Identity => "identity",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Brotli => "br",
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Get the content coding of a body from the `Content-Encoding` headers.
+///
+/// `None` is returned if the body has several codings, or a coding which is unknown.
+pub(crate) fn content_encoding(headers: &HeaderMap) -> Option<Encoding> {
+    let mut encodings = Vec::new();
+    for value in headers.get_all(CONTENT_ENCODING) {
+        for name in value.to_str().ok()?.split(',').filter(|name| !name.trim().is_empty()) {
+            match Encoding::from_name(name)? {
+                Encoding::Identity => {}
+                encoding => encodings.push(encoding),
+            }
+        }
+    }
+    match encodings[..] {
+        [] => Some(Encoding::Identity),
+        [encoding] => Some(encoding),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(Encoding::from_name("GZIP"), Some(Encoding::Gzip));
+    assert_eq!(Encoding::from_name(" x-gzip"), Some(Encoding::Gzip));
+    assert_eq!(Encoding::from_name("br"), Some(Encoding::Brotli));
+    assert_eq!(Encoding::from_name("zstd"), None);
+    assert_eq!(Encoding::Deflate.to_string(), "deflate");
+}
+
+#[test]
+fn test_content_encoding() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(content_encoding(&headers), Some(Encoding::Identity));
+    headers.insert(CONTENT_ENCODING, "identity, gzip".parse().unwrap());
+    assert_eq!(content_encoding(&headers), Some(Encoding::Gzip));
+    headers.append(CONTENT_ENCODING, "br".parse().unwrap());
+    assert_eq!(content_encoding(&headers), None);
+    headers.insert(CONTENT_ENCODING, "zstd".parse().unwrap());
+    assert_eq!(content_encoding(&headers), None);
+}
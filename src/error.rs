@@ -3,6 +3,9 @@ use std::fmt::{self, Display};
 use std::io;
 use std::result;
 
+use http::header::HeaderName;
+use http::StatusCode;
+
 /// Errors than can occur while parsing the response from the server.
 #[derive(Debug)]
 pub enum InvalidResponseKind {
@@ -22,6 +25,12 @@ pub enum InvalidResponseKind {
     Chunk,
     /// Invalid Content-Length header
     ContentLength,
+    /// Unsupported Content-Encoding header
+    ContentEncoding,
+    /// Invalid or unexpected Content-Range header
+    ContentRange,
+    /// The head of the response is larger than the limit
+    HeadTooLarge,
 }
 
 impl Display for InvalidResponseKind {
@@ -37,6 +46,9 @@ impl Display for InvalidResponseKind {
             ChunkSize => write!(f, "invalid chunk size"),
             Chunk => write!(f, "invalid chunk"),
             ContentLength => write!(f, "invalid content length"),
+            ContentEncoding => write!(f, "unsupported content encoding"),
+            ContentRange => write!(f, "invalid content range"),
+            HeadTooLarge => write!(f, "response head too large"),
         }
     }
 }
@@ -50,37 +62,155 @@ pub enum ErrorKind {
     Http(http::Error),
     /// IO Error
     Io(io::Error),
+    /// The server refused the connection, no one is listening on its port.
+    ConnectionRefused,
     /// Invalid base URL given to the Request.
     InvalidBaseUrl,
     /// An URL with an invalid host was found while processing the request.
     InvalidUrlHost,
     /// The URL scheme is unknown and the port is missing.
     InvalidUrlPort,
+    /// The Request was refused because it would be sent over plain HTTP while `https_only` is set.
+    InsecureRequest,
+    /// The URL contains credentials, which are refused unless `url_credentials` is set.
+    UrlCredentials,
+    /// The header cannot be removed because it is computed by this crate to frame the request: `Host`,
+    /// `Content-Length` or `Transfer-Encoding`.
+    ProtectedHeader(HeaderName),
+    /// Invalid proxy URL given to the Request.
+    InvalidProxyUrl,
+    /// The proxy refused to open a tunnel to the server.
+    ProxyConnect(StatusCode),
+    /// The SOCKS proxy refused to connect to the server, with the given reply code.
+    SocksConnect(u8),
     /// Server sent an invalid response.
     InvalidResponse(InvalidResponseKind),
     /// Too many redirections
     TooManyRedirections,
+    /// The `RedirectPolicy` of the request refused to follow a redirection.
+    RedirectionRefused,
+    /// The body of the request was consumed and cannot be sent again to follow a `307` or `308` redirection.
+    BodyNotReplayable,
+    /// The request did not complete before the timeout set with `timeout`.
+    Timeout,
+    /// The transfer rate stayed below the limit set with `low_speed_limit`.
+    TransferTooSlow,
+    /// The server does not support range requests.
+    RangeNotSupported,
+    /// The server answered with an unsuccessful status code.
+    StatusCode(StatusCode),
+    /// The body of the response is larger than the size set with `max_response_size`.
+    ResponseTooLarge,
+    /// The digest of the body of the response does not match the expected checksum.
+    ChecksumMismatch,
+    /// Invalid URI template.
+    #[cfg(feature = "uri-template")]
+    InvalidUriTemplate,
+    /// The label given to `text_with_charset` is not a known charset.
+    #[cfg(feature = "charsets")]
+    UnknownCharset(String),
     /// JSON decoding/encoding error.
     #[cfg(feature = "json")]
     Json(serde_json::Error),
+    /// JSON decoding/encoding error, with the path of the value which caused the error.
+    #[cfg(feature = "json-path")]
+    JsonPath(serde_path_to_error::Error<serde_json::Error>),
     /// TLS error encountered while connecting to an https server.
     #[cfg(feature = "tls")]
     Tls(native_tls::Error),
+    /// TLS error encountered while connecting to an https server with the `tls-rustls` feature.
+    #[cfg(feature = "tls-rustls")]
+    Rustls(rustls::Error),
 }
 
 /// A type that contains all the errors that can possibly occur while accessing an HTTP server.
 #[derive(Debug)]
-pub struct Error(Box<ErrorKind>);
+pub struct Error(Box<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    kind: ErrorKind,
+    body: Option<Vec<u8>>,
+}
 
 impl Error {
+    /// Create a `StatusCode` error which keeps the beginning of the body of the response.
+    pub(crate) fn with_body(status: StatusCode, body: Vec<u8>) -> Error {
+        Error(Box::new(Inner {
+            kind: ErrorKind::StatusCode(status),
+            body: Some(body),
+        }))
+    }
+
     /// Get a reference to the `ErrorKind` inside.
     pub fn kind(&self) -> &ErrorKind {
-        &self.0
+        &self.0.kind
     }
 
     /// Comsume this `Error` and get the `ErrorKind` inside.
     pub fn into_kind(self) -> ErrorKind {
-        *self.0
+        self.0.kind
+    }
+
+    /// Get the beginning of the body of the unsuccessful response which caused this error, if it was kept.
+    ///
+    /// The body is kept by `Response::error_for_status`, up to 64 KiB.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.0.body.as_deref()
+    }
+
+    /// Check if this error is caused by a timeout, either the one set with `timeout` or a read or connect timeout.
+    pub fn is_timeout(&self) -> bool {
+        match self.0.kind {
+            ErrorKind::Timeout => true,
+            ErrorKind::Io(ref err) => err.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+
+    /// Check if this error occurred while connecting to the server or to the proxy.
+    pub fn is_connect(&self) -> bool {
+        matches!(
+            self.0.kind,
+            ErrorKind::ConnectionRefused | ErrorKind::ProxyConnect(_) | ErrorKind::SocksConnect(_)
+        )
+    }
+
+    /// Check if this error is a TLS error.
+    pub fn is_tls(&self) -> bool {
+        match self.0.kind {
+            #[cfg(feature = "tls")]
+            ErrorKind::Tls(_) => true,
+            #[cfg(feature = "tls-rustls")]
+            ErrorKind::Rustls(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Check if this error is caused by following too many redirections.
+    pub fn is_too_many_redirects(&self) -> bool {
+        matches!(self.0.kind, ErrorKind::TooManyRedirections)
+    }
+
+    /// Check if the request which caused this error may succeed if it is sent again.
+    ///
+    /// Timeouts, refused connections, connections closed by the server and the `408`, `429`, `502`, `503`
+    /// and `504` status codes are transient errors. The request should only be sent again if it is idempotent.
+    pub fn is_retryable(&self) -> bool {
+        match self.0.kind {
+            ErrorKind::ConnectionRefused | ErrorKind::TransferTooSlow => true,
+            ErrorKind::Io(ref err) => matches!(
+                err.kind(),
+                io::ErrorKind::TimedOut
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::Interrupted
+            ),
+            ErrorKind::StatusCode(status) => matches!(status.as_u16(), 408 | 429 | 502 | 503 | 504),
+            _ => self.is_timeout(),
+        }
     }
 }
 
@@ -88,53 +218,65 @@ impl Display for Error {
     fn fmt(&self, w: &mut fmt::Formatter) -> fmt::Result {
         use ErrorKind::*;
 
-        match *self.0 {
+        match self.0.kind {
             ConnectNotSupported => write!(w, "CONNECT is not supported"),
             Http(ref e) => write!(w, "Http Error: {}", e),
             Io(ref e) => write!(w, "Io Error: {}", e),
+            ConnectionRefused => write!(w, "Connection refused"),
             InvalidBaseUrl => write!(w, "Invalid base URL"),
             InvalidUrlHost => write!(w, "URL is missing a host"),
             InvalidUrlPort => write!(w, "URL is missing a port"),
+            InsecureRequest => write!(w, "Refusing to send a request over plain HTTP"),
+            UrlCredentials => write!(w, "Refusing to send the credentials of the URL"),
+            ProtectedHeader(ref name) => write!(w, "The {} header cannot be removed", name),
+            InvalidProxyUrl => write!(w, "Invalid proxy URL"),
+            ProxyConnect(status) => write!(w, "Proxy refused to open a tunnel: {}", status),
+            SocksConnect(code) => write!(
+                w,
+                "SOCKS proxy refused to connect: {}",
+                crate::socks::reply_message(code)
+            ),
             InvalidResponse(ref k) => write!(w, "InvalidResponse: {}", k),
             TooManyRedirections => write!(w, "Too many redirections"),
+            RedirectionRefused => write!(w, "Redirection refused by the redirect policy"),
+            BodyNotReplayable => write!(w, "Cannot send the request body again to follow the redirection"),
+            Timeout => write!(w, "Request timed out"),
+            TransferTooSlow => write!(w, "Transfer rate below the low speed limit"),
+            RangeNotSupported => write!(w, "Server does not support range requests"),
+            StatusCode(status) => write!(w, "Server returned an unsuccessful status: {}", status),
+            ResponseTooLarge => write!(w, "Response body exceeds the maximum size"),
+            ChecksumMismatch => write!(w, "Response body does not match the expected checksum"),
+            #[cfg(feature = "uri-template")]
+            InvalidUriTemplate => write!(w, "Invalid URI template"),
+            #[cfg(feature = "charsets")]
+            UnknownCharset(ref label) => write!(w, "Unknown charset: {}", label),
             #[cfg(feature = "json")]
             Json(ref e) => write!(w, "Json Error: {}", e),
+            #[cfg(feature = "json-path")]
+            JsonPath(ref e) => write!(w, "Json Error: {}", e),
             #[cfg(feature = "tls")]
             Tls(ref e) => write!(w, "Tls Error: {}", e),
+            #[cfg(feature = "tls-rustls")]
+            Rustls(ref e) => write!(w, "Tls Error: {}", e),
         }
     }
 }
 
 impl StdError for Error {
-    fn description(&self) -> &str {
-        use ErrorKind::*;
-
-        match *self.0 {
-            ConnectNotSupported => "CONNECT is not supported",
-            Http(ref e) => e.description(),
-            Io(ref e) => e.description(),
-            InvalidBaseUrl => "invalid base url",
-            InvalidUrlHost => "url has no host",
-            InvalidUrlPort => "url has no port",
-            InvalidResponse(_) => "invalid response",
-            TooManyRedirections => "too many redirections",
-            #[cfg(feature = "json")]
-            Json(ref e) => e.description(),
-            #[cfg(feature = "tls")]
-            Tls(ref e) => e.description(),
-        }
-    }
-
     fn cause(&self) -> Option<&dyn StdError> {
         use ErrorKind::*;
 
-        match *self.0 {
+        match self.0.kind {
             Io(ref e) => Some(e),
             Http(ref e) => Some(e),
             #[cfg(feature = "json")]
             Json(ref e) => Some(e),
+            #[cfg(feature = "json-path")]
+            JsonPath(ref e) => Some(e.inner()),
             #[cfg(feature = "tls")]
             Tls(ref e) => Some(e),
+            #[cfg(feature = "tls-rustls")]
+            Rustls(ref e) => Some(e),
             _ => None,
         }
     }
@@ -142,33 +284,52 @@ impl StdError for Error {
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
-        Error(Box::new(ErrorKind::Io(err)))
+        // Errors of this crate which went through an `io::Error`, for instance while reading a response, are
+        // unwrapped.
+        if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return *err.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
+        Error::from(ErrorKind::Io(err))
     }
 }
 
 impl From<http::Error> for Error {
     fn from(err: http::Error) -> Error {
-        Error(Box::new(ErrorKind::Http(err)))
+        Error::from(ErrorKind::Http(err))
     }
 }
 
 #[cfg(feature = "tls")]
 impl From<native_tls::Error> for Error {
     fn from(err: native_tls::Error) -> Error {
-        Error(Box::new(ErrorKind::Tls(err)))
+        Error::from(ErrorKind::Tls(err))
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl From<rustls::Error> for Error {
+    fn from(err: rustls::Error) -> Error {
+        Error::from(ErrorKind::Rustls(err))
     }
 }
 
 #[cfg(feature = "json")]
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Error {
-        Error(Box::new(ErrorKind::Json(err)))
+        Error::from(ErrorKind::Json(err))
+    }
+}
+
+#[cfg(feature = "json-path")]
+impl From<serde_path_to_error::Error<serde_json::Error>> for Error {
+    fn from(err: serde_path_to_error::Error<serde_json::Error>) -> Error {
+        Error::from(ErrorKind::JsonPath(err))
     }
 }
 
 impl From<ErrorKind> for Error {
-    fn from(err: ErrorKind) -> Error {
-        Error(Box::new(err))
+    fn from(kind: ErrorKind) -> Error {
+        Error(Box::new(Inner { kind, body: None }))
     }
 }
 
@@ -180,13 +341,13 @@ impl From<InvalidResponseKind> for Error {
 
 impl From<Error> for io::Error {
     fn from(err: Error) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err)
+        io::Error::other(err)
     }
 }
 
 impl From<InvalidResponseKind> for io::Error {
     fn from(kind: InvalidResponseKind) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, Error(Box::new(ErrorKind::InvalidResponse(kind))))
+        io::Error::other(Error::from(ErrorKind::InvalidResponse(kind)))
     }
 }
 
@@ -0,0 +1,100 @@
+//! Support for GraphQL over HTTP.
+
+use std::fmt::{self, Display};
+use std::result;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The body of a GraphQL request.
+#[derive(Debug, Serialize)]
+pub(crate) struct GraphQlRequest<'a, V> {
+    pub query: &'a str,
+    pub variables: &'a V,
+    #[serde(rename = "operationName", skip_serializing_if = "Option::is_none")]
+    pub operation_name: Option<&'a str>,
+}
+
+/// A location in the GraphQL document of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct GraphQlLocation {
+    /// Line of the location, starting at 1.
+    pub line: u32,
+    /// Column of the location, starting at 1.
+    pub column: u32,
+}
+
+/// An error returned by a GraphQL server.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GraphQlError {
+    /// Description of the error.
+    pub message: String,
+    /// Locations in the GraphQL document associated with the error.
+    #[serde(default)]
+    pub locations: Vec<GraphQlLocation>,
+    /// Path of the field of the response associated with the error, made of field names and list indices.
+    #[serde(default)]
+    pub path: Vec<Value>,
+    /// Additional information provided by the server.
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+impl Display for GraphQlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The response to a GraphQL request, with the `data` deserialized into a type `T`.
+///
+/// A response can contain both data and errors, when some fields of the data could not be resolved.
+///
+/// This type only exists when the `json` feature is enabled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQlResponse<T> {
+    /// The data of the response, if the request could be executed.
+    pub data: Option<T>,
+    /// The errors which occured while executing the request.
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+    /// Additional information provided by the server.
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+impl<T> GraphQlResponse<T> {
+    /// Get the data if there are no errors, or the errors otherwise.
+    ///
+    /// A response without data and without errors is returned as an empty list of errors.
+    pub fn into_result(self) -> result::Result<T, Vec<GraphQlError>> {
+        match self.data {
+            Some(data) if self.errors.is_empty() => Ok(data),
+            _ => Err(self.errors),
+        }
+    }
+}
+
+#[test]
+fn test_parse_graphql_response() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Data {
+        hero: Option<String>,
+    }
+
+    let resp: GraphQlResponse<Data> = serde_json::from_str(
+        r#"{
+            "data": {"hero": null},
+            "errors": [{"message": "not found", "locations": [{"line": 2, "column": 3}], "path": ["hero"]}]
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(resp.data, Some(Data { hero: None }));
+    assert_eq!(resp.errors[0].locations, vec![GraphQlLocation { line: 2, column: 3 }]);
+    assert_eq!(resp.errors[0].path, vec![Value::from("hero")]);
+    assert_eq!(resp.into_result().unwrap_err()[0].to_string(), "not found");
+
+    let resp: GraphQlResponse<Data> = serde_json::from_str(r#"{"data": {"hero": "R2-D2"}}"#).unwrap();
+    let hero = Some("R2-D2".to_string());
+    assert_eq!(resp.into_result().unwrap(), Data { hero });
+}
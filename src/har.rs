@@ -0,0 +1,328 @@
+//! Recording of exchanges in the HTTP Archive (HAR) format.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use http::header::{HeaderMap, CONTENT_TYPE, LOCATION};
+use http::{Method, StatusCode};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::date::civil_from_days;
+use crate::error::Result;
+
+const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+
+/// Format a time as an ISO 8601 date and time in UTC, with milliseconds.
+fn format_time(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Convert headers to HAR name/value pairs. The values of sensitive headers are hidden.
+fn headers_to_json(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if value.is_sensitive() {
+                "***".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            json!({ "name": name.as_str(), "value": value })
+        })
+        .collect()
+}
+
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("")
+        .to_string()
+}
+
+#[derive(Debug)]
+struct Entry {
+    started: SystemTime,
+    request: Value,
+    status: StatusCode,
+    headers: Vec<Value>,
+    mime_type: String,
+    redirect_url: String,
+    content: Vec<u8>,
+    content_size: u64,
+    send: Duration,
+    wait: Duration,
+    receive: Duration,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        json!({
+            "startedDateTime": format_time(self.started),
+            "time": millis(self.send + self.wait + self.receive),
+            "request": self.request,
+            "response": {
+                "status": self.status.as_u16(),
+                "statusText": self.status.canonical_reason().unwrap_or(""),
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": self.headers,
+                "content": {
+                    "size": self.content_size,
+                    "mimeType": self.mime_type,
+                    "text": String::from_utf8_lossy(&self.content),
+                },
+                "redirectURL": self.redirect_url,
+                "headersSize": -1,
+                "bodySize": -1,
+            },
+            "cache": {},
+            "timings": {
+                "send": millis(self.send),
+                "wait": millis(self.wait),
+                "receive": millis(self.receive),
+            },
+        })
+    }
+}
+
+/// Records the exchanges of requests in the HTTP Archive (HAR) format.
+///
+/// Each request and response, including the ones made while following redirections, is recorded as an
+/// entry with its headers, timings and body. The bodies are truncated to a limit, 64 KiB by default, and
+/// the response body is recorded as it is read by the caller. The values of sensitive headers, such as the
+/// ones created from a `Secret`, are hidden.
+///
+/// The archive can be written as JSON using `write_to`, and opened in browser devtools or HAR analyzers.
+/// Cloning a `HarRecorder` is cheap, the clones share the same entries.
+///
+/// This type only exists when the `json` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct HarRecorder {
+    entries: Arc<Mutex<Vec<Arc<Mutex<Entry>>>>>,
+    body_limit: usize,
+}
+
+impl Default for HarRecorder {
+    fn default() -> HarRecorder {
+        HarRecorder {
+            entries: Arc::default(),
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+}
+
+impl HarRecorder {
+    /// Create a new, empty `HarRecorder`.
+    pub fn new() -> HarRecorder {
+        HarRecorder::default()
+    }
+
+    /// Set the maximum number of bytes of each body which are recorded.
+    ///
+    /// This value defaults to 64 KiB.
+    pub fn body_limit(mut self, body_limit: usize) -> HarRecorder {
+        self.body_limit = body_limit;
+        self
+    }
+
+    /// Get the number of entries recorded so far.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Check if no entries were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all the entries recorded so far.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Get the archive as a JSON value.
+    pub fn to_json(&self) -> Value {
+        let entries: Vec<Value> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.lock().unwrap().to_json())
+            .collect();
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "attohttpc",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": entries,
+            }
+        })
+    }
+
+    /// Write the archive as JSON to any object that implements `Write`.
+    pub fn write_to<W>(&self, writer: W) -> Result
+    where
+        W: Write,
+    {
+        serde_json::to_writer_pretty(writer, &self.to_json())?;
+        Ok(())
+    }
+
+    /// Record a request sent at `started`, fully written at `sent`, and for which the response head was
+    /// just received. Returns the handle used to record the response body.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn record(
+        &self,
+        method: &Method,
+        url: &Url,
+        request_headers: &HeaderMap,
+        request_body: &[u8],
+        started: (SystemTime, Instant),
+        sent: Instant,
+        status: StatusCode,
+        response_headers: &HeaderMap,
+    ) -> HarBody {
+        let now = Instant::now();
+        let body = &request_body[..request_body.len().min(self.body_limit)];
+        let mut request = json!({
+            "method": method.as_str(),
+            "url": url.as_str(),
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": headers_to_json(request_headers),
+            "queryString": url
+                .query_pairs()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>(),
+            "headersSize": -1,
+            "bodySize": request_body.len(),
+        });
+        if !request_body.is_empty() {
+            request["postData"] = json!({
+                "mimeType": content_type(request_headers),
+                "text": String::from_utf8_lossy(body),
+            });
+        }
+
+        let entry = Arc::new(Mutex::new(Entry {
+            started: started.0,
+            request,
+            status,
+            headers: headers_to_json(response_headers),
+            mime_type: content_type(response_headers),
+            redirect_url: response_headers
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("")
+                .to_string(),
+            content: Vec::new(),
+            content_size: 0,
+            send: sent - started.1,
+            wait: now - sent,
+            receive: Duration::from_secs(0),
+        }));
+        self.entries.lock().unwrap().push(entry.clone());
+
+        HarBody {
+            entry,
+            limit: self.body_limit,
+            head_received: now,
+        }
+    }
+}
+
+/// Handle used to record the body of a response as it is read.
+pub(crate) struct HarBody {
+    entry: Arc<Mutex<Entry>>,
+    limit: usize,
+    head_received: Instant,
+}
+
+impl HarBody {
+    pub(crate) fn record(&self, data: &[u8]) {
+        let mut entry = self.entry.lock().unwrap();
+        let room = self.limit.saturating_sub(entry.content.len());
+        entry.content.extend_from_slice(&data[..data.len().min(room)]);
+        entry.content_size += data.len() as u64;
+        entry.receive = self.head_received.elapsed();
+    }
+}
+
+#[test]
+fn test_format_time() {
+    assert_eq!(format_time(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        format_time(UNIX_EPOCH + Duration::from_millis(951_782_400_123)),
+        "2000-02-29T00:00:00.123Z"
+    );
+    assert_eq!(
+        format_time(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        "2023-11-14T22:13:20.000Z"
+    );
+}
+
+#[test]
+fn test_har_recorder() {
+    use crate::parsing::response::parse_response;
+    use crate::request::PreparedRequest;
+    use crate::streams::BaseStream;
+    use http::header::{HeaderValue, AUTHORIZATION};
+
+    let recorder = HarRecorder::new().body_limit(4);
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+    headers.get_mut(AUTHORIZATION).unwrap().set_sensitive(true);
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello";
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let mut resp = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+
+    let url = Url::parse("http://google.ca/?q=1").unwrap();
+    let now = (SystemTime::now(), Instant::now());
+    let body = recorder.record(
+        &Method::POST,
+        &url,
+        &headers,
+        b"data",
+        now,
+        now.1,
+        resp.status(),
+        resp.headers(),
+    );
+    resp.set_har(body);
+    assert_eq!(resp.text().unwrap(), "hello");
+
+    let har = recorder.to_json();
+    let entry = &har["log"]["entries"][0];
+    assert_eq!(entry["request"]["method"], "POST");
+    assert_eq!(entry["request"]["headers"][0]["value"], "***");
+    assert_eq!(entry["request"]["queryString"][0]["value"], "1");
+    assert_eq!(entry["request"]["postData"]["text"], "data");
+    assert_eq!(entry["response"]["status"], 200);
+    assert_eq!(entry["response"]["content"]["mimeType"], "text/plain");
+    assert_eq!(entry["response"]["content"]["size"], 5);
+    assert_eq!(entry["response"]["content"]["text"], "hell");
+}
@@ -0,0 +1,193 @@
+//! Support for HTTP Strict Transport Security, as described in RFC 6797.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::{HeaderMap, STRICT_TRANSPORT_SECURITY};
+use url::{Host, Url};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Policy {
+    max_age: Duration,
+    include_subdomains: bool,
+}
+
+/// Parse the value of a `Strict-Transport-Security` header.
+fn parse_sts(value: &str) -> Option<Policy> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+
+    for directive in value.split(';').map(|s| s.trim()) {
+        let mut kv = directive.splitn(2, '=');
+        let name = kv.next()?.trim();
+        if name.eq_ignore_ascii_case("max-age") {
+            let value = kv.next()?.trim().trim_matches('"');
+            max_age = Some(Duration::from_secs(value.parse().ok()?));
+        } else if name.eq_ignore_ascii_case("includeSubDomains") {
+            include_subdomains = true;
+        }
+    }
+
+    Some(Policy {
+        max_age: max_age?,
+        include_subdomains,
+    })
+}
+
+#[derive(Debug)]
+struct Entry {
+    expires: Instant,
+    include_subdomains: bool,
+}
+
+/// A store of the hosts known to require HTTPS, shared between requests.
+///
+/// When a `Request` uses an `HstsStore`, the `Strict-Transport-Security` headers received over HTTPS are
+/// recorded in the store. Further requests to `http` URLs of these hosts, including redirections, are
+/// automatically upgraded to `https` until the policy expires.
+///
+/// Cloning an `HstsStore` is cheap, the clones share the same store.
+#[derive(Debug, Clone, Default)]
+pub struct HstsStore {
+    hosts: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl HstsStore {
+    /// Create a new, empty `HstsStore`.
+    pub fn new() -> HstsStore {
+        HstsStore::default()
+    }
+
+    /// Forget all the hosts in this store.
+    pub fn clear(&self) {
+        self.hosts.lock().unwrap().clear();
+    }
+
+    /// Check if requests to the given host must use HTTPS.
+    pub fn is_known_host(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let mut hosts = self.hosts.lock().unwrap();
+        let now = Instant::now();
+        hosts.retain(|_, entry| entry.expires > now);
+
+        if hosts.contains_key(&host) {
+            return true;
+        }
+        // Check the superdomains of the host, which might include their subdomains.
+        let mut domain = host.as_str();
+        while let Some(dot) = domain.find('.') {
+            domain = &domain[dot + 1..];
+            if let Some(entry) = hosts.get(domain) {
+                if entry.include_subdomains {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Record the policy found in the headers of a response from the given URL.
+    pub(crate) fn update(&self, url: &Url, headers: &HeaderMap) {
+        // The header is ignored when received over an insecure connection, or for IP addresses.
+        if url.scheme() != "https" {
+            return;
+        }
+        let host = match url.host() {
+            Some(Host::Domain(host)) => host.to_ascii_lowercase(),
+            _ => return,
+        };
+        let policy = match headers
+            .get(STRICT_TRANSPORT_SECURITY)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_sts)
+        {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let mut hosts = self.hosts.lock().unwrap();
+        if policy.max_age == Duration::from_secs(0) {
+            debug!("removing {} from the hsts store", host);
+            hosts.remove(&host);
+        } else {
+            debug!("adding {} to the hsts store", host);
+            hosts.insert(
+                host,
+                Entry {
+                    expires: Instant::now() + policy.max_age,
+                    include_subdomains: policy.include_subdomains,
+                },
+            );
+        }
+    }
+
+    /// Upgrade the given URL to `https` if its host is known to require it.
+    ///
+    /// Returns true if the URL was upgraded.
+    pub(crate) fn upgrade(&self, url: &mut Url) -> bool {
+        if url.scheme() != "http" {
+            return false;
+        }
+        match url.host() {
+            Some(Host::Domain(host)) if self.is_known_host(host) => {}
+            _ => return false,
+        }
+        if url.port() == Some(80) {
+            let _ = url.set_port(None);
+        }
+        url.set_scheme("https").is_ok()
+    }
+}
+
+#[test]
+fn test_parse_sts() {
+    assert_eq!(
+        parse_sts("max-age=31536000; includeSubDomains"),
+        Some(Policy {
+            max_age: Duration::from_secs(31_536_000),
+            include_subdomains: true,
+        })
+    );
+    assert_eq!(
+        parse_sts(r#"Max-Age="60""#),
+        Some(Policy {
+            max_age: Duration::from_secs(60),
+            include_subdomains: false,
+        })
+    );
+    assert_eq!(parse_sts("includeSubDomains"), None);
+    assert_eq!(parse_sts("max-age=abc"), None);
+}
+
+#[test]
+fn test_hsts_store() {
+    use http::header::HeaderValue;
+
+    let store = HstsStore::new();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        STRICT_TRANSPORT_SECURITY,
+        HeaderValue::from_static("max-age=60; includeSubDomains"),
+    );
+
+    // Ignored over plain HTTP.
+    store.update(&Url::parse("http://foo.bar/").unwrap(), &headers);
+    assert!(!store.is_known_host("foo.bar"));
+
+    store.update(&Url::parse("https://foo.bar/").unwrap(), &headers);
+    assert!(store.is_known_host("foo.bar"));
+    assert!(store.is_known_host("www.FOO.bar"));
+    assert!(!store.is_known_host("qux.bar"));
+
+    let mut url = Url::parse("http://www.foo.bar:80/path?q=1").unwrap();
+    assert!(store.upgrade(&mut url));
+    assert_eq!(url.as_str(), "https://www.foo.bar/path?q=1");
+
+    let mut url = Url::parse("http://qux.bar/").unwrap();
+    assert!(!store.upgrade(&mut url));
+
+    headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static("max-age=0"));
+    store.update(&Url::parse("https://foo.bar/").unwrap(), &headers);
+    assert!(!store.is_known_host("foo.bar"));
+}
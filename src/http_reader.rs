@@ -0,0 +1,217 @@
+//! Random access to remote resources using range requests.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Seek, SeekFrom};
+
+use http::header::RANGE;
+use http::{Method, StatusCode};
+
+use crate::error::{ErrorKind, InvalidResponseKind, Result};
+use crate::range::parse_content_range;
+use crate::request::RequestBuilder;
+use crate::settings::Settings;
+
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+const DEFAULT_READ_AHEAD: usize = 1;
+const DEFAULT_CACHED_BLOCKS: usize = 16;
+
+/// Reader of a remote resource which implements `Read` and `Seek`, using range requests.
+///
+/// The resource is fetched in blocks, 64 KiB by default, with one `GET` request using a `Range` header per
+/// fetch. Each fetch also reads the following blocks ahead, and the most recently used blocks are kept in
+/// a cache. This allows formats such as zip archives to be read remotely without downloading the whole
+/// resource.
+///
+/// The server must support range requests, opening the reader fails with a `RangeNotSupported` error
+/// otherwise. Compression is disabled for the requests of the reader, since ranges apply to the
+/// encoded content.
+#[derive(Debug)]
+pub struct HttpReader {
+    url: String,
+    settings: Settings,
+    len: u64,
+    pos: u64,
+    block_size: usize,
+    read_ahead: usize,
+    cached_blocks: usize,
+    cache: HashMap<u64, Vec<u8>>,
+    lru: VecDeque<u64>,
+}
+
+impl HttpReader {
+    /// Open the resource at the given URL.
+    ///
+    /// The first block of the resource is fetched to find its length.
+    pub fn open<U>(url: U) -> Result<HttpReader>
+    where
+        U: AsRef<str>,
+    {
+        HttpReader::open_with_settings(url, Settings::default())
+    }
+
+    /// Open the resource at the given URL, using the given `Settings` for the requests.
+    pub fn open_with_settings<U>(url: U, settings: Settings) -> Result<HttpReader>
+    where
+        U: AsRef<str>,
+    {
+        #[cfg(feature = "compress")]
+        let settings = settings.allow_compression(false);
+
+        let mut reader = HttpReader {
+            url: url.as_ref().to_string(),
+            settings,
+            len: 0,
+            pos: 0,
+            block_size: DEFAULT_BLOCK_SIZE,
+            read_ahead: DEFAULT_READ_AHEAD,
+            cached_blocks: DEFAULT_CACHED_BLOCKS,
+            cache: HashMap::new(),
+            lru: VecDeque::new(),
+        };
+        reader.len = reader.fetch(0)?;
+        Ok(reader)
+    }
+
+    /// Set the size of the blocks which are fetched and cached.
+    ///
+    /// The cache is cleared when this value changes. This value defaults to 64 KiB.
+    ///
+    /// # Panics
+    /// This method will panic if the block size is 0.
+    pub fn block_size(mut self, block_size: usize) -> HttpReader {
+        assert!(block_size > 0, "block size must not be 0");
+        if block_size != self.block_size {
+            self.block_size = block_size;
+            self.cache.clear();
+            self.lru.clear();
+        }
+        self
+    }
+
+    /// Set the number of blocks fetched after the block being read.
+    ///
+    /// This value defaults to 1.
+    pub fn read_ahead(mut self, read_ahead: usize) -> HttpReader {
+        self.read_ahead = read_ahead;
+        self
+    }
+
+    /// Set the maximum number of blocks kept in the cache.
+    ///
+    /// This value defaults to 16.
+    pub fn cached_blocks(mut self, cached_blocks: usize) -> HttpReader {
+        self.cached_blocks = cached_blocks.max(1);
+        self
+    }
+
+    /// Get the length of the resource.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Check if the resource is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Fetch the given block and the blocks read ahead, and return the length of the resource.
+    fn fetch(&mut self, block: u64) -> Result<u64> {
+        let block_size = self.block_size as u64;
+        let start = block * block_size;
+        let end = start + block_size * (1 + self.read_ahead as u64) - 1;
+        debug!("fetching bytes {}-{} of {}", start, end, self.url);
+
+        let resp = RequestBuilder::try_with_settings(Method::GET, &self.url, self.settings.clone())?
+            .header(RANGE, format!("bytes={}-{}", start, end))
+            .send()?;
+        if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE && start == 0 {
+            // The resource is empty.
+            return Ok(0);
+        }
+        if resp.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(ErrorKind::RangeNotSupported.into());
+        }
+        let (range_start, _, len) = parse_content_range(resp.headers()).ok_or(InvalidResponseKind::ContentRange)?;
+        if range_start != start {
+            return Err(InvalidResponseKind::ContentRange.into());
+        }
+
+        let data = resp.bytes()?;
+        for (i, chunk) in data.chunks(self.block_size).enumerate() {
+            let index = block + i as u64;
+            let complete = chunk.len() == self.block_size || start + (i * self.block_size + chunk.len()) as u64 == len;
+            if complete {
+                self.insert(index, chunk.to_vec());
+            }
+        }
+        Ok(len)
+    }
+
+    fn insert(&mut self, index: u64, data: Vec<u8>) {
+        if self.cache.insert(index, data).is_none() {
+            self.lru.push_back(index);
+        }
+        while self.lru.len() > self.cached_blocks {
+            if let Some(old) = self.lru.pop_front() {
+                self.cache.remove(&old);
+            }
+        }
+    }
+
+    fn touch(&mut self, index: u64) {
+        if let Some(i) = self.lru.iter().position(|&x| x == index) {
+            self.lru.remove(i);
+            self.lru.push_back(index);
+        }
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let block_size = self.block_size as u64;
+        let index = self.pos / block_size;
+        if !self.cache.contains_key(&index) {
+            self.fetch(index).map_err(io::Error::other)?;
+        }
+        self.touch(index);
+
+        let block = self
+            .cache
+            .get(&index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "the server returned a truncated range"))?;
+        let offset = (self.pos - index * block_size) as usize;
+        if offset >= block.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server returned a truncated range",
+            ));
+        }
+        let n = buf.len().min(block.len() - offset);
+        buf[..n].copy_from_slice(&block[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )),
+        }
+    }
+}
@@ -31,8 +31,18 @@
 //! # Features
 //! * `charsets` support for decoding more text encodings than just UTF-8
 //! * `compress` support for decompressing response bodies (**default**)
+//! * `compress-br` support for decompressing response bodies encoded with brotli
+//! * `cookies` support for storing cookies and sending them with further requests
+//! * `doh` support for resolving host names using DNS-over-HTTPS
 //! * `json` support for serialization and deserialization
+//! * `json-path` support for reporting the path of the value which caused a JSON error
+//! * `mime-guess` support for guessing the `Content-Type` of files from their extension
+//! * `netrc` support for reading credentials from the `.netrc` file
+//! * `serde` support for serializing and deserializing `Settings`
+//! * `test-server` support for testing code using this crate with a scripted local HTTP server
+//! * `uri-template` support for building URLs from URI templates
 //! * `tls` support for tls connections (**default**)
+//! * `tls-rustls` support for tls connections using `rustls` instead of `native-tls`
 //!
 //! Check out the [repository](https://github.com/sbstp/attohttpc) for more general information
 //! and examples.
@@ -40,20 +50,98 @@
 #[macro_use]
 extern crate log;
 
+mod address_health;
+mod alt_svc;
+mod body;
+mod cache;
 #[cfg(feature = "charsets")]
 pub mod charsets;
+mod checksum;
+#[cfg(feature = "cookies")]
+mod cookies;
+mod date;
+mod digest_auth;
+#[cfg(feature = "doh")]
+mod doh;
+mod encoding;
 mod error;
+#[cfg(feature = "json")]
+mod graphql;
+#[cfg(feature = "json")]
+mod har;
+mod hsts;
+mod http_reader;
+mod media_type;
+mod middleware;
+mod multipart;
+#[cfg(feature = "netrc")]
+mod netrc;
+mod observer;
 mod parsing;
+mod pool;
+mod proxy;
+#[cfg(feature = "json")]
+mod query;
+mod range;
+mod rate_limit;
+mod redirect;
 mod request;
+mod resolver;
+mod secret;
+mod session;
+mod settings;
+mod socks;
 mod streams;
+#[cfg(feature = "test-server")]
+pub mod test;
+mod timings;
+#[cfg(feature = "uri-template")]
+mod uri_template;
+mod wire_log;
+mod wire_tap;
 
+pub use crate::address_health::AddressHealth;
+pub use crate::alt_svc::{AltSvc, AltSvcCache};
+#[cfg(feature = "json")]
+pub use crate::body::Json;
+pub use crate::body::{Body, BodyKind, Form, ReaderBody};
+pub use crate::cache::{CacheStore, CachedResponse, MemoryCache};
+pub use crate::checksum::Checksum;
+#[cfg(feature = "cookies")]
+pub use crate::cookies::CookieJar;
+#[cfg(feature = "doh")]
+pub use crate::doh::DohResolver;
+pub use crate::encoding::Encoding;
 pub use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
-pub use crate::parsing::{Response, ResponseReader};
+#[cfg(feature = "json")]
+pub use crate::graphql::{GraphQlError, GraphQlLocation, GraphQlResponse};
+#[cfg(feature = "json")]
+pub use crate::har::HarRecorder;
+pub use crate::hsts::HstsStore;
+pub use crate::http_reader::HttpReader;
+pub use crate::media_type::MediaType;
+pub use crate::middleware::Middleware;
+pub use crate::multipart::{Multipart, Part};
+pub use crate::observer::{Event, Observer};
+pub use crate::parsing::{Chunks, Response, ResponseReader};
+pub use crate::pool::ConnectionPool;
+pub use crate::rate_limit::RateLimiter;
+pub use crate::redirect::{RedirectAction, RedirectPolicy};
 pub use crate::request::{PreparedRequest, RequestBuilder};
+pub use crate::resolver::{Resolve, StaticResolver, SystemResolver};
+pub use crate::secret::Secret;
+pub use crate::session::Session;
+pub use crate::settings::Settings;
+pub use crate::timings::Timings;
+#[cfg(feature = "uri-template")]
+pub use crate::uri_template::{TemplateValue, UriTemplate};
+pub use crate::wire_log::WireLog;
+pub use crate::wire_tap::{Direction, WireTap};
 #[cfg(feature = "charsets")]
 pub use crate::{charsets::Charset, parsing::TextReader};
 pub use http::Method;
 pub use http::StatusCode;
+pub use http::Version;
 
 pub mod header {
     //! This module is a re-export of the `http` crate's `header` module.
@@ -123,3 +211,28 @@ where
 {
     RequestBuilder::new(Method::TRACE, base_url)
 }
+
+/// Create a new `RequestBuilder` with the given method.
+///
+/// Any method can be used, including extension methods such as `PROPFIND`, which can be created with
+/// `Method::from_bytes`.
+///
+/// # Panics
+/// Panics if the base url is invalid or if the method is CONNECT.
+pub fn request<U>(method: Method, base_url: U) -> RequestBuilder
+where
+    U: AsRef<str>,
+{
+    RequestBuilder::new(method, base_url)
+}
+
+/// Send an `http::Request` and wait for the result.
+///
+/// This is useful to send requests built by code written against the types of the `http` crate. The URI of
+/// the request must be absolute.
+pub fn send<B>(request: http::Request<B>) -> Result<Response>
+where
+    B: Into<Vec<u8>>,
+{
+    RequestBuilder::try_from_http_request(request)?.send()
+}
@@ -0,0 +1,218 @@
+//! Parsing of media types, as found in the `Content-Type` header.
+
+use std::fmt;
+use std::path::Path;
+
+/// A media type, such as `text/html; charset=utf-8`, as described in RFC 7231.
+///
+/// The type, the subtype and the names of the parameters are case-insensitive and are kept in lowercase.
+/// The values of the parameters are unquoted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    essence: String,
+    slash: usize,
+    params: Vec<(String, String)>,
+}
+
+/// Split the value on the semicolons which are not inside a quoted string.
+fn split_params(value: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_string();
+    }
+    let mut unquoted = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+impl MediaType {
+    /// Parse a media type. `None` is returned if the type or the subtype is missing.
+    pub fn parse(value: &str) -> Option<MediaType> {
+        let parts = split_params(value);
+        let essence = parts[0].trim().to_ascii_lowercase();
+        let slash = essence.find('/')?;
+        if slash == 0 || slash == essence.len() - 1 || essence[slash + 1..].contains('/') {
+            return None;
+        }
+
+        let params = parts[1..]
+            .iter()
+            .filter_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                let name = name.trim().to_ascii_lowercase();
+                if name.is_empty() {
+                    return None;
+                }
+                Some((name, unquote(value.trim())))
+            })
+            .collect();
+
+        Some(MediaType { essence, slash, params })
+    }
+
+    /// Get the type and the subtype, without the parameters, such as `text/html`.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// Get the type, such as `text`.
+    pub fn type_(&self) -> &str {
+        &self.essence[..self.slash]
+    }
+
+    /// Get the subtype, such as `html`.
+    pub fn subtype(&self) -> &str {
+        &self.essence[self.slash + 1..]
+    }
+
+    /// Get the value of the parameter with the given name. The name is case-insensitive.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the parameters, in the order in which they were given.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Get the value of the `charset` parameter.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.essence)?;
+        for (name, value) in &self.params {
+            let is_token =
+                !value.is_empty() && value.bytes().all(|b| b.is_ascii_alphanumeric() || b"-._+".contains(&b));
+            if is_token {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(f, "; {}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Media types of common file extensions, sorted by extension.
+#[cfg(feature = "mime-guess")]
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("css", "text/css"),
+    ("csv", "text/csv"),
+    ("gif", "image/gif"),
+    ("gz", "application/gzip"),
+    ("htm", "text/html"),
+    ("html", "text/html"),
+    ("ico", "image/vnd.microsoft.icon"),
+    ("jpeg", "image/jpeg"),
+    ("jpg", "image/jpeg"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("md", "text/markdown"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("ogg", "audio/ogg"),
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("svg", "image/svg+xml"),
+    ("tar", "application/x-tar"),
+    ("txt", "text/plain"),
+    ("wasm", "application/wasm"),
+    ("wav", "audio/wav"),
+    ("webm", "video/webm"),
+    ("webp", "image/webp"),
+    ("xml", "application/xml"),
+    ("zip", "application/zip"),
+];
+
+/// Guess the media type of a file from the extension of its path.
+///
+/// `application/octet-stream` is returned for unknown extensions.
+#[cfg(feature = "mime-guess")]
+pub(crate) fn guess_media_type(path: &Path) -> &'static str {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| {
+            let extension = extension.to_ascii_lowercase();
+            EXTENSIONS
+                .binary_search_by(|(known, _)| (*known).cmp(extension.as_str()))
+                .ok()
+        })
+        .map_or("application/octet-stream", |index| EXTENSIONS[index].1)
+}
+
+#[cfg(not(feature = "mime-guess"))]
+pub(crate) fn guess_media_type(_: &Path) -> &'static str {
+    "application/octet-stream"
+}
+
+#[test]
+fn test_parse() {
+    let media_type = MediaType::parse("Text/HTML; Charset=\"UTF-8\"; q=\"a;b\\\"c\"").unwrap();
+    assert_eq!(media_type.essence(), "text/html");
+    assert_eq!(media_type.type_(), "text");
+    assert_eq!(media_type.subtype(), "html");
+    assert_eq!(media_type.charset(), Some("UTF-8"));
+    assert_eq!(media_type.param("Q"), Some("a;b\"c"));
+    assert_eq!(media_type.to_string(), "text/html; charset=UTF-8; q=\"a;b\\\"c\"");
+
+    let media_type = MediaType::parse("application/json").unwrap();
+    assert_eq!(media_type.params(), &[]);
+    assert_eq!(media_type.charset(), None);
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert_eq!(MediaType::parse(""), None);
+    assert_eq!(MediaType::parse("text"), None);
+    assert_eq!(MediaType::parse("text/"), None);
+    assert_eq!(MediaType::parse("/html"), None);
+    assert_eq!(MediaType::parse("text/html/x"), None);
+}
+
+#[test]
+#[cfg(feature = "mime-guess")]
+fn test_guess_media_type() {
+    assert!(EXTENSIONS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(guess_media_type(Path::new("photos/beach.JPG")), "image/jpeg");
+    assert_eq!(guess_media_type(Path::new("data.json")), "application/json");
+    assert_eq!(
+        guess_media_type(Path::new("archive.unknown")),
+        "application/octet-stream"
+    );
+    assert_eq!(guess_media_type(Path::new("README")), "application/octet-stream");
+}
@@ -0,0 +1,30 @@
+//! Hooks called around the requests of a `Session`.
+
+use std::fmt::Debug;
+
+use crate::error::Result;
+use crate::parsing::Response;
+use crate::request::PreparedRequest;
+
+/// Hooks called before a request is sent and after its response is received, added to a `Session` with
+/// `Session::middleware`.
+///
+/// A `Middleware` can modify the requests, for instance to add authentication headers, inspect or modify
+/// the responses, or record metrics. An error returned by a hook fails the request with this error.
+///
+/// The `before_send` hooks are called in the order the middlewares were added, once per request, before the
+/// redirections are followed. The `after_receive` hooks are called in the reverse order with the final
+/// response, before its body is read. Both hooks do nothing by default.
+pub trait Middleware: Debug + Send + Sync {
+    /// Called before the request is sent.
+    fn before_send(&self, request: &mut PreparedRequest) -> Result {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Called after the head of the response is received.
+    fn after_receive(&self, response: &mut Response) -> Result {
+        let _ = response;
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+//! Encoding of `multipart/form-data` request bodies, as described in RFC 7578.
+
+use std::collections::hash_map::RandomState;
+use std::fs::File;
+use std::hash::BuildHasher;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use http::header::HeaderValue;
+
+use crate::error::Result;
+use crate::media_type::guess_media_type;
+
+static BOUNDARY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Generate a boundary which is very unlikely to appear in the contents of the parts.
+fn generate_boundary() -> String {
+    // Each `RandomState` is seeded with random keys, which makes the hashes unpredictable.
+    let count = BOUNDARY_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let now = SystemTime::now();
+    let mut halves = [0u64; 2];
+    for half in halves.iter_mut() {
+        *half = RandomState::new().hash_one((count, now));
+    }
+    format!("------------------------{:016x}{:016x}", halves[0], halves[1])
+}
+
+/// Quote a name or a file name in the `Content-Disposition` header of a part, as browsers do.
+fn quote(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+#[derive(Clone)]
+enum Content {
+    Bytes(Vec<u8>),
+    File(PathBuf, u64),
+}
+
+/// A part of a `Multipart` body.
+///
+/// The contents of a part can be text, bytes or a file. Files are only opened when the request is sent, and
+/// are streamed rather than loaded in memory.
+#[derive(Clone)]
+pub struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<HeaderValue>,
+    content: Content,
+}
+
+impl Part {
+    /// Create a text field with the given name and value.
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Part {
+        Part {
+            name: name.into(),
+            file_name: None,
+            content_type: None,
+            content: Content::Bytes(value.into().into_bytes()),
+        }
+    }
+
+    /// Create a part with the given name, containing bytes.
+    ///
+    /// The `Content-Type` of the part is `application/octet-stream` unless it is set with `content_type`.
+    pub fn bytes(name: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Part {
+        Part {
+            name: name.into(),
+            file_name: None,
+            content_type: Some(HeaderValue::from_static("application/octet-stream")),
+            content: Content::Bytes(bytes.into()),
+        }
+    }
+
+    /// Create a part with the given name, containing the file at the given path.
+    ///
+    /// The file name of the part is the last component of the path, and its `Content-Type` is guessed from
+    /// the extension of the path when the `mime-guess` feature is enabled, `application/octet-stream`
+    /// otherwise, unless they are set with `file_name` and `content_type`. An error is
+    /// returned if the metadata of the file cannot be read. The length of the file is read when the part is
+    /// created, the file must not be shorter when the request is sent.
+    pub fn file(name: impl Into<String>, path: impl AsRef<Path>) -> Result<Part> {
+        let path = path.as_ref();
+        let len = path.metadata()?.len();
+        Ok(Part {
+            name: name.into(),
+            file_name: path.file_name().map(|name| name.to_string_lossy().into_owned()),
+            content_type: Some(HeaderValue::from_static(guess_media_type(path))),
+            content: Content::File(path.to_path_buf(), len),
+        })
+    }
+
+    /// Set the file name of this part.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Part {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// Set the `Content-Type` of this part.
+    ///
+    /// An error is returned if the value is not a valid header value.
+    pub fn content_type(mut self, content_type: &str) -> Result<Part> {
+        self.content_type = Some(HeaderValue::from_str(content_type).map_err(http::Error::from)?);
+        Ok(self)
+    }
+
+    fn head(&self, boundary: &str) -> Vec<u8> {
+        let mut head = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            boundary,
+            quote(&self.name)
+        )
+        .into_bytes();
+        if let Some(file_name) = &self.file_name {
+            head.extend_from_slice(format!("; filename=\"{}\"", quote(file_name)).as_bytes());
+        }
+        if let Some(content_type) = &self.content_type {
+            head.extend_from_slice(b"\r\nContent-Type: ");
+            head.extend_from_slice(content_type.as_bytes());
+        }
+        head.extend_from_slice(b"\r\n\r\n");
+        head
+    }
+
+    fn content_len(&self) -> u64 {
+        match &self.content {
+            Content::Bytes(bytes) => bytes.len() as u64,
+            Content::File(_, len) => *len,
+        }
+    }
+}
+
+/// A `multipart/form-data` request body, made of text fields and files.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> attohttpc::Result {
+/// use attohttpc::{Multipart, Part};
+///
+/// let form = Multipart::new()
+///     .text("title", "Holidays")
+///     .part(Part::file("photo", "beach.jpg")?.content_type("image/jpeg")?);
+/// let resp = attohttpc::post("https://example.com/upload").multipart(form).send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Default for Multipart {
+    fn default() -> Multipart {
+        Multipart::new()
+    }
+}
+
+impl Multipart {
+    /// Create a new, empty `Multipart` body with a randomly generated boundary.
+    pub fn new() -> Multipart {
+        Multipart {
+            boundary: generate_boundary(),
+            parts: Vec::new(),
+        }
+    }
+
+    /// Get the boundary which separates the parts of this body.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Add a text field with the given name and value.
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Multipart {
+        self.part(Part::text(name, value))
+    }
+
+    /// Add a part with the given name, containing the file at the given path.
+    ///
+    /// See `Part::file` for the details.
+    pub fn file(self, name: impl Into<String>, path: impl AsRef<Path>) -> Result<Multipart> {
+        Ok(self.part(Part::file(name, path)?))
+    }
+
+    /// Add a part.
+    pub fn part(mut self, part: Part) -> Multipart {
+        self.parts.push(part);
+        self
+    }
+
+    /// Get the value of the `Content-Type` header of this body.
+    pub(crate) fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    fn tail(&self) -> Vec<u8> {
+        format!("--{}--\r\n", self.boundary).into_bytes()
+    }
+
+    /// Get the length of the encoded body.
+    pub(crate) fn len(&self) -> u64 {
+        let parts: u64 = self
+            .parts
+            .iter()
+            .map(|part| part.head(&self.boundary).len() as u64 + part.content_len() + 2)
+            .sum();
+        parts + self.tail().len() as u64
+    }
+
+    /// Get a reader of the encoded body, which opens the files of the parts as they are reached.
+    pub(crate) fn reader(&self) -> MultipartReader<'_> {
+        MultipartReader {
+            multipart: self,
+            index: 0,
+            state: State::Head(Cursor::new(Vec::new())),
+        }
+    }
+}
+
+enum State<'a> {
+    Head(Cursor<Vec<u8>>),
+    Bytes(&'a [u8]),
+    File(File, u64),
+    Tail(Cursor<Vec<u8>>),
+}
+
+/// Reader of the encoding of a `Multipart` body.
+pub(crate) struct MultipartReader<'a> {
+    multipart: &'a Multipart,
+    // Index of the next part to read.
+    index: usize,
+    state: State<'a>,
+}
+
+impl MultipartReader<'_> {
+    /// Move on to the next section of the body. Returns false at the end of the body.
+    fn advance(&mut self) -> io::Result<bool> {
+        self.state = match &self.state {
+            State::Head(_) if self.index > 0 => {
+                let part = &self.multipart.parts[self.index - 1];
+                match &part.content {
+                    Content::Bytes(bytes) => State::Bytes(bytes),
+                    Content::File(path, len) => State::File(File::open(path)?, *len),
+                }
+            }
+            State::Head(_) | State::Bytes(_) | State::File(..) => {
+                let separator: &[u8] = if self.index > 0 { b"\r\n" } else { b"" };
+                match self.multipart.parts.get(self.index) {
+                    Some(part) => {
+                        self.index += 1;
+                        let mut head = separator.to_vec();
+                        head.extend_from_slice(&part.head(&self.multipart.boundary));
+                        State::Head(Cursor::new(head))
+                    }
+                    None => {
+                        let mut tail = separator.to_vec();
+                        tail.extend_from_slice(&self.multipart.tail());
+                        State::Tail(Cursor::new(tail))
+                    }
+                }
+            }
+            State::Tail(_) => return Ok(false),
+        };
+        Ok(true)
+    }
+}
+
+impl Read for MultipartReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = match &mut self.state {
+                State::Head(cursor) | State::Tail(cursor) => cursor.read(buf)?,
+                State::Bytes(bytes) => bytes.read(buf)?,
+                State::File(_, 0) => 0,
+                State::File(file, remaining) => {
+                    let max = buf.len().min(*remaining as usize);
+                    match file.read(&mut buf[..max])? {
+                        0 => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "file of a multipart body is shorter than its length",
+                            ))
+                        }
+                        n => {
+                            *remaining -= n as u64;
+                            n
+                        }
+                    }
+                }
+            };
+            if n > 0 || !self.advance()? {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Read;
+
+    use super::{Multipart, Part};
+
+    fn encode(multipart: &Multipart) -> String {
+        let mut body = String::new();
+        multipart.reader().read_to_string(&mut body).unwrap();
+        assert_eq!(body.len() as u64, multipart.len());
+        body
+    }
+
+    #[test]
+    fn test_boundary() {
+        let a = Multipart::new();
+        let b = Multipart::new();
+        assert_ne!(a.boundary(), b.boundary());
+        assert_eq!(a.boundary().len(), 56);
+        assert!(a.content_type().ends_with(a.boundary()));
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut multipart = Multipart::new();
+        multipart.boundary = "b".into();
+        assert_eq!(encode(&multipart), "--b--\r\n");
+    }
+
+    #[test]
+    fn test_encode() {
+        let path = std::env::temp_dir().join(format!("attohttpc-multipart-{}.txt", std::process::id()));
+        fs::write(&path, "file contents").unwrap();
+
+        let mut multipart = Multipart::new()
+            .text("title", "hello")
+            .part(Part::bytes("data", &b"\x00\x01"[..]).file_name("a\"b.bin"))
+            .part(
+                Part::file("upload", &path)
+                    .unwrap()
+                    .file_name("notes.txt")
+                    .content_type("text/plain")
+                    .unwrap(),
+            );
+        multipart.boundary = "b".into();
+        let body = encode(&multipart);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            body,
+            "--b\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nhello\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"data\"; filename=\"a%22b.bin\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n\x00\x01\r\n\
+             --b\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nfile contents\r\n\
+             --b--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_content_type() {
+        assert!(Part::text("a", "b")
+            .content_type("text/plain\r\nX-Injected: 1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(Part::file("a", "/nonexistent/attohttpc/file").is_err());
+    }
+}
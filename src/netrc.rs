@@ -0,0 +1,153 @@
+//! Parsing of `.netrc` files, used to find credentials for a host.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Credentials found in a `.netrc` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetrcEntry {
+    pub login: String,
+    pub password: Option<String>,
+}
+
+/// Parsed content of a `.netrc` file.
+#[derive(Debug, Default)]
+pub struct Netrc {
+    machines: Vec<(String, NetrcEntry)>,
+    default: Option<NetrcEntry>,
+}
+
+impl Netrc {
+    /// Read the `.netrc` file of the current user.
+    ///
+    /// The `NETRC` environment variable can be used to override the location of the file.
+    pub fn load() -> Option<Netrc> {
+        let path = netrc_path()?;
+        let content = fs::read_to_string(&path).ok()?;
+        debug!("loaded netrc file from {}", path.display());
+        Some(Netrc::parse(&content))
+    }
+
+    /// Parse the content of a `.netrc` file.
+    ///
+    /// Unknown tokens are ignored, and macro definitions are skipped.
+    pub fn parse(content: &str) -> Netrc {
+        let mut netrc = Netrc::default();
+        // Machine being defined. `None` as the name means the default entry.
+        let mut current: Option<(Option<String>, NetrcEntry)> = None;
+        let mut lines = content.lines();
+
+        while let Some(line) = lines.next() {
+            let mut tokens = line.split_whitespace();
+            while let Some(token) = tokens.next() {
+                match token {
+                    "machine" | "default" => {
+                        if let Some(entry) = current.take() {
+                            netrc.push(entry);
+                        }
+                        let name = if token == "machine" {
+                            match tokens.next() {
+                                Some(name) => Some(name.to_string()),
+                                None => break,
+                            }
+                        } else {
+                            None
+                        };
+                        current = Some((
+                            name,
+                            NetrcEntry {
+                                login: String::new(),
+                                password: None,
+                            },
+                        ));
+                    }
+                    "login" => {
+                        if let (Some((_, entry)), Some(login)) = (current.as_mut(), tokens.next()) {
+                            entry.login = login.to_string();
+                        }
+                    }
+                    "password" => {
+                        if let (Some((_, entry)), Some(password)) = (current.as_mut(), tokens.next()) {
+                            entry.password = Some(password.to_string());
+                        }
+                    }
+                    "account" => {
+                        tokens.next();
+                    }
+                    "macdef" => {
+                        // A macro definition ends with an empty line.
+                        for line in lines.by_ref() {
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(entry) = current.take() {
+            netrc.push(entry);
+        }
+
+        netrc
+    }
+
+    fn push(&mut self, (name, entry): (Option<String>, NetrcEntry)) {
+        match name {
+            Some(name) => self.machines.push((name, entry)),
+            None => self.default = Some(entry),
+        }
+    }
+
+    /// Find the credentials for the given host, falling back to the default entry.
+    pub fn find(&self, host: &str) -> Option<&NetrcEntry> {
+        self.machines
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host))
+            .map(|(_, entry)| entry)
+            .or(self.default.as_ref())
+    }
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC") {
+        return Some(PathBuf::from(path));
+    }
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    let home = PathBuf::from(home);
+    let path = home.join(".netrc");
+    if cfg!(windows) && !path.exists() {
+        return Some(home.join("_netrc"));
+    }
+    Some(path)
+}
+
+#[test]
+fn test_parse_single_line() {
+    let netrc = Netrc::parse("machine foo.bar login user password hunter2");
+    assert_eq!(
+        netrc.find("foo.bar"),
+        Some(&NetrcEntry {
+            login: "user".into(),
+            password: Some("hunter2".into()),
+        })
+    );
+    assert_eq!(netrc.find("qux.bar"), None);
+}
+
+#[test]
+fn test_parse_multi_line_and_default() {
+    let netrc = Netrc::parse(
+        "machine foo.bar\n  login user\n  account acc\n  password hunter2\n\nmacdef init\ncd /\nmachine fake.bar\n\ndefault login anonymous password me@foo.bar\n",
+    );
+    assert_eq!(netrc.find("FOO.bar").map(|e| e.login.as_str()), Some("user"));
+    assert_eq!(netrc.find("fake.bar").map(|e| e.login.as_str()), Some("anonymous"));
+    assert_eq!(
+        netrc.find("qux.bar").and_then(|e| e.password.as_deref()),
+        Some("me@foo.bar")
+    );
+}
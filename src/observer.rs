@@ -0,0 +1,150 @@
+//! Observation of the progress of the requests.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+/// An event in the progress of a request, given to an `Observer`.
+///
+/// The durations of the name resolution, of the connection and of the TLS handshake are the durations of
+/// these steps. The durations of the other events are measured from the moment the request started to be
+/// written. The resolution, connection and handshake events are not emitted when a connection is reused
+/// from the pool. When a proxy is used, they concern the proxy as well.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// The resolution of a host name started.
+    DnsStart {
+        /// The host name.
+        host: &'a str,
+    },
+    /// The resolution of a host name succeeded.
+    DnsEnd {
+        /// The host name.
+        host: &'a str,
+        /// The addresses found.
+        addrs: &'a [SocketAddr],
+        /// The duration of the resolution.
+        elapsed: Duration,
+    },
+    /// A connection to an address started.
+    ConnectStart {
+        /// The address.
+        addr: SocketAddr,
+    },
+    /// A connection to an address ended.
+    ConnectEnd {
+        /// The address.
+        addr: SocketAddr,
+        /// The error which occurred, if the connection failed.
+        error: Option<&'a io::Error>,
+        /// The duration of the connection.
+        elapsed: Duration,
+    },
+    /// A TLS handshake started.
+    TlsHandshakeStart {
+        /// The host name of the server.
+        host: &'a str,
+    },
+    /// A TLS handshake succeeded.
+    TlsHandshakeEnd {
+        /// The host name of the server.
+        host: &'a str,
+        /// The duration of the handshake.
+        elapsed: Duration,
+    },
+    /// The request, including its body, was written.
+    RequestWritten {
+        /// The URL of the request.
+        url: &'a Url,
+        /// The time spent writing the request.
+        elapsed: Duration,
+    },
+    /// The first byte of the response was received.
+    FirstByte {
+        /// The URL of the request.
+        url: &'a Url,
+        /// The time elapsed since the request started to be written.
+        elapsed: Duration,
+    },
+    /// The body of the response was read entirely.
+    BodyComplete {
+        /// The URL of the request.
+        url: &'a Url,
+        /// The number of bytes of the body, after decompression.
+        bytes: u64,
+        /// The time elapsed since the request started to be written.
+        elapsed: Duration,
+    },
+}
+
+type ObserverFn = dyn Fn(&Event) + Send + Sync;
+
+/// A callback which receives the events in the progress of the requests, such as the end of a connection or
+/// the first byte of a response, with their timings.
+///
+/// This is useful to diagnose where slow requests spend their time. The callback is called from the thread
+/// performing the request, and should not block. See `Event` for the list of the events.
+///
+/// Cloning an `Observer` is cheap, the clones share the same callback.
+#[derive(Clone)]
+pub struct Observer(Arc<ObserverFn>);
+
+impl Observer {
+    /// Create a new `Observer` calling the given function.
+    pub fn new<F>(f: F) -> Observer
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        Observer(Arc::new(f))
+    }
+
+    pub(crate) fn notify(&self, event: Event) {
+        (self.0)(&event);
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// Counts the bytes of a response body, to notify the observer when the body was read entirely.
+pub(crate) struct BodyObserver {
+    observer: Observer,
+    url: Url,
+    started: Instant,
+    bytes: u64,
+    done: bool,
+}
+
+impl BodyObserver {
+    pub(crate) fn new(observer: Observer, url: Url, started: Instant) -> BodyObserver {
+        BodyObserver {
+            observer,
+            url,
+            started,
+            bytes: 0,
+            done: false,
+        }
+    }
+
+    pub(crate) fn record(&mut self, n: usize) {
+        self.bytes += n as u64;
+    }
+
+    pub(crate) fn complete(&mut self) {
+        if !self.done {
+            self.done = true;
+            self.observer.notify(Event::BodyComplete {
+                url: &self.url,
+                bytes: self.bytes,
+                elapsed: self.started.elapsed(),
+            });
+        }
+    }
+}
@@ -12,7 +12,7 @@ pub use self::body_reader::BodyReader;
 pub use self::chunked_reader::ChunkedReader;
 pub use self::compressed_reader::CompressedReader;
 pub use self::length_reader::LengthReader;
-pub use self::response::{parse_response, parse_response_head, Response};
-pub use self::response_reader::ResponseReader;
+pub use self::response::{cached_response, parse_response_after_upload, Response};
+pub use self::response_reader::{Chunks, ResponseReader};
 #[cfg(feature = "charsets")]
 pub use self::text_reader::TextReader;
@@ -1,6 +1,7 @@
 use std::io::{self, BufReader, Read};
 
 use http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING};
+use http::{Method, StatusCode};
 
 use crate::error::{InvalidResponseKind, Result};
 use crate::parsing::{ChunkedReader, LengthReader};
@@ -16,13 +17,46 @@ impl Read for BodyReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
-            BodyReader::Chunked(r) => r.read(buf),
-            BodyReader::Length(r) => r.read(buf),
+            BodyReader::Chunked(r) => {
+                let n = r.read(buf)?;
+                if r.is_done() {
+                    release(r.get_mut());
+                }
+                Ok(n)
+            }
+            BodyReader::Length(r) => {
+                let n = r.read(buf)?;
+                if r.is_done() {
+                    release(r.get_mut());
+                }
+                Ok(n)
+            }
             BodyReader::Close(r) => r.read(buf),
         }
     }
 }
 
+/// Give the connection back to its pool once the body was read entirely.
+///
+/// The connection is not reused if the server sent more data than the body, since it cannot be the start
+/// of a response to another request.
+pub fn release(reader: &mut BufReader<BaseStream>) {
+    if reader.buffer().is_empty() {
+        reader.get_mut().release();
+    }
+}
+
+/// Check if a response with the given status to a request with the given method can have a body.
+///
+/// Responses to HEAD requests and responses with a 1xx, 204 or 304 status never have a body, whatever
+/// their Content-Length and Transfer-Encoding headers say.
+pub fn response_has_body(status: StatusCode, method: &Method) -> bool {
+    !(*method == Method::HEAD
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED)
+}
+
 fn is_chunked(headers: &HeaderMap) -> bool {
     headers
         .get_all(TRANSFER_ENCODING)
@@ -37,7 +71,7 @@ fn is_chunked(headers: &HeaderMap) -> bool {
 
 fn parse_content_length(val: &HeaderValue) -> Result<u64> {
     let val = val.to_str().map_err(|_| InvalidResponseKind::ContentLength)?;
-    let val: u64 = u64::from_str_radix(val, 10).map_err(|_| InvalidResponseKind::ContentLength)?;
+    let val: u64 = val.parse().map_err(|_| InvalidResponseKind::ContentLength)?;
     Ok(val)
 }
 
@@ -57,13 +91,27 @@ fn is_content_length(headers: &HeaderMap) -> Result<Option<u64>> {
 }
 
 impl BodyReader {
-    pub fn new(headers: &HeaderMap, reader: BufReader<BaseStream>) -> Result<BodyReader> {
-        if is_chunked(headers) {
+    pub fn new(
+        headers: &HeaderMap,
+        status: StatusCode,
+        method: &Method,
+        mut reader: BufReader<BaseStream>,
+    ) -> Result<BodyReader> {
+        if !response_has_body(status, method) {
+            // Nothing past the head is read, which leaves the stream at the start of the next response.
+            debug!("creating an empty body reader");
+            release(&mut reader);
+            Ok(BodyReader::Length(LengthReader::new(reader, 0)))
+        } else if is_chunked(headers) {
             debug!("creating a chunked body reader");
             Ok(BodyReader::Chunked(ChunkedReader::new(reader)))
         } else if let Some(val) = is_content_length(headers)? {
             debug!("creating a length body reader");
-            Ok(BodyReader::Length(LengthReader::new(reader, val)))
+            let mut reader = LengthReader::new(reader, val);
+            if reader.is_done() {
+                release(reader.get_mut());
+            }
+            Ok(BodyReader::Length(reader))
         } else {
             debug!("creating close reader");
             Ok(BodyReader::Close(reader))
@@ -71,6 +119,16 @@ impl BodyReader {
     }
 }
 
+#[test]
+fn test_response_has_body() {
+    assert!(response_has_body(StatusCode::OK, &Method::GET));
+    assert!(response_has_body(StatusCode::NOT_FOUND, &Method::POST));
+    assert!(!response_has_body(StatusCode::OK, &Method::HEAD));
+    assert!(!response_has_body(StatusCode::CONTINUE, &Method::GET));
+    assert!(!response_has_body(StatusCode::NO_CONTENT, &Method::DELETE));
+    assert!(!response_has_body(StatusCode::NOT_MODIFIED, &Method::GET));
+}
+
 #[test]
 fn test_is_chunked_false() {
     let mut headers = HeaderMap::new();
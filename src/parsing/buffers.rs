@@ -18,20 +18,6 @@ where
     Ok(n)
 }
 
-pub fn trim_byte(byte: u8, buf: &[u8]) -> &[u8] {
-    trim_byte_left(byte, trim_byte_right(byte, buf))
-}
-
-pub fn trim_byte_left(byte: u8, buf: &[u8]) -> &[u8] {
-    let n = buf.iter().take_while(|b| **b == byte).count();
-    &buf[n..]
-}
-
-pub fn trim_byte_right(byte: u8, buf: &[u8]) -> &[u8] {
-    let n = buf.iter().rev().take_while(|b| **b == byte).count();
-    &buf[..buf.len() - n]
-}
-
 #[test]
 fn test_read_line_lf() {
     let mut reader = BufReader::new(&b"hello\nworld"[..]);
@@ -73,24 +59,3 @@ fn test_read_line_empty_line() {
     assert_eq!(read_line(&mut reader, &mut line).ok(), Some(1));
     assert_eq!(line, b"");
 }
-
-#[test]
-fn test_trim_byte() {
-    assert_eq!(trim_byte(b' ', b"  hello  "), b"hello");
-    assert_eq!(trim_byte(b' ', b"hello"), b"hello");
-    assert_eq!(trim_byte(b' ', b""), b"");
-}
-
-#[test]
-fn test_trim_byte_left() {
-    assert_eq!(trim_byte_left(b' ', b"  hello"), b"hello");
-    assert_eq!(trim_byte_left(b' ', b"hello"), b"hello");
-    assert_eq!(trim_byte_left(b' ', b""), b"");
-}
-
-#[test]
-fn test_trim_byte_right() {
-    assert_eq!(trim_byte_right(b' ', b"hello  "), b"hello");
-    assert_eq!(trim_byte_right(b' ', b"hello"), b"hello");
-    assert_eq!(trim_byte_right(b' ', b""), b"");
-}
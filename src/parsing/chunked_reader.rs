@@ -1,7 +1,6 @@
 use std::cmp;
 use std::io::{self, BufReader, Read};
 use std::str;
-use std::u64;
 
 use crate::error::InvalidResponseKind;
 use crate::parsing::buffers;
@@ -24,6 +23,7 @@ where
     read: u64,                // bytes read in the chunk
     length: u64,              // chunk length
     line: Vec<u8>,
+    done: bool, // has received the EOF chunk
 }
 
 impl<R> ChunkedReader<R>
@@ -37,9 +37,19 @@ where
             read: 0,
             length: 0,
             line: Vec::new(),
+            done: false,
         }
     }
 
+    /// Check if the whole body was read, up to the EOF chunk.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn get_mut(&mut self) -> &mut BufReader<R> {
+        &mut self.inner
+    }
+
     #[inline]
     fn remaining(&self) -> u64 {
         self.length - self.read
@@ -58,6 +68,48 @@ where
         parse_chunk_size(&self.line)
     }
 
+    fn start_chunk(&mut self) -> io::Result<()> {
+        if self.is_expecting_chunk {
+            debug!("waiting, parsing new chunk size");
+            // If we're waiting for a new chunk, we read a line and parse the number as hexadecimal.
+            self.read = 0;
+            self.length = self.read_chunk_size()?;
+            // If the chunk's length is 0, we've received the EOF chunk.
+            if self.length == 0 {
+                debug!("received EOF chunk");
+                // Read CRLF
+                self.read_empty_line()?;
+                self.done = true;
+            }
+            self.is_expecting_chunk = false;
+        }
+        Ok(())
+    }
+
+    /// Read the rest of the current chunk, or the next chunk if the current one is finished.
+    ///
+    /// Returns `None` once the EOF chunk has been received.
+    pub fn read_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.start_chunk()?;
+        if self.length == 0 {
+            return Ok(None);
+        }
+
+        // The chunk is read through `take` so that a bogus chunk size does not cause a huge allocation.
+        let mut chunk = Vec::new();
+        let remaining = self.remaining();
+        let n = (&mut self.inner).take(remaining).read_to_end(&mut chunk)?;
+        self.read += n as u64;
+        if self.remaining() != 0 {
+            self.length = 0;
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.read_empty_line()?;
+        self.is_expecting_chunk = true;
+        Ok(Some(chunk))
+    }
+
     fn read_empty_line(&mut self) -> io::Result<()> {
         let n = self.read_line()?;
         if n == 0 || !self.line.is_empty() {
@@ -73,19 +125,7 @@ where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.is_expecting_chunk {
-            debug!("waiting, parsing new chunk size");
-            // If we're waiting for a new chunk, we read a line and parse the number as hexadecimal.
-            self.read = 0;
-            self.length = self.read_chunk_size()?;
-            // If the chunk's length is 0, we've received the EOF chunk.
-            if self.length == 0 {
-                debug!("received EOF chunk");
-                // Read CRLF
-                self.read_empty_line()?;
-            }
-            self.is_expecting_chunk = false;
-        }
+        self.start_chunk()?;
 
         // If we have a length of 0 for a chunk, we've reached EOF.
         if self.length == 0 {
@@ -184,3 +224,23 @@ fn test_read_invalid_bad_terminating_chunk() {
         io::ErrorKind::Other
     );
 }
+
+#[test]
+fn test_read_chunk() {
+    let msg = b"4\r\nwiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n";
+    let mut reader = ChunkedReader::new(BufReader::new(&msg[..]));
+    let mut buf = [0; 2];
+    assert_eq!(reader.read(&mut buf).unwrap(), 2);
+    assert_eq!(reader.read_chunk().unwrap().unwrap(), b"ki");
+    assert_eq!(reader.read_chunk().unwrap().unwrap(), b"pedia");
+    assert_eq!(reader.read_chunk().unwrap().unwrap(), b" in\r\n\r\nchunks.");
+    assert!(reader.read_chunk().unwrap().is_none());
+    assert!(reader.read_chunk().unwrap().is_none());
+}
+
+#[test]
+fn test_read_chunk_truncated() {
+    let msg = b"4\r\nwik";
+    let mut reader = ChunkedReader::new(BufReader::new(&msg[..]));
+    assert_eq!(reader.read_chunk().unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -1,93 +1,338 @@
-#[cfg(feature = "compress")]
-use std::io::BufReader;
 use std::io::{self, Read};
-
-use http::header::HeaderMap;
 #[cfg(feature = "compress")]
-use http::header::{CONTENT_ENCODING, TRANSFER_ENCODING};
+use std::io::{BufRead, BufReader};
 #[cfg(feature = "compress")]
-use http::Method;
+use std::mem;
+
+#[cfg(feature = "compress")]
+use http::header::TRANSFER_ENCODING;
+use http::header::{HeaderMap, CONTENT_ENCODING};
+use http::StatusCode;
 #[cfg(feature = "compress")]
 use libflate::{deflate, gzip};
 
-use crate::error::Result;
-use crate::parsing::body_reader::BodyReader;
+use crate::error::{InvalidResponseKind, Result};
+use crate::parsing::body_reader::{response_has_body, BodyReader};
 use crate::request::PreparedRequest;
 
+// The decoders wrap the reader they decode in a box, which leaves the plain reader as the largest variant. It is
+// the most common one, so it is not boxed.
+#[allow(clippy::large_enum_variant)]
 pub enum CompressedReader {
     Plain(BodyReader),
     #[cfg(feature = "compress")]
-    // The BodyReader needs to be wrapped in a BufReader because libflate reads one byte at a time.
-    Deflate(deflate::Decoder<BufReader<BodyReader>>),
+    // The inner reader needs to be wrapped in a BufReader because libflate reads one byte at a time.
+    Deflate(deflate::Decoder<BufReader<Box<CompressedReader>>>),
     #[cfg(feature = "compress")]
-    // The BodyReader needs to be wrapped in a BufReader because libflate reads one byte at a time.
-    Gzip(gzip::Decoder<BufReader<BodyReader>>),
+    Gzip(GzipDecoder),
+    #[cfg(feature = "compress-br")]
+    // The brotli decoder holds its state inline, it is boxed to keep the other variants small.
+    Brotli(Box<brotli_decompressor::Decompressor<Box<CompressedReader>>>),
 }
 
-#[cfg(feature = "compress")]
-fn have_encoding_item(value: &str, enc: &str) -> bool {
-    value.split(",").map(|s| s.trim()).any(|s| s.eq_ignore_ascii_case(enc))
+/// Size of the input buffer of the brotli decoder.
+#[cfg(feature = "compress-br")]
+const BROTLI_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Content codings which can be decoded.
+#[cfg(feature = "compress-br")]
+const SUPPORTED_ENCODINGS: &[&str] = &["identity", "gzip", "deflate", "br"];
+#[cfg(all(feature = "compress", not(feature = "compress-br")))]
+const SUPPORTED_ENCODINGS: &[&str] = &["identity", "gzip", "deflate"];
+#[cfg(not(feature = "compress"))]
+const SUPPORTED_ENCODINGS: &[&str] = &["identity"];
+
+/// Map the deprecated aliases of the content codings to their canonical names.
+fn canonical_encoding(enc: &str) -> &str {
+    if enc.eq_ignore_ascii_case("x-gzip") {
+        "gzip"
+    } else if enc.eq_ignore_ascii_case("x-compress") {
+        "compress"
+    } else {
+        enc
+    }
+}
+
+fn encoding_items(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(|s| canonical_encoding(s.trim()))
+        .filter(|s| !s.is_empty())
 }
 
+/// Get the codings applied to the body, in the order in which they were applied: the content codings, then
+/// the transfer codings other than `chunked`.
 #[cfg(feature = "compress")]
-fn have_encoding_content_encoding(headers: &HeaderMap, enc: &str) -> bool {
+fn body_codings(headers: &HeaderMap) -> Vec<&str> {
     headers
         .get_all(CONTENT_ENCODING)
         .into_iter()
+        .chain(headers.get_all(TRANSFER_ENCODING))
         .filter_map(|val| val.to_str().ok())
-        .any(|val| have_encoding_item(val, enc))
+        .flat_map(encoding_items)
+        .filter(|s| !s.eq_ignore_ascii_case("identity") && !s.eq_ignore_ascii_case("chunked"))
+        .collect()
 }
 
+/// Check if the given coding can be decoded and is one of the encodings accepted by the request.
 #[cfg(feature = "compress")]
-fn have_encoding_transfer_encoding(headers: &HeaderMap, enc: &str) -> bool {
+fn is_accepted_encoding(request: &PreparedRequest, enc: &str) -> bool {
+    let accepted = match &request.settings.allowed_encodings {
+        Some(allowed) => allowed
+            .iter()
+            .any(|e| canonical_encoding(e.trim()).eq_ignore_ascii_case(enc)),
+        None => true,
+    };
+    accepted && SUPPORTED_ENCODINGS.iter().any(|e| enc.eq_ignore_ascii_case(e))
+}
+
+/// Check if the Content-Encoding header contains a coding which cannot be decoded.
+fn have_unknown_encoding(headers: &HeaderMap) -> bool {
     headers
-        .get_all(TRANSFER_ENCODING)
+        .get_all(CONTENT_ENCODING)
         .into_iter()
-        .filter_map(|val| val.to_str().ok())
-        .any(|val| have_encoding_item(val, enc))
+        .any(|val| match val.to_str() {
+            Ok(val) => encoding_items(val).any(|s| !SUPPORTED_ENCODINGS.iter().any(|e| s.eq_ignore_ascii_case(e))),
+            Err(_) => true,
+        })
 }
 
-#[cfg(feature = "compress")]
-fn have_encoding(headers: &HeaderMap, enc: &str) -> bool {
-    have_encoding_content_encoding(headers, enc) || have_encoding_transfer_encoding(headers, enc)
+/// Check if the response has a body to decode.
+///
+/// Responses which cannot have a body, and bodies known to be empty from their `Content-Length`, are not decoded
+/// whatever their codings. Some proxies send a `Content-Encoding` header along with an empty body.
+fn has_body_to_decode(status: StatusCode, request: &PreparedRequest, reader: &BodyReader) -> bool {
+    response_has_body(status, request.method()) && !matches!(reader, BodyReader::Length(r) if r.length() == 0)
+}
+
+/// Check if the body of the response must be passed through without decoding, according to the
+/// `strict_content_encoding` setting of the request.
+fn check_unknown_encoding(headers: &HeaderMap, has_body: bool, request: &PreparedRequest) -> Result<bool> {
+    if !has_body || !have_unknown_encoding(headers) {
+        return Ok(false);
+    }
+    if request.settings.strict_content_encoding {
+        return Err(InvalidResponseKind::ContentEncoding.into());
+    }
+    debug!("unknown content encoding, passing the body through");
+    Ok(true)
 }
 
 impl CompressedReader {
     #[cfg(feature = "compress")]
-    pub fn new(headers: &HeaderMap, request: &PreparedRequest, reader: BodyReader) -> Result<CompressedReader> {
-        if request.method() != Method::HEAD {
-            if have_encoding(headers, "gzip") {
-                // There's an issue when a Content-Encoding of Transfer-Encoding header are present and the body
-                // is empty, because the gzip decoder tries to read the header eagerly.
-                debug!("creating gzip decoder");
-                return Ok(CompressedReader::Gzip(gzip::Decoder::new(BufReader::new(reader))?));
-            }
-
-            if have_encoding(headers, "deflate") {
-                debug!("creating deflate decoder");
-                return Ok(CompressedReader::Deflate(deflate::Decoder::new(BufReader::new(reader))));
+    pub fn new(
+        headers: &HeaderMap,
+        status: StatusCode,
+        request: &PreparedRequest,
+        mut reader: BodyReader,
+    ) -> Result<CompressedReader> {
+        if !request.settings.auto_decompress {
+            debug!("decompression disabled, creating plain reader");
+            return Ok(CompressedReader::Plain(reader));
+        }
+        let has_body = has_body_to_decode(status, request, &reader);
+        if has_body && !check_unknown_encoding(headers, has_body, request)? {
+            // The body is only decoded when all of its codings can be, otherwise it is passed through as is.
+            let codings = body_codings(headers);
+            if !codings.is_empty() && codings.iter().all(|enc| is_accepted_encoding(request, enc)) {
+                // An empty body is not a valid gzip stream, which needs at least a header.
+                if codings[codings.len() - 1].eq_ignore_ascii_case("gzip") && is_empty_body(&mut reader)? {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "empty gzip body").into());
+                }
+                let mut reader = CompressedReader::Plain(reader);
+                for enc in codings.iter().rev() {
+                    reader = reader.decode(enc);
+                }
+                return Ok(reader);
             }
         }
         debug!("creating plain reader");
-        return Ok(CompressedReader::Plain(reader));
+        Ok(CompressedReader::Plain(reader))
     }
 
     #[cfg(not(feature = "compress"))]
-    pub fn new(_: &HeaderMap, _: &PreparedRequest, reader: BodyReader) -> Result<CompressedReader> {
+    pub fn new(
+        headers: &HeaderMap,
+        status: StatusCode,
+        request: &PreparedRequest,
+        reader: BodyReader,
+    ) -> Result<CompressedReader> {
+        check_unknown_encoding(headers, has_body_to_decode(status, request, &reader), request)?;
         Ok(CompressedReader::Plain(reader))
     }
+
+    /// Wrap this reader in a decoder of the given coding, which must be supported.
+    #[cfg(feature = "compress")]
+    fn decode(self, enc: &str) -> CompressedReader {
+        let inner = Box::new(self);
+        #[cfg(feature = "compress-br")]
+        {
+            if enc.eq_ignore_ascii_case("br") {
+                debug!("creating brotli decoder");
+                return CompressedReader::Brotli(Box::new(brotli_decompressor::Decompressor::new(
+                    inner,
+                    BROTLI_BUFFER_SIZE,
+                )));
+            }
+        }
+        if enc.eq_ignore_ascii_case("gzip") {
+            debug!("creating gzip decoder");
+            CompressedReader::Gzip(GzipDecoder::new(BufReader::new(inner)))
+        } else {
+            debug!("creating deflate decoder");
+            CompressedReader::Deflate(deflate::Decoder::new(BufReader::new(inner)))
+        }
+    }
+
+    /// Get the reader of the body as it was sent, without decoding it.
+    ///
+    /// Nothing must have been read yet: the decoders only read the body once data is requested, so it is
+    /// still untouched.
+    pub fn into_raw(self) -> BodyReader {
+        match self {
+            CompressedReader::Plain(reader) => reader,
+            #[cfg(feature = "compress")]
+            CompressedReader::Deflate(decoder) => unbuffer(decoder.into_inner()).into_raw(),
+            #[cfg(feature = "compress")]
+            CompressedReader::Gzip(decoder) => unbuffer(decoder.into_inner()).into_raw(),
+            #[cfg(feature = "compress-br")]
+            CompressedReader::Brotli(decoder) => decoder.into_inner().into_raw(),
+        }
+    }
+
+    /// Get the number of bytes which will be read, if it is known: the body has a `Content-Length` and is
+    /// not decoded.
+    pub fn content_length(&self) -> Option<u64> {
+        match self {
+            CompressedReader::Plain(BodyReader::Length(reader)) => Some(reader.length()),
+            _ => None,
+        }
+    }
+
+    /// Check if the body is decompressed.
+    pub fn is_decoded(&self) -> bool {
+        !matches!(self, CompressedReader::Plain(_))
+    }
+}
+
+/// Check if the body is empty, without consuming it. The length of a chunked body is not known in advance.
+#[cfg(feature = "compress")]
+fn is_empty_body(reader: &mut BodyReader) -> io::Result<bool> {
+    match reader {
+        BodyReader::Close(reader) => Ok(reader.fill_buf()?.is_empty()),
+        BodyReader::Length(reader) => Ok(reader.length() == 0),
+        BodyReader::Chunked(_) => Ok(false),
+    }
+}
+
+/// Get the reader wrapped by a decoder which did not read anything yet.
+#[cfg(feature = "compress")]
+fn unbuffer(reader: BufReader<Box<CompressedReader>>) -> CompressedReader {
+    debug_assert!(reader.buffer().is_empty());
+    *reader.into_inner()
+}
+
+/// Read the rest of the body once the decoder is done.
+///
+/// The decoders stop at the end of the compressed stream, which leaves the end of the body unread, and the
+/// connection could not be reused.
+#[cfg(feature = "compress")]
+fn finish<R: Read>(n: usize, inner: &mut R, buf: &[u8]) -> io::Result<usize> {
+    if n == 0 && !buf.is_empty() {
+        io::copy(inner, &mut io::sink())?;
+    }
+    Ok(n)
+}
+
+/// The first bytes of a gzip member.
+#[cfg(feature = "compress")]
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decoder of gzip bodies, which can contain several members, as produced by concatenating gzip files.
+///
+/// Each member is read up to the end of its trailer, whose CRC is verified. Once the last member is decoded,
+/// the rest of the body is read and ignored, as the `gzip` tool does with trailing garbage, so that the
+/// connection can be reused. The header of a member is only read once data is requested, which leaves the
+/// body untouched until then.
+#[cfg(feature = "compress")]
+pub struct GzipDecoder {
+    state: GzipState,
+}
+
+// The inner reader needs to be wrapped in a BufReader because libflate reads one byte at a time.
+#[cfg(feature = "compress")]
+enum GzipState {
+    /// A member starts at the current position of the reader.
+    Header(BufReader<Box<CompressedReader>>),
+    Member(gzip::Decoder<BufReader<Box<CompressedReader>>>),
+    /// The header of a member could not be read.
+    Failed,
+}
+
+#[cfg(feature = "compress")]
+impl GzipDecoder {
+    fn new(reader: BufReader<Box<CompressedReader>>) -> GzipDecoder {
+        GzipDecoder {
+            state: GzipState::Header(reader),
+        }
+    }
+
+    fn into_inner(self) -> BufReader<Box<CompressedReader>> {
+        match self.state {
+            GzipState::Header(reader) => reader,
+            GzipState::Member(decoder) => decoder.into_inner(),
+            GzipState::Failed => unreachable!("the gzip decoder failed while reading the body"),
+        }
+    }
+}
+
+/// Check if the data which follows a complete gzip member is the start of another member.
+#[cfg(feature = "compress")]
+fn starts_gzip_member<R: BufRead>(inner: &mut R) -> io::Result<bool> {
+    let data = inner.fill_buf()?;
+    Ok(!data.is_empty() && GZIP_MAGIC.starts_with(&data[..data.len().min(2)]))
+}
+
+#[cfg(feature = "compress")]
+impl Read for GzipDecoder {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoder = match &mut self.state {
+                GzipState::Header(_) => {
+                    if let GzipState::Header(reader) = mem::replace(&mut self.state, GzipState::Failed) {
+                        self.state = GzipState::Member(gzip::Decoder::new(reader)?);
+                    }
+                    continue;
+                }
+                GzipState::Member(decoder) => decoder,
+                GzipState::Failed => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid gzip member")),
+            };
+            let n = decoder.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            if !starts_gzip_member(decoder.as_inner_mut())? {
+                return finish(n, decoder.as_inner_mut(), buf);
+            }
+            debug!("decoding the next gzip member");
+            if let GzipState::Member(decoder) = mem::replace(&mut self.state, GzipState::Failed) {
+                self.state = GzipState::Header(decoder.into_inner());
+            }
+        }
+    }
 }
 
 impl Read for CompressedReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        // TODO: gzip does not read until EOF, leaving some data in the buffer.
         match self {
             CompressedReader::Plain(s) => s.read(buf),
             #[cfg(feature = "compress")]
-            CompressedReader::Deflate(s) => s.read(buf),
+            CompressedReader::Deflate(s) => finish(s.read(buf)?, s.as_inner_mut(), buf),
             #[cfg(feature = "compress")]
             CompressedReader::Gzip(s) => s.read(buf),
+            #[cfg(feature = "compress-br")]
+            CompressedReader::Brotli(s) => finish(s.read(buf)?, s.get_mut(), buf),
         }
     }
 }
@@ -103,11 +348,16 @@ mod tests {
     use libflate::{deflate, gzip};
 
     #[cfg(feature = "compress")]
-    use super::have_encoding;
+    use super::{body_codings, have_unknown_encoding};
     use crate::parsing::response::parse_response;
     use crate::streams::BaseStream;
     use crate::PreparedRequest;
 
+    #[cfg(feature = "compress")]
+    fn have_encoding(headers: &HeaderMap, enc: &str) -> bool {
+        body_codings(headers).iter().any(|s| s.eq_ignore_ascii_case(enc))
+    }
+
     #[test]
     #[cfg(feature = "compress")]
     fn test_have_encoding_none() {
@@ -148,6 +398,29 @@ mod tests {
         assert!(have_encoding(&headers, "gzip"));
     }
 
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_body_codings_order() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", HeaderValue::from_static("identity, deflate"));
+        headers.append("content-encoding", HeaderValue::from_static("X-Gzip"));
+        headers.insert("transfer-encoding", HeaderValue::from_static("br, chunked"));
+        assert_eq!(body_codings(&headers), ["deflate", "gzip", "br"]);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_have_encoding_aliases() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-encoding", HeaderValue::from_static("X-Gzip"));
+        assert!(have_encoding(&headers, "gzip"));
+        assert!(!have_unknown_encoding(&headers));
+
+        headers.insert("content-encoding", HeaderValue::from_static("x-compress"));
+        assert!(have_encoding(&headers, "compress"));
+        assert!(have_unknown_encoding(&headers));
+    }
+
     #[test]
     fn test_stream_plain() {
         let payload = b"Hello world!!!!!!!!";
@@ -158,7 +431,7 @@ mod tests {
 
         let req = PreparedRequest::new(Method::GET, "http://google.ca");
 
-        let sock = BaseStream::mock(buf);
+        let sock = BaseStream::memory(buf);
         let response = parse_response(sock, &req).unwrap();
         assert_eq!(response.text().unwrap(), "Hello world!!!!!!!!");
     }
@@ -181,7 +454,7 @@ mod tests {
 
         let req = PreparedRequest::new(Method::GET, "http://google.ca");
 
-        let sock = BaseStream::mock(buf);
+        let sock = BaseStream::memory(buf);
         let response = parse_response(sock, &req).unwrap();
         assert_eq!(response.text().unwrap(), "Hello world!!!!!!!!");
     }
@@ -204,19 +477,251 @@ mod tests {
 
         let req = PreparedRequest::new(Method::GET, "http://google.ca");
 
-        let sock = BaseStream::mock(buf);
+        let sock = BaseStream::memory(buf);
         let response = parse_response(sock, &req).unwrap();
 
         assert_eq!(response.text().unwrap(), "Hello world!!!!!!!!");
     }
 
+    #[cfg(feature = "compress")]
+    fn gzip_member(data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        let mut enc = gzip::Encoder::new(&mut payload).unwrap();
+        enc.write_all(data).unwrap();
+        enc.finish().into_result().unwrap();
+        payload
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_multi_member() {
+        let mut payload = gzip_member(b"Hello ");
+        payload.extend(gzip_member(b"world!"));
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.text().unwrap(), "Hello world!");
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_trailing_garbage() {
+        let mut payload = gzip_member(b"Hello world!");
+        payload.extend(b"\0\0garbage");
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n{:x}\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+        buf.extend(b"\r\n0\r\n\r\n");
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf);
+        let mut reader = parse_response(sock, &req).unwrap().split().2;
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "Hello world!");
+        // The rest of the body was read up to the last chunk.
+        assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_bad_crc() {
+        let mut payload = gzip_member(b"Hello world!");
+        let len = payload.len();
+        payload[len - 8] ^= 0xff;
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+        assert!(response.bytes().is_err());
+    }
+
+    #[cfg(feature = "compress")]
+    fn deflate_stream(data: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        let mut enc = deflate::Encoder::new(&mut payload);
+        enc.write_all(data).unwrap();
+        enc.finish().into_result().unwrap();
+        payload
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_stacked_encodings() {
+        let payload = gzip_member(&deflate_stream(b"Hello world!"));
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: deflate, gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(&payload);
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf.clone());
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.text().unwrap(), "Hello world!");
+
+        // The body is passed through when one of its codings is not accepted.
+        let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+        req.settings.allowed_encodings = Some(vec!["gzip".to_string()]);
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.bytes().unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_transfer_encoding_over_content_encoding() {
+        let payload = gzip_member(&deflate_stream(b"Hello world!"));
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nTransfer-Encoding: gzip, chunked\r\n\r\n{:x}\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+        buf.extend(b"\r\n0\r\n\r\n");
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.text().unwrap(), "Hello world!");
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_raw() {
+        let payload = gzip_member(&deflate_stream(b"Hello world!"));
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nTransfer-Encoding: gzip, chunked\r\n\r\n{:x}\r\n",
+            payload.len()
+        );
+        buf.extend(&payload);
+        buf.extend(b"\r\n0\r\n\r\n");
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.bytes_raw().unwrap(), (payload, Some(crate::Encoding::Deflate)));
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(buf, "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        let sock = BaseStream::memory(buf);
+        let (mut reader, encoding) = parse_response(sock, &req).unwrap().raw_reader();
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(encoding, Some(crate::Encoding::Identity));
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_without_auto_decompress() {
+        let mut payload = Vec::new();
+        let mut enc = gzip::Encoder::new(&mut payload).unwrap();
+        enc.write_all(b"Hello world!!!!!!!!").unwrap();
+        enc.finish();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(&payload);
+
+        let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+        req.settings.auto_decompress = false;
+
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        assert_eq!(response.headers()[http::header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.bytes().unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_not_accepted() {
+        let mut payload = Vec::new();
+        let mut enc = gzip::Encoder::new(&mut payload).unwrap();
+        enc.write_all(b"Hello world!!!!!!!!").unwrap();
+        enc.finish();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(&payload);
+
+        let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+        req.settings.allowed_encodings = Some(vec!["deflate".to_string()]);
+
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        assert_eq!(response.content_encoding(), Some(crate::Encoding::Gzip));
+        assert_eq!(response.bytes().unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "compress-br")]
+    fn test_stream_brotli() {
+        let payload = b"\x8b\x05\x80hello brotli\x03";
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: br\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(&payload[..]);
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+
+        let sock = BaseStream::memory(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        assert_eq!(response.text().unwrap(), "hello brotli");
+    }
+
     #[test]
     #[cfg(feature = "compress")]
     fn test_no_body_with_gzip() {
         let buf = b"HTTP/1.1 200 OK\r\ncontent-encoding: gzip\r\n\r\n";
 
         let req = PreparedRequest::new(Method::GET, "http://google.ca");
-        let sock = BaseStream::mock(buf.to_vec());
+        let sock = BaseStream::memory(buf.to_vec());
         assert!(parse_response(sock, &req).is_err());
     }
 
@@ -226,7 +731,66 @@ mod tests {
         let buf = b"HTTP/1.1 200 OK\r\ncontent-encoding: gzip\r\n\r\n";
 
         let req = PreparedRequest::new(Method::HEAD, "http://google.ca");
-        let sock = BaseStream::mock(buf.to_vec());
+        let sock = BaseStream::memory(buf.to_vec());
+        assert!(parse_response(sock, &req).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_empty_body_with_gzip() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nContent-Encoding: gzip\r\n\r\n";
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf.to_vec());
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.bytes().unwrap(), b"");
+
+        let buf = b"HTTP/1.1 204 No Content\r\nContent-Encoding: gzip\r\n\r\n";
+        let sock = BaseStream::memory(buf.to_vec());
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.bytes().unwrap(), b"");
+    }
+
+    #[test]
+    fn test_unknown_encoding_lenient() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Encoding: zstd\r\n\r\nhello";
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf.to_vec());
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.headers()["content-encoding"], "zstd");
+        assert_eq!(response.bytes().unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_unknown_encoding_strict() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Encoding: zstd\r\n\r\nhello";
+
+        let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+        req.settings.strict_content_encoding = true;
+        let sock = BaseStream::memory(buf.to_vec());
+        assert!(parse_response(sock, &req).is_err());
+
+        let mut req = PreparedRequest::new(Method::HEAD, "http://google.ca");
+        req.settings.strict_content_encoding = true;
+        let sock = BaseStream::memory(buf.to_vec());
         assert!(parse_response(sock, &req).is_ok());
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nContent-Encoding: zstd\r\n\r\n";
+        let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+        req.settings.strict_content_encoding = true;
+        let sock = BaseStream::memory(buf.to_vec());
+        assert!(parse_response(sock, &req).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_no_body_status_with_gzip() {
+        let buf = b"HTTP/1.1 304 Not Modified\r\nContent-Length: 25\r\nContent-Encoding: gzip\r\n\r\n";
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::memory(buf.to_vec());
+        let response = parse_response(sock, &req).unwrap();
+        assert_eq!(response.bytes().unwrap(), b"");
     }
 }
@@ -17,6 +17,20 @@ where
     pub fn new(inner: R, length: u64) -> LengthReader<R> {
         LengthReader { inner, length, read: 0 }
     }
+
+    /// Get the length of the body.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Check if the whole body was read.
+    pub fn is_done(&self) -> bool {
+        self.read == self.length
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
 }
 
 impl<R> Read for LengthReader<R>
@@ -33,6 +47,10 @@ where
         let n = self.inner.read(&mut buf[..count])?;
         self.read += n as u64;
         debug!("read {} bytes", n);
+        // The connection was closed before the whole body was received.
+        if n == 0 && count > 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
         Ok(n)
     }
 }
@@ -86,3 +104,15 @@ fn test_read_tiny_buf() {
     let n = reader.read(&mut buf).unwrap();
     assert_eq!(n, 0);
 }
+
+#[test]
+fn test_read_truncated() {
+    let mut reader = LengthReader::new(&b"hel"[..], 5);
+    let mut buf = [0u8; 1024];
+
+    let n = reader.read(&mut buf).unwrap();
+    assert_eq!(n, 3);
+
+    let err = reader.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
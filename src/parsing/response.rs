@@ -1,92 +1,247 @@
-use std::io::{BufReader, Read, Write};
-use std::str;
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "json")]
+use std::result;
+use std::sync::Arc;
+use std::time::SystemTime;
 
+use bytes::Bytes;
 use http::{
-    header::{HeaderName, HeaderValue, TRANSFER_ENCODING},
-    HeaderMap, StatusCode,
+    header::{
+        HeaderName, HeaderValue, ALT_SVC, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, ETAG, LAST_MODIFIED,
+        TRANSFER_ENCODING,
+    },
+    HeaderMap, StatusCode, Version,
 };
+use url::Url;
 
-use crate::error::{InvalidResponseKind, Result};
-use crate::parsing::buffers::{self, trim_byte};
-use crate::parsing::{BodyReader, CompressedReader, ResponseReader};
+use crate::alt_svc::{parse_alt_svc, AltSvc};
+use crate::cache::CachedResponse;
+use crate::checksum::{checksum_from_headers, Checksum};
+use crate::date::parse_http_date;
+use crate::encoding::{content_encoding, Encoding};
+use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
+use crate::media_type::MediaType;
+use crate::observer::BodyObserver;
+use crate::parsing::body_reader::response_has_body;
+use crate::parsing::{BodyReader, Chunks, CompressedReader, ResponseReader};
+use crate::pool::KeepAlive;
 use crate::request::PreparedRequest;
-use crate::streams::BaseStream;
+use crate::streams::{BaseStream, ByteCounters};
+use crate::timings::Timings;
 
 #[cfg(feature = "charsets")]
 use crate::{charsets::Charset, parsing::TextReader};
 
+#[cfg(feature = "json")]
+use crate::graphql::GraphQlResponse;
 #[cfg(feature = "json")]
 use serde::de::DeserializeOwned;
 
-pub fn parse_response_head<R>(reader: &mut BufReader<R>) -> Result<(StatusCode, HeaderMap)>
-where
-    R: Read,
-{
-    let mut line = Vec::new();
-    let mut headers = HeaderMap::new();
+const KEEP_ALIVE: &str = "keep-alive";
 
-    // status line
-    let status: StatusCode = {
-        buffers::read_line(reader, &mut line)?;
-        let mut parts = line.split(|&b| b == b' ').filter(|x| !x.is_empty());
+/// Maximum size of the status line and headers of a response.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
 
-        let _ = parts.next().ok_or(InvalidResponseKind::StatusLine)?;
-        let code = parts.next().ok_or(InvalidResponseKind::StatusLine)?;
+/// Maximum size of the beginning of the body kept by `error_for_status`.
+const MAX_ERROR_BODY_SIZE: u64 = 64 * 1024;
 
-        str::from_utf8(code)
-            .map_err(|_| InvalidResponseKind::StatusCode)?
-            .parse()
-            .map_err(|_| InvalidResponseKind::StatusCode)?
-    };
+fn map_parse_error(err: httparse::Error) -> InvalidResponseKind {
+    match err {
+        httparse::Error::Status => InvalidResponseKind::StatusCode,
+        httparse::Error::Version | httparse::Error::Token => InvalidResponseKind::StatusLine,
+        _ => InvalidResponseKind::Header,
+    }
+}
+
+/// Compute the position of `slice` inside of `buf`. `slice` must be a sub-slice of `buf`.
+fn offset_of(buf: &[u8], slice: &[u8]) -> usize {
+    slice.as_ptr() as usize - buf.as_ptr() as usize
+}
 
+pub fn parse_response_head<R>(reader: &mut BufReader<R>) -> Result<(Version, StatusCode, HeaderMap)>
+where
+    R: Read,
+{
+    // The whole head is read into a single buffer. Header values are slices of this buffer,
+    // which avoids allocating for every header value.
+    let mut buf = Vec::with_capacity(1024);
+    let mut num_lines: usize = 0;
     loop {
-        buffers::read_line(reader, &mut line)?;
-        if line.is_empty() {
+        let start = buf.len();
+        if start >= MAX_HEAD_SIZE {
+            return Err(InvalidResponseKind::HeadTooLarge.into());
+        }
+        let limit = (MAX_HEAD_SIZE - start) as u64;
+        if (&mut *reader).take(limit).read_until(b'\n', &mut buf)? == 0 {
             break;
         }
+        num_lines += 1;
+        if num_lines > 1 && (&buf[start..] == b"\r\n" || &buf[start..] == b"\n") {
+            break;
+        }
+    }
+    let head = Bytes::from(buf);
 
-        let col = line
-            .iter()
-            .position(|&c| c == b':')
-            .ok_or(InvalidResponseKind::Header)?;
+    let mut raw_headers = vec![httparse::EMPTY_HEADER; num_lines.saturating_sub(2)];
+    let mut response = httparse::Response::new(&mut raw_headers);
+    match response.parse(&head).map_err(map_parse_error)? {
+        httparse::Status::Complete(_) => {}
+        httparse::Status::Partial if head.is_empty() => return Err(InvalidResponseKind::StatusLine.into()),
+        httparse::Status::Partial => return Err(InvalidResponseKind::Header.into()),
+    }
 
-        let header = trim_byte(b' ', &line[..col]);
-        let value = trim_byte(b' ', &line[col + 1..]);
+    let version = match response.version {
+        Some(0) => Version::HTTP_10,
+        _ => Version::HTTP_11,
+    };
+    let code = response.code.ok_or(InvalidResponseKind::StatusLine)?;
+    let status = StatusCode::from_u16(code).map_err(|_| InvalidResponseKind::StatusCode)?;
 
+    let mut headers = HeaderMap::with_capacity(response.headers.len());
+    for header in response.headers.iter() {
+        let start = offset_of(&head, header.value);
         headers.append(
-            HeaderName::from_bytes(header).map_err(http::Error::from)?,
-            HeaderValue::from_bytes(value).map_err(http::Error::from)?,
+            HeaderName::from_bytes(header.name.as_bytes()).map_err(http::Error::from)?,
+            HeaderValue::from_shared(head.slice(start, start + header.value.len())).map_err(http::Error::from)?,
         );
     }
 
-    Ok((status, headers))
+    Ok((version, status, headers))
+}
+
+/// Check if the `Connection` headers contain the given token.
+fn has_connection_token(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+/// Check if the connection can be reused after this response.
+///
+/// HTTP/1.1 connections are persistent unless the server sends `Connection: close`, HTTP/1.0 connections
+/// are only persistent when the server sends `Connection: keep-alive`.
+fn is_persistent(version: Version, headers: &HeaderMap) -> bool {
+    match version {
+        Version::HTTP_10 => has_connection_token(headers, "keep-alive"),
+        _ => !has_connection_token(headers, "close"),
+    }
+}
+
+/// Find the checksum which the body of the response must match.
+///
+/// The checksum set on the request is preferred. The checksum sent by the server describes the body as it was
+/// sent, so it is only used when the body is not decompressed while it is read.
+fn expected_checksum(
+    headers: &HeaderMap,
+    status: StatusCode,
+    request: &PreparedRequest,
+    reader: &ResponseReader,
+) -> Option<Checksum> {
+    if !response_has_body(status, request.method()) {
+        return None;
+    }
+    match &request.settings.checksum {
+        Some(checksum) => Some(checksum.clone()),
+        None if request.settings.verify_checksum_headers && !reader.is_decoded() => checksum_from_headers(headers),
+        None => None,
+    }
 }
 
+#[cfg(test)]
 pub fn parse_response(reader: BaseStream, request: &PreparedRequest) -> Result<Response> {
+    parse_response_after_upload(reader, request, true)
+}
+
+/// Parse the response to a request whose body may not have been sent entirely.
+///
+/// When the server responded before the end of the upload, it still expects the rest of the body, so the
+/// connection is not reused.
+pub fn parse_response_after_upload(reader: BaseStream, request: &PreparedRequest, complete: bool) -> Result<Response> {
+    let counters = reader.counters().clone();
     let mut reader = BufReader::new(reader);
-    let (status, mut headers) = parse_response_head(&mut reader)?;
-    let body_reader = BodyReader::new(&headers, reader)?;
-    let compressed_reader = CompressedReader::new(&headers, request, body_reader)?;
-    let response_reader = ResponseReader::new(&headers, request, compressed_reader);
+    // Interim responses, such as `100 Continue`, are followed by the final response.
+    let (version, status, mut headers) = loop {
+        let (version, status, headers) = parse_response_head(&mut reader)?;
+        if !status.is_informational() || status == StatusCode::SWITCHING_PROTOCOLS {
+            break (version, status, headers);
+        }
+        debug!("skipping the interim response {}", status);
+    };
+    let keep_alive = headers
+        .get(KEEP_ALIVE)
+        .and_then(|value| value.to_str().ok())
+        .map(KeepAlive::parse)
+        .unwrap_or_default();
+    let persistent = complete && is_persistent(version, &headers) && !has_connection_token(request.headers(), "close");
+    reader.get_mut().set_keep_alive(persistent, keep_alive);
+    let body_reader = BodyReader::new(&headers, status, request.method(), reader)?;
+    let compressed_reader = CompressedReader::new(&headers, status, request, body_reader)?;
+    let mut response_reader = ResponseReader::new(&headers, request, compressed_reader);
+    if let Some(checksum) = expected_checksum(&headers, status, request, &response_reader) {
+        response_reader.set_checksum(checksum);
+    }
 
     // Remove HOP-BY-HOP headers
     headers.remove(TRANSFER_ENCODING);
 
     Ok(Response {
+        version,
         status,
         headers,
         reader: response_reader,
+        counters,
+        url: request.url().clone(),
+        history: Vec::new(),
+        timings: Timings::default(),
+    })
+}
+
+/// Create a response from a cached response, reading its body from memory.
+pub fn cached_response(
+    cached: CachedResponse,
+    request: &PreparedRequest,
+    counters: Arc<ByteCounters>,
+) -> Result<Response> {
+    let (status, mut headers, body) = cached.into_parts();
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    let reader = BufReader::new(BaseStream::memory(body));
+    let body_reader = BodyReader::new(&headers, status, request.method(), reader)?;
+    let response_reader = ResponseReader::new(&headers, request, CompressedReader::Plain(body_reader));
+
+    Ok(Response {
+        version: Version::HTTP_11,
+        status,
+        headers,
+        reader: response_reader,
+        counters,
+        url: request.url().clone(),
+        history: Vec::new(),
+        timings: Timings::default(),
     })
 }
 
 /// `Response` represents a response returned by a server.
 pub struct Response {
+    version: Version,
     status: StatusCode,
     headers: HeaderMap,
     reader: ResponseReader,
+    counters: Arc<ByteCounters>,
+    url: Url,
+    history: Vec<(StatusCode, Url)>,
+    timings: Timings,
 }
 
 impl Response {
+    /// Get the HTTP version of this `Response`.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
     /// Get the status code of this `Response`.
     #[inline]
     pub fn status(&self) -> StatusCode {
@@ -99,20 +254,227 @@ impl Response {
         &self.headers
     }
 
+    /// Get a mutable reference to the headers of this `Response`.
+    #[inline]
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Get the URL of this `Response`.
+    ///
+    /// This is the URL of the last request which was sent, after following the redirections.
+    #[inline]
+    pub fn final_url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the redirections which were followed to get this `Response`.
+    ///
+    /// Each redirection is given by its status code and the URL which returned it, in the order they were
+    /// followed. The list is empty if no redirection was followed.
+    #[inline]
+    pub fn history(&self) -> &[(StatusCode, Url)] {
+        &self.history
+    }
+
+    pub(crate) fn set_url(&mut self, url: Url, history: Vec<(StatusCode, Url)>) {
+        self.url = url;
+        self.history = history;
+    }
+
+    /// Get the time spent in the steps of the request which returned this `Response`.
+    #[inline]
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    pub(crate) fn set_timings(&mut self, timings: Timings) {
+        self.timings = timings;
+    }
+
+    pub(crate) fn set_observer(&mut self, observer: BodyObserver) {
+        self.reader.set_observer(observer);
+    }
+
+    #[cfg(feature = "json")]
+    pub(crate) fn set_har(&mut self, har: crate::har::HarBody) {
+        self.reader.set_har(har);
+    }
+
+    /// Get the number of bytes sent to the server while performing the request.
+    ///
+    /// This is the number of raw bytes written to the connection, including the request head and the bytes
+    /// sent while following redirections. When using TLS, it includes the overhead of the TLS protocol.
+    #[inline]
+    pub fn bytes_sent(&self) -> u64 {
+        self.counters.sent()
+    }
+
+    /// Get the number of bytes received from the server so far.
+    ///
+    /// This is the number of raw bytes read from the connection, before any decompression, including the
+    /// bytes received while following redirections. It increases as the body of the `Response` is read.
+    #[inline]
+    pub fn bytes_received(&self) -> u64 {
+        self.counters.received()
+    }
+
+    /// Get the alternative services advertised in the `Alt-Svc` headers of this `Response`.
+    ///
+    /// Invalid alternative services are skipped. The list is empty if the server cleared its alternative
+    /// services.
+    pub fn alt_svc(&self) -> Vec<AltSvc> {
+        self.headers
+            .get_all(ALT_SVC)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(parse_alt_svc)
+            .flatten()
+            .collect()
+    }
+
+    /// Get the entity tag of the resource, from the `ETag` header of this `Response`.
+    ///
+    /// The entity tag includes its quotes, and can be given to `RequestBuilder::if_match`.
+    pub fn etag(&self) -> Option<&str> {
+        self.headers.get(ETAG).and_then(|value| value.to_str().ok())
+    }
+
+    /// Get the media type of the body, from the `Content-Type` header of this `Response`.
+    ///
+    /// `None` is returned if the header is missing or invalid.
+    pub fn content_type(&self) -> Option<MediaType> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(MediaType::parse)
+    }
+
+    /// Get the content coding of the body as it was sent, from the `Content-Encoding` header of this
+    /// `Response`.
+    ///
+    /// `Encoding::Identity` is returned if the body is not compressed. `None` is returned if the body has
+    /// several codings or a coding which is unknown. The reading methods decode the body when its coding was
+    /// accepted by the request and decompression is enabled.
+    pub fn content_encoding(&self) -> Option<Encoding> {
+        content_encoding(&self.headers)
+    }
+
+    /// Get the charset of the body, from the `charset` parameter of the `Content-Type` header of this
+    /// `Response`.
+    ///
+    /// This is the label sent by the server, it may not be a known charset.
+    pub fn charset(&self) -> Option<String> {
+        self.content_type()?.charset().map(str::to_string)
+    }
+
+    /// Get the time when the resource was last modified, from the `Last-Modified` header of this `Response`.
+    ///
+    /// `None` is returned if the header is missing or is not a valid date.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.headers
+            .get(LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_http_date)
+    }
+
+    /// Checks if the status code of this `Response` is `412 Precondition Failed`.
+    ///
+    /// This status is returned when a condition such as `if_match` or `if_unmodified_since` is not met.
+    #[inline]
+    pub fn is_precondition_failed(&self) -> bool {
+        self.status == StatusCode::PRECONDITION_FAILED
+    }
+
     /// Checks if the status code of this `Response` was a success code.
     #[inline]
     pub fn is_success(&self) -> bool {
         self.status.is_success()
     }
 
+    /// Turn a `4xx` or `5xx` status code into a `StatusCode` error.
+    ///
+    /// The beginning of the body, up to 64 KiB, is read and kept in the error, and can be retrieved with
+    /// `Error::body`. Use `error_for_status_ref` to keep the response and read the whole body.
+    pub fn error_for_status(mut self) -> Result<Response> {
+        if self.error_for_status_ref().is_err() {
+            let mut body = Vec::new();
+            // The error is returned even if the body cannot be read, with the part which was read.
+            let _ = (&mut self.reader).take(MAX_ERROR_BODY_SIZE).read_to_end(&mut body);
+            return Err(Error::with_body(self.status, body));
+        }
+        Ok(self)
+    }
+
+    /// Turn a `4xx` or `5xx` status code into a `StatusCode` error, keeping this `Response`.
+    pub fn error_for_status_ref(&self) -> Result<&Response> {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            return Err(ErrorKind::StatusCode(self.status).into());
+        }
+        Ok(self)
+    }
+
     /// Split this `Response` into a tuple of `StatusCode`, `HeaderMap`, `ResponseReader`.
     ///
     /// This method is useful to read the status code or headers after consuming the response.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn main() -> attohttpc::Result {
+    /// let (status, headers, mut reader) = attohttpc::get("https://example.com").send()?.split();
+    /// std::io::copy(&mut reader, &mut std::io::stdout())?;
+    /// println!("{} {:?}", status, headers);
+    /// # Ok(())
+    /// # }
+    /// ```
     #[inline]
     pub fn split(self) -> (StatusCode, HeaderMap, ResponseReader) {
         (self.status, self.headers, self.reader)
     }
 
+    /// Get a reader of the body as it was sent by the server, along with its content coding.
+    ///
+    /// Unlike the other reading methods, the body is not decompressed: its content codings, and its transfer
+    /// codings other than `chunked`, are left as is. The content coding is the one returned by
+    /// `content_encoding`. This is useful to store or forward the original representation of the body.
+    pub fn raw_reader(self) -> (ResponseReader, Option<Encoding>) {
+        let encoding = self.content_encoding();
+        (self.reader.into_raw(), encoding)
+    }
+
+    /// Read the body as it was sent by the server, along with its content coding.
+    ///
+    /// See `raw_reader`.
+    pub fn bytes_raw(self) -> Result<(Vec<u8>, Option<Encoding>)> {
+        let (reader, encoding) = self.raw_reader();
+        Ok((reader.bytes()?, encoding))
+    }
+
+    /// Read the body and convert this `Response` into an `http::Response`.
+    ///
+    /// The final URL of the response is stored in the extensions of the `http::Response`.
+    pub fn into_http_response(self) -> Result<http::Response<Vec<u8>>> {
+        let (parts, reader) = self.into_http_parts();
+        Ok(http::Response::from_parts(parts, reader.bytes()?))
+    }
+
+    /// Convert this `Response` into an `http::Response` whose body is the `ResponseReader`.
+    ///
+    /// Unlike `into_http_response`, the body is not read, it is streamed from the reader.
+    pub fn into_http_response_reader(self) -> http::Response<ResponseReader> {
+        let (parts, reader) = self.into_http_parts();
+        http::Response::from_parts(parts, reader)
+    }
+
+    fn into_http_parts(self) -> (http::response::Parts, ResponseReader) {
+        let (mut parts, ()) = http::Response::new(()).into_parts();
+        parts.status = self.status;
+        parts.version = self.version;
+        parts.headers = self.headers;
+        parts.extensions.insert(self.url);
+        (parts, self.reader)
+    }
+
     /// Write the response to any object that implements `Write`.
     #[inline]
     pub fn write_to<W>(self, writer: W) -> Result<u64>
@@ -122,6 +484,48 @@ impl Response {
         self.reader.write_to(writer)
     }
 
+    /// Write the response to the file at the given path, replacing it atomically.
+    ///
+    /// See `ResponseReader::write_to_file`.
+    #[inline]
+    pub fn write_to_file<P>(self, path: P) -> Result<u64>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.reader.write_to_file(path)
+    }
+
+    /// Write the response to any object that implements `Write`, reporting the progress.
+    ///
+    /// After each write, `progress` is called with the number of bytes written so far and the length of the
+    /// body, if it is known. See `ResponseReader::write_to_with_progress`.
+    #[inline]
+    pub fn write_to_with_progress<W, F>(self, writer: W, progress: F) -> Result<u64>
+    where
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        self.reader.write_to_with_progress(writer, progress)
+    }
+
+    /// Get the number of bytes of the body which will be read, if it is known.
+    ///
+    /// The length is known when the response has a `Content-Length` header and its body is not decompressed.
+    #[inline]
+    pub fn content_length(&self) -> Option<u64> {
+        self.reader.content_length()
+    }
+
+    /// Create an iterator over the chunks of the response.
+    ///
+    /// When the response uses the chunked transfer encoding and its body is not compressed, each item is
+    /// the content of a chunk, as sent by the server. This is useful for servers which use the chunks to
+    /// delimit messages. Otherwise, the whole body is returned as a single item.
+    #[inline]
+    pub fn chunks(self) -> Chunks {
+        self.reader.chunks()
+    }
+
     /// Read the response to a `Vec` of bytes.
     #[inline]
     pub fn bytes(self) -> Result<Vec<u8>> {
@@ -133,7 +537,8 @@ impl Response {
     /// If the `charsets` feature is enabled, it will try to decode the response using
     /// the encoding in the headers. If there's no encoding specified in the headers,
     /// it will fall back to the default encoding, and if that's also not specified,
-    /// it will fall back to the default of ISO-8859-1.
+    /// it will fall back to the default of ISO-8859-1. A UTF-8 or UTF-16 byte order mark
+    /// at the start of the body takes precedence over the encoding, and is removed.
     ///
     /// If the `charsets` feature is disabled, this method is the same as calling
     /// `text_utf8`.
@@ -153,12 +558,27 @@ impl Response {
         self.reader.text_with(charset)
     }
 
+    /// Read the response to a `String`, decoding with the charset of the given label, such as `iso-8859-1`.
+    ///
+    /// This will ignore the encoding from the response headers and the default encoding, if any. An
+    /// `UnknownCharset` error is returned if the label is not known.
+    ///
+    /// This method only exists when the `charsets` feature is enabled.
+    #[cfg(feature = "charsets")]
+    #[inline]
+    pub fn text_with_charset(self, label: &str) -> Result<String> {
+        self.reader.text_with_charset(label)
+    }
+
     /// Create a `TextReader` from this `ResponseReader`.
     ///
     /// If the response headers contain charset information, that charset will be used to decode the body.
     /// Otherwise, if a default encoding is set it will be used. If there is no default encoding, ISO-8859-1
     /// will be used.
     ///
+    /// The body is decoded as it is read. The `TextReader` implements `BufRead`, so that large bodies can be
+    /// processed line by line with `BufRead::lines`.
+    ///
     /// This method only exists when the `charsets` feature is enabled.
     #[cfg(feature = "charsets")]
     pub fn text_reader(self) -> TextReader<BufReader<ResponseReader>> {
@@ -215,15 +635,575 @@ impl Response {
     {
         self.reader.json_utf8()
     }
+
+    /// Parse the response to a GraphQL request, deserializing its data into a type `T`.
+    ///
+    /// Servers may respond with an error status, such as 400, along with a GraphQL response which describes
+    /// the errors. The response is parsed regardless of the status. The body is decoded as with `json`.
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn graphql<T>(self) -> Result<GraphQlResponse<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.json()
+    }
+
+    /// Parse the response as a JSON object of type `T` if the status is successful, or as an error object
+    /// of type `E` if the status is a client or server error, 4xx or 5xx.
+    ///
+    /// Responses with any other status are parsed as `T`. The body is decoded as with `json`. Errors which
+    /// occur while reading or parsing the body are returned in the outer `Result`.
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn json_or_error<T, E>(self) -> Result<result::Result<T, E>>
+    where
+        T: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        if self.status.is_client_error() || self.status.is_server_error() {
+            Ok(Err(self.json()?))
+        } else {
+            Ok(Ok(self.json()?))
+        }
+    }
 }
 
 #[test]
 fn test_read_request_head() {
     let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Type: text/plain\r\n\r\nhello";
     let mut reader = BufReader::new(&response[..]);
-    let (status, headers) = parse_response_head(&mut reader).unwrap();
+    let (_, status, headers) = parse_response_head(&mut reader).unwrap();
     assert_eq!(status, StatusCode::OK);
     assert_eq!(headers.len(), 2);
     assert_eq!(headers[http::header::CONTENT_LENGTH], "5");
     assert_eq!(headers[http::header::CONTENT_TYPE], "text/plain");
 }
+
+#[test]
+fn test_is_persistent() {
+    let mut headers = HeaderMap::new();
+    assert!(is_persistent(Version::HTTP_11, &headers));
+    assert!(!is_persistent(Version::HTTP_10, &headers));
+
+    headers.insert(CONNECTION, HeaderValue::from_static("Keep-Alive"));
+    assert!(is_persistent(Version::HTTP_10, &headers));
+
+    headers.insert(CONNECTION, HeaderValue::from_static("upgrade, close"));
+    assert!(!is_persistent(Version::HTTP_11, &headers));
+}
+
+#[test]
+fn test_http_10_response() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    // Without a length, the body of an HTTP/1.0 response ends when the connection is closed.
+    let buf = b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\nhello".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.version(), Version::HTTP_10);
+    assert_eq!(response.text().unwrap(), "hello");
+}
+
+#[test]
+fn test_typed_headers() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Type: Text/Plain; charset=ISO-8859-1\r\n\
+Last-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\nETag: \"abc\"\r\nContent-Length: 5\r\n\r\nhello"
+        .to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.content_type().unwrap().essence(), "text/plain");
+    assert_eq!(response.charset().as_deref(), Some("ISO-8859-1"));
+    assert_eq!(
+        response.last_modified(),
+        Some(UNIX_EPOCH + Duration::from_secs(784_111_777))
+    );
+    assert_eq!(response.etag(), Some("\"abc\""));
+    assert_eq!(response.content_length(), Some(5));
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Type: text\r\nLast-Modified: yesterday\r\n\r\n".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.content_type(), None);
+    assert_eq!(response.charset(), None);
+    assert_eq!(response.last_modified(), None);
+}
+
+#[test]
+fn test_error_for_status() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.error_for_status().unwrap().text().unwrap(), "hello");
+
+    let buf = b"HTTP/1.1 404 Not Found\r\nContent-Length: 7\r\n\r\nmissing".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    match response.error_for_status_ref().map(|_| ()).unwrap_err().kind() {
+        ErrorKind::StatusCode(StatusCode::NOT_FOUND) => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert_eq!(response.text().unwrap(), "missing");
+
+    let mut buf = b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 100000\r\n\r\n".to_vec();
+    buf.resize(buf.len() + 100_000, b'x');
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    let err = response.error_for_status().map(|_| ()).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::StatusCode(StatusCode::INTERNAL_SERVER_ERROR)
+    ));
+    assert_eq!(err.body().map(|body| body.len()), Some(64 * 1024));
+}
+
+#[test]
+fn test_write_to_file() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+    use std::fs;
+
+    let path = std::env::temp_dir().join(format!("attohttpc-write-to-file-{}.txt", std::process::id()));
+    let part_path = std::env::temp_dir().join(format!("attohttpc-write-to-file-{}.txt.part", std::process::id()));
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.write_to_file(&path).unwrap(), 5);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+    assert!(!part_path.exists());
+
+    // A truncated body leaves the file untouched.
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nworld".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert!(response.write_to_file(&path).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+    assert!(!part_path.exists());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_into_http_response() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf.clone()), &req)
+        .unwrap()
+        .into_http_response()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.version(), Version::HTTP_11);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+    assert_eq!(
+        response.extensions().get::<Url>().unwrap().as_str(),
+        "http://google.ca/"
+    );
+    assert_eq!(response.body(), b"hello");
+
+    let response = parse_response(BaseStream::memory(buf), &req)
+        .unwrap()
+        .into_http_response_reader();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(response.into_body().bytes().unwrap(), b"hello");
+}
+
+#[test]
+#[cfg(feature = "charsets")]
+fn test_text_charsets() {
+    use crate::request::PreparedRequest;
+    use crate::ErrorKind;
+    use http::Method;
+
+    let response = |body: &[u8]| {
+        let mut buf = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=iso-8859-1\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        buf.extend(body);
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        parse_response(BaseStream::memory(buf), &req).unwrap()
+    };
+
+    assert_eq!(response(b"caf\xe9").text().unwrap(), "café");
+    assert_eq!(response(b"caf\xc3\xa9").text_with_charset("UTF-8").unwrap(), "café");
+    // The byte order mark overrides the charset of the header.
+    assert_eq!(response(b"\xef\xbb\xbfcaf\xc3\xa9").text().unwrap(), "café");
+    assert_eq!(response(b"\xff\xfeh\0i\0").text().unwrap(), "hi");
+
+    let lines: Vec<String> = response(b"caf\xe9\r\nth\xe9\n")
+        .text_reader()
+        .lines()
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+    assert_eq!(lines, ["café", "thé"]);
+
+    match response(b"").text_with_charset("klingon").unwrap_err().kind() {
+        ErrorKind::UnknownCharset(label) => assert_eq!(label, "klingon"),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+fn test_read_request_head_lf_and_spaces() {
+    let response = b"HTTP/1.1 404 Not Found\nX-Foo:   bar baz  \nX-Foo: qux\nX-Empty:\n\nbody";
+    let mut reader = BufReader::new(&response[..]);
+    let (_, status, headers) = parse_response_head(&mut reader).unwrap();
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers.len(), 3);
+    let values: Vec<_> = headers.get_all("x-foo").iter().collect();
+    assert_eq!(values, ["bar baz", "qux"]);
+    assert_eq!(headers["x-empty"], "");
+
+    let mut rest = String::new();
+    reader.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "body");
+}
+
+#[test]
+fn test_bytes_received() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let len = buf.len() as u64;
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.bytes_sent(), 0);
+    assert_eq!(response.bytes_received(), len);
+}
+
+#[test]
+fn test_no_body_responses() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let next = b"HTTP/1.1 200 OK\r\n\r\n";
+    for (method, head) in &[
+        (
+            Method::GET,
+            &b"HTTP/1.1 204 No Content\r\nContent-Length: 5\r\n\r\n"[..],
+        ),
+        (
+            Method::GET,
+            &b"HTTP/1.1 304 Not Modified\r\nTransfer-Encoding: chunked\r\n\r\n"[..],
+        ),
+        (Method::HEAD, &b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"[..]),
+    ] {
+        let mut buf = head.to_vec();
+        buf.extend(&next[..]);
+        let req = PreparedRequest::new(method.clone(), "http://google.ca");
+        let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+        assert_eq!(response.bytes().unwrap(), b"");
+    }
+}
+
+#[test]
+fn test_chunks() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+
+    let buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\none\r\n3\r\ntwo\r\n0\r\n\r\n";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    let chunks: Vec<Vec<u8>> = response.chunks().collect::<Result<_>>().unwrap();
+    assert_eq!(chunks, vec![b"one".to_vec(), b"two".to_vec()]);
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nonetwo";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    let chunks: Vec<Vec<u8>> = response.chunks().collect::<Result<_>>().unwrap();
+    assert_eq!(chunks, vec![b"onetwo".to_vec()]);
+}
+
+#[test]
+fn test_max_response_size() {
+    use crate::request::PreparedRequest;
+    use crate::ErrorKind;
+    use http::Method;
+
+    let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+    req.settings.max_response_size = Some(6);
+    let is_too_large = |result: Result<Vec<u8>>| matches!(result.unwrap_err().kind(), ErrorKind::ResponseTooLarge);
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nonetwo";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    assert_eq!(response.bytes().unwrap(), b"onetwo");
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nonetwo!";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    assert!(is_too_large(response.bytes()));
+
+    // Without a length, the body is read until the limit is exceeded.
+    let buf = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nonetwothree";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    assert!(is_too_large(response.bytes()));
+
+    let buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\none\r\n4\r\ntwo!\r\n0\r\n\r\n";
+    let response = parse_response(BaseStream::memory(buf.to_vec()), &req).unwrap();
+    let mut chunks = response.chunks();
+    assert_eq!(chunks.next().unwrap().unwrap(), b"one");
+    assert!(is_too_large(chunks.next().unwrap()));
+    assert!(chunks.next().is_none());
+}
+
+#[test]
+#[cfg(feature = "compress")]
+fn test_max_response_size_compressed() {
+    use std::io::Write;
+
+    use crate::request::PreparedRequest;
+    use crate::ErrorKind;
+    use http::Method;
+    use libflate::gzip::Encoder;
+
+    // A small compressed body which inflates to a megabyte.
+    let mut encoder = Encoder::new(Vec::new()).unwrap();
+    encoder.write_all(&vec![0; 1 << 20]).unwrap();
+    let body = encoder.finish().into_result().unwrap();
+
+    let mut buf = format!(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    buf.extend(body);
+
+    let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+    req.settings.max_response_size = Some(64 * 1024);
+    let response = parse_response(BaseStream::memory(buf.clone()), &req).unwrap();
+    match response.bytes().unwrap_err().kind() {
+        ErrorKind::ResponseTooLarge => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    req.settings.max_response_size = Some(1 << 20);
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.bytes().unwrap().len(), 1 << 20);
+}
+
+#[test]
+fn test_alt_svc() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 200 OK\r\nAlt-Svc: h2=\":443\"\r\nAlt-Svc: h3=\"alt.foo.bar:443\"; ma=60\r\n\r\n".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    let protocols: Vec<_> = response.alt_svc().into_iter().map(|svc| svc.protocol).collect();
+    assert_eq!(protocols, ["h2", "h3"]);
+}
+
+#[test]
+#[cfg(feature = "json-path")]
+fn test_json_error_path() {
+    use crate::error::ErrorKind;
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 23\r\n\r\n{\"items\": [1, 2, \"x\"]}".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    let err = response
+        .json_utf8::<std::collections::HashMap<String, Vec<u32>>>()
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::JsonPath(e) => assert_eq!(e.path().to_string(), "items[2]"),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+#[cfg(all(feature = "json", feature = "charsets"))]
+fn test_json_charset() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        name: String,
+        age: u32,
+    }
+
+    let mut buf = b"HTTP/1.1 200 OK\r\nContent-Type: application/json; charset=iso-8859-1\r\n\r\n".to_vec();
+    buf.extend_from_slice(b"{\"name\": \"Ren\xe9e\", \"age\": 42}");
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(
+        response.json::<User>().unwrap(),
+        User {
+            name: "Renée".into(),
+            age: 42
+        }
+    );
+}
+
+#[test]
+#[cfg(all(feature = "json", not(feature = "json-path")))]
+fn test_json_error() {
+    use crate::error::ErrorKind;
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\n{\"age\": 1".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    match response
+        .json::<std::collections::HashMap<String, u32>>()
+        .unwrap_err()
+        .kind()
+    {
+        ErrorKind::Json(err) => assert!(err.is_eof()),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_json_or_error() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct ApiError {
+        message: String,
+    }
+
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n[1, 2]".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.json_or_error::<Vec<u32>, ApiError>().unwrap(), Ok(vec![1, 2]));
+
+    let buf = b"HTTP/1.1 404 Not Found\r\nContent-Length: 19\r\n\r\n{\"message\": \"gone\"}".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    let message = "gone".to_string();
+    assert_eq!(
+        response.json_or_error::<Vec<u32>, ApiError>().unwrap(),
+        Err(ApiError { message })
+    );
+}
+
+#[test]
+fn test_precondition_failed() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let req = PreparedRequest::new(Method::PUT, "http://google.ca");
+    let buf = b"HTTP/1.1 412 Precondition Failed\r\nETag: \"v2\"\r\nContent-Length: 0\r\n\r\n".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert!(response.is_precondition_failed());
+    assert_eq!(response.etag(), Some("\"v2\""));
+}
+
+#[test]
+fn test_read_request_head_invalid() {
+    use crate::error::ErrorKind;
+
+    fn parse_err(response: &[u8]) -> String {
+        let mut reader = BufReader::new(response);
+        match parse_response_head(&mut reader).unwrap_err().into_kind() {
+            ErrorKind::InvalidResponse(kind) => format!("{}", kind),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+
+    assert_eq!(parse_err(b""), "invalid status line");
+    assert_eq!(parse_err(b"HTTP/1.1 abc OK\r\n\r\n"), "invalid status code");
+    assert_eq!(parse_err(b"FOO 200 OK\r\n\r\n"), "invalid status line");
+    assert_eq!(parse_err(b"HTTP/1.1 200 OK\r\nNoColon\r\n\r\n"), "invalid header");
+    assert_eq!(parse_err(b"HTTP/1.1 200 OK\r\nX-Foo: bar\r\n"), "invalid header");
+
+    let mut huge = b"HTTP/1.1 200 OK\r\n".to_vec();
+    for _ in 0..10_000 {
+        huge.extend_from_slice(b"X-Foo: bar\r\n");
+    }
+    assert_eq!(parse_err(&huge), "response head too large");
+    let mut long_line = b"HTTP/1.1 200 OK\r\nX-Foo: ".to_vec();
+    long_line.resize(100_000, b'a');
+    assert_eq!(parse_err(&long_line), "response head too large");
+}
+
+#[test]
+fn test_write_to_with_progress() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let mut buf = b"HTTP/1.1 200 OK\r\nContent-Length: 100000\r\n\r\n".to_vec();
+    buf.extend(vec![b'x'; 100_000]);
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.content_length(), Some(100_000));
+
+    let mut calls = Vec::new();
+    let mut body = Vec::new();
+    let n = response
+        .write_to_with_progress(&mut body, |written, total| calls.push((written, total)))
+        .unwrap();
+    assert_eq!(n, 100_000);
+    assert_eq!(body.len(), 100_000);
+    assert!(calls.len() > 1);
+    assert!(calls.windows(2).all(|w| w[0].0 < w[1].0));
+    assert_eq!(calls.last(), Some(&(100_000, Some(100_000))));
+}
+
+#[test]
+fn test_content_length_chunked() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n".to_vec();
+    let req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.content_length(), None);
+
+    let mut calls = Vec::new();
+    response
+        .write_to_with_progress(Vec::new(), |written, total| calls.push((written, total)))
+        .unwrap();
+    assert_eq!(calls, [(5, None)]);
+}
+
+#[test]
+fn test_checksum() {
+    use crate::request::PreparedRequest;
+    use http::Method;
+
+    let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+    req.settings.checksum = Checksum::md5_hex("5d41402abc4b2a76b9719d911017c592");
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    assert_eq!(response.text().unwrap(), "hello");
+
+    let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nworld".to_vec();
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    match response.bytes().unwrap_err().kind() {
+        ErrorKind::ChecksumMismatch => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    // The checksum sent by the server is only verified when asked to.
+    let mut req = PreparedRequest::new(Method::GET, "http://google.ca");
+    let buf = b"HTTP/1.1 200 OK\r\nContent-MD5: XUFAKrxLKna5cZ2REBfFkg==\r\nContent-Length: 5\r\n\r\nworld".to_vec();
+    let response = parse_response(BaseStream::memory(buf.clone()), &req).unwrap();
+    assert_eq!(response.bytes().unwrap(), b"world");
+
+    req.settings.verify_checksum_headers = true;
+    let response = parse_response(BaseStream::memory(buf), &req).unwrap();
+    match response.bytes().unwrap_err().kind() {
+        ErrorKind::ChecksumMismatch => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
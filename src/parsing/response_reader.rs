@@ -1,40 +1,68 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
 #[cfg(any(feature = "charsets", feature = "json"))]
 use std::io::BufReader;
 use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
 use http::header::HeaderMap;
 #[cfg(feature = "json")]
 use serde::de::DeserializeOwned;
 
-use crate::error::Result;
-use crate::parsing::CompressedReader;
+use crate::checksum::{Checksum, ChecksumVerifier};
+use crate::error::{Error, ErrorKind, Result};
+#[cfg(feature = "json")]
+use crate::har::HarBody;
+use crate::observer::BodyObserver;
+use crate::parsing::body_reader::release;
+use crate::parsing::{BodyReader, ChunkedReader, CompressedReader};
 use crate::request::PreparedRequest;
+use crate::streams::BaseStream;
 
 #[cfg(feature = "charsets")]
 use {
     crate::{
         charsets::{self, Charset},
-        parsing::buffers::trim_byte,
+        media_type::MediaType,
         parsing::TextReader,
     },
     encoding_rs::Encoding,
     http::header::CONTENT_TYPE,
 };
 
+const PROGRESS_BUFFER_SIZE: usize = 64 * 1024;
+
+#[cfg(all(feature = "json", not(feature = "json-path")))]
+fn from_json_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let obj = serde_json::from_reader(reader)?;
+    Ok(obj)
+}
+
+#[cfg(feature = "json-path")]
+fn from_json_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let obj = serde_path_to_error::deserialize(&mut de)?;
+    de.end()?;
+    Ok(obj)
+}
+
 #[cfg(feature = "charsets")]
 fn get_charset(headers: &HeaderMap, default_charset: Option<Charset>) -> Charset {
-    if let Some(value) = headers.get(CONTENT_TYPE) {
-        let bytes = value.as_bytes();
-        if let Some(scol) = bytes.iter().position(|&b| b == b';') {
-            let rhs = trim_byte(b' ', &bytes[scol + 1..]);
-            if rhs.starts_with(b"charset=") {
-                if let Some(enc) = Encoding::for_label(&rhs[8..]) {
-                    return enc;
-                }
-            }
-        }
-    }
-    default_charset.unwrap_or(charsets::WINDOWS_1252)
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(MediaType::parse)
+        .and_then(|media_type| Encoding::for_label(media_type.charset()?.as_bytes()))
+        .or(default_charset)
+        .unwrap_or(charsets::WINDOWS_1252)
 }
 
 /// The `ResponseReader` is used to read the body of a response.
@@ -49,8 +77,13 @@ fn get_charset(headers: &HeaderMap, default_charset: Option<Charset>) -> Charset
 /// helper methods, they process the data stream properly.
 pub struct ResponseReader {
     inner: CompressedReader,
+    limit: SizeLimit,
     #[cfg(feature = "charsets")]
     charset: Charset,
+    #[cfg(feature = "json")]
+    har: Option<HarBody>,
+    observer: Option<BodyObserver>,
+    checksum: Option<ChecksumVerifier>,
 }
 
 impl ResponseReader {
@@ -58,13 +91,38 @@ impl ResponseReader {
     pub(crate) fn new(headers: &HeaderMap, request: &PreparedRequest, reader: CompressedReader) -> ResponseReader {
         ResponseReader {
             inner: reader,
-            charset: get_charset(&headers, request.default_charset),
+            limit: SizeLimit::new(request.settings.max_response_size),
+            charset: get_charset(headers, request.settings.default_charset),
+            #[cfg(feature = "json")]
+            har: None,
+            observer: None,
+            checksum: None,
         }
     }
 
     #[cfg(not(feature = "charsets"))]
-    pub(crate) fn new(_: &HeaderMap, _: &PreparedRequest, reader: CompressedReader) -> ResponseReader {
-        ResponseReader { inner: reader }
+    pub(crate) fn new(_: &HeaderMap, request: &PreparedRequest, reader: CompressedReader) -> ResponseReader {
+        ResponseReader {
+            inner: reader,
+            limit: SizeLimit::new(request.settings.max_response_size),
+            #[cfg(feature = "json")]
+            har: None,
+            observer: None,
+            checksum: None,
+        }
+    }
+
+    #[cfg(feature = "json")]
+    pub(crate) fn set_har(&mut self, har: HarBody) {
+        self.har = Some(har);
+    }
+
+    pub(crate) fn set_observer(&mut self, observer: BodyObserver) {
+        self.observer = Some(observer);
+    }
+
+    pub(crate) fn set_checksum(&mut self, checksum: Checksum) {
+        self.checksum = Some(ChecksumVerifier::new(checksum));
     }
 
     /// Write the response to any object that implements `Write`.
@@ -72,10 +130,110 @@ impl ResponseReader {
     where
         W: Write,
     {
-        let n = io::copy(&mut self.inner, &mut writer)?;
+        let n = io::copy(&mut self, &mut writer)?;
         Ok(n)
     }
 
+    /// Write the response to any object that implements `Write`, reporting the progress.
+    ///
+    /// After each write, `progress` is called with the number of bytes written so far and the value of
+    /// `content_length`. This is useful to display the progress of a large download.
+    pub fn write_to_with_progress<W, F>(mut self, mut writer: W, mut progress: F) -> Result<u64>
+    where
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        let content_length = self.content_length();
+        let mut buf = vec![0; PROGRESS_BUFFER_SIZE];
+        let mut written = 0;
+        loop {
+            let n = match self.read(&mut buf) {
+                Ok(0) => return Ok(written),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            writer.write_all(&buf[..n])?;
+            written += n as u64;
+            progress(written, content_length);
+        }
+    }
+
+    /// Write the response to the file at the given path, replacing it atomically.
+    ///
+    /// The body is written to a temporary file next to the given path, with the `.part` suffix, which is
+    /// renamed to the given path once the whole body was received and written to the disk. When an error
+    /// occurs, the temporary file is removed and the file at the given path is left untouched. A body shorter
+    /// than its `Content-Length` is an error.
+    pub fn write_to_file<P>(self, path: P) -> Result<u64>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let part_path = part_path(path);
+        let result = File::create(&part_path)
+            .map_err(Error::from)
+            .and_then(|mut file| {
+                let written = self.write_to(&mut file)?;
+                file.sync_all()?;
+                Ok(written)
+            })
+            .and_then(|written| {
+                fs::rename(&part_path, path)?;
+                Ok(written)
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&part_path);
+        }
+        result
+    }
+
+    /// Get the number of bytes of the body which will be read, if it is known.
+    ///
+    /// The length is known when the response has a `Content-Length` header and its body is not decompressed.
+    pub fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+
+    /// Check if the body is decompressed while it is read.
+    pub(crate) fn is_decoded(&self) -> bool {
+        self.inner.is_decoded()
+    }
+
+    /// Get a reader of the body as it was sent, without decoding its codings. Nothing must have been read yet.
+    ///
+    /// The checksum set on the request applies to the decoded body, it is not verified when the body was to
+    /// be decoded.
+    pub(crate) fn into_raw(self) -> ResponseReader {
+        let checksum = if self.is_decoded() { None } else { self.checksum };
+        ResponseReader {
+            inner: CompressedReader::Plain(self.inner.into_raw()),
+            checksum,
+            ..self
+        }
+    }
+
+    /// Create an iterator over the chunks of the response.
+    ///
+    /// When the response uses the chunked transfer encoding and its body is not compressed, each item is
+    /// the content of a chunk, as sent by the server. This is useful for servers which use the chunks to
+    /// delimit messages. Otherwise, the whole body is returned as a single item.
+    pub fn chunks(self) -> Chunks {
+        let inner = match self.inner {
+            CompressedReader::Plain(BodyReader::Chunked(reader)) => ChunksInner::Chunked(reader),
+            reader => ChunksInner::Whole(reader),
+        };
+        Chunks {
+            inner,
+            limit: self.limit,
+            done: false,
+            #[cfg(feature = "json")]
+            har: self.har,
+            observer: self.observer,
+            checksum: self.checksum,
+        }
+    }
+
     /// Read the response to a `Vec` of bytes.
     pub fn bytes(self) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
@@ -125,6 +283,19 @@ impl ResponseReader {
         Ok(text)
     }
 
+    /// Read the response to a `String`, decoding with the charset of the given label, such as `iso-8859-1`.
+    ///
+    /// This will ignore the encoding from the response headers and the default encoding, if any. An
+    /// `UnknownCharset` error is returned if the label is not known.
+    ///
+    /// This method only exists when the `charsets` feature is enabled.
+    #[cfg(feature = "charsets")]
+    pub fn text_with_charset(self, label: &str) -> Result<String> {
+        let charset =
+            Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| ErrorKind::UnknownCharset(label.to_string()))?;
+        self.text_with(charset)
+    }
+
     /// Create a `TextReader` from this `ResponseReader`.
     ///
     /// If the response headers contain charset information, that charset will be used to decode the body.
@@ -153,7 +324,7 @@ impl ResponseReader {
     /// This method ignores headers and the default encoding.
     pub fn text_utf8(mut self) -> Result<String> {
         let mut text = String::new();
-        self.inner.read_to_string(&mut text)?;
+        self.read_to_string(&mut text)?;
         Ok(text)
     }
 
@@ -172,9 +343,7 @@ impl ResponseReader {
     where
         T: DeserializeOwned,
     {
-        let reader = BufReader::new(self.text_reader());
-        let obj = serde_json::from_reader(reader)?;
-        Ok(obj)
+        from_json_reader(BufReader::new(self.text_reader()))
     }
 
     /// Parse the response as a JSON object and return it.
@@ -205,19 +374,177 @@ impl ResponseReader {
     where
         T: DeserializeOwned,
     {
-        let reader = BufReader::new(self);
-        let obj = serde_json::from_reader(reader)?;
-        Ok(obj)
+        from_json_reader(BufReader::new(self))
     }
 }
 
 impl Read for ResponseReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        if self.limit.max.is_some() && self.limit.read == 0 {
+            if let Some(len) = self.inner.content_length() {
+                self.limit.check(len)?;
+            }
+        }
+        let buf = self.limit.bound(buf);
+        let n = self.inner.read(buf)?;
+        self.limit.add(n)?;
+        if let Some(checksum) = &mut self.checksum {
+            match n {
+                0 if !buf.is_empty() => checksum.verify()?,
+                n => checksum.update(&buf[..n]),
+            }
+        }
+        #[cfg(feature = "json")]
+        {
+            if let Some(har) = &self.har {
+                har.record(&buf[..n]);
+            }
+        }
+        if let Some(observer) = &mut self.observer {
+            match n {
+                0 if !buf.is_empty() => observer.complete(),
+                n => observer.record(n),
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// The maximum size of a response body, and the number of bytes read so far.
+#[derive(Debug, Clone, Copy)]
+struct SizeLimit {
+    max: Option<u64>,
+    read: u64,
+}
+
+impl SizeLimit {
+    fn new(max: Option<u64>) -> SizeLimit {
+        SizeLimit { max, read: 0 }
+    }
+
+    /// Fail with a `ResponseTooLarge` error if the given size exceeds the limit.
+    fn check(&self, size: u64) -> io::Result<()> {
+        match self.max {
+            Some(max) if size > max => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                Error::from(ErrorKind::ResponseTooLarge),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Shorten the buffer so that a read cannot go further than one byte past the limit.
+    fn bound<'a>(&self, buf: &'a mut [u8]) -> &'a mut [u8] {
+        match self.max {
+            Some(max) => {
+                let allowed = max.saturating_sub(self.read).saturating_add(1);
+                let len = allowed.min(buf.len() as u64) as usize;
+                &mut buf[..len]
+            }
+            None => buf,
+        }
+    }
+
+    fn add(&mut self, n: usize) -> io::Result<()> {
+        self.read += n as u64;
+        self.check(self.read)
+    }
+}
+
+enum ChunksInner {
+    Chunked(ChunkedReader<BaseStream>),
+    Whole(CompressedReader),
+}
+
+/// An iterator over the chunks of a response, created by the `chunks` method of `Response`.
+pub struct Chunks {
+    inner: ChunksInner,
+    limit: SizeLimit,
+    done: bool,
+    #[cfg(feature = "json")]
+    har: Option<HarBody>,
+    observer: Option<BodyObserver>,
+    checksum: Option<ChecksumVerifier>,
+}
+
+impl Iterator for Chunks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+        let res = match &mut self.inner {
+            ChunksInner::Chunked(reader) => {
+                let chunk = reader.read_chunk();
+                if reader.is_done() {
+                    release(reader.get_mut());
+                }
+                chunk
+            }
+            ChunksInner::Whole(reader) => {
+                let mut buf = Vec::new();
+                let res = match self.limit.max {
+                    Some(max) => reader.take(max.saturating_add(1)).read_to_end(&mut buf),
+                    None => reader.read_to_end(&mut buf),
+                };
+                res.map(|_| Some(buf))
+            }
+        };
+        let res = res.and_then(|chunk| {
+            if let Some(bytes) = &chunk {
+                self.limit.add(bytes.len())?;
+            }
+            if let Some(checksum) = &mut self.checksum {
+                if let Some(bytes) = &chunk {
+                    checksum.update(bytes);
+                }
+                if chunk.is_none() || matches!(self.inner, ChunksInner::Whole(_)) {
+                    checksum.verify()?;
+                }
+            }
+            Ok(chunk)
+        });
+        match res {
+            Ok(Some(chunk)) => {
+                #[cfg(feature = "json")]
+                {
+                    if let Some(har) = &self.har {
+                        har.record(&chunk);
+                    }
+                }
+                self.done = matches!(self.inner, ChunksInner::Whole(_));
+                if let Some(observer) = &mut self.observer {
+                    observer.record(chunk.len());
+                    if self.done {
+                        observer.complete();
+                    }
+                }
+                Some(Ok(chunk))
+            }
+            Ok(None) => {
+                self.done = true;
+                if let Some(observer) = &mut self.observer {
+                    observer.complete();
+                }
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err.into()))
+            }
+        }
     }
 }
 
+/// Get the path of the temporary file of `write_to_file`.
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".part");
+    PathBuf::from(name)
+}
+
 #[cfg(test)]
 #[cfg(feature = "charsets")]
 mod tests {
@@ -246,6 +573,16 @@ mod tests {
         assert_eq!(get_charset(&headers, None), charsets::UTF_8);
     }
 
+    #[test]
+    fn test_get_charset_from_header_params() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_bytes(&b"text/html;format=flowed; Charset=\"iso-8859-2\""[..]).unwrap(),
+        );
+        assert_eq!(get_charset(&headers, None), charsets::ISO_8859_2);
+    }
+
     #[test]
     fn test_get_charset_from_default() {
         let headers = HeaderMap::new();
@@ -257,5 +594,4 @@ mod tests {
         let headers = HeaderMap::new();
         assert_eq!(get_charset(&headers, None), charsets::WINDOWS_1252);
     }
-
 }
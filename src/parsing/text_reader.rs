@@ -1,14 +1,20 @@
 use std::io::{self, BufRead, Read};
+use std::mem;
 
 use encoding_rs::{CoderResult, Decoder};
 
 use crate::charsets::Charset;
 
+/// Size of the buffer of decoded text used by the `BufRead` implementation.
+const BUFFER_SIZE: usize = 8 * 1024;
+
 /// `TextReader` converts bytes in a specific charset to bytes in UTF-8.
 ///
 /// It can be used to convert a stream of text in a specific charset into a stream
 /// of UTF-8 encoded bytes. The `Read::read_to_string` method can be used to convert
-/// the stream of UTF-8 bytes into a `String`.
+/// the stream of UTF-8 bytes into a `String`. `TextReader` also implements `BufRead`,
+/// so that the text can be processed line by line with `BufRead::lines` without
+/// holding all of it in memory.
 pub struct TextReader<R>
 where
     R: BufRead,
@@ -16,6 +22,10 @@ where
     inner: R,
     decoder: Decoder,
     eof: bool,
+    /// Decoded text which was not consumed yet, for the `BufRead` implementation.
+    buffer: Vec<u8>,
+    pos: usize,
+    cap: usize,
 }
 
 impl<R> TextReader<R>
@@ -25,18 +35,17 @@ where
     /// Create a new `TextReader` with the given charset.
     pub fn new(inner: R, charset: Charset) -> TextReader<R> {
         TextReader {
-            inner: inner,
+            inner,
             decoder: charset.new_decoder(),
             eof: false,
+            buffer: Vec::new(),
+            pos: 0,
+            cap: 0,
         }
     }
-}
 
-impl<R> Read for TextReader<R>
-where
-    R: BufRead,
-{
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    /// Decode the text of the inner reader into `buf`.
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.eof {
             return Ok(0);
         }
@@ -47,7 +56,7 @@ where
             let src = self.inner.fill_buf()?;
             if src.is_empty() {
                 // inner has reached EOF, write last to the buffer.
-                let (res, _, written, _) = self.decoder.decode_to_utf8(src, buf, true);
+                let (res, _, written, _) = self.decoder.decode_to_utf8(src, &mut buf[total_written..], true);
                 total_written += written;
 
                 match res {
@@ -62,14 +71,19 @@ where
                     }
                 }
             } else {
-                let (res, read, written, _) = self.decoder.decode_to_utf8(src, buf, false);
+                let (res, read, written, _) = self.decoder.decode_to_utf8(src, &mut buf[total_written..], false);
 
                 self.inner.consume(read);
                 total_written += written;
 
                 match res {
+                    CoderResult::InputEmpty if total_written > 0 => {
+                        // read all the bytes available in src, return what was decoded instead of
+                        // blocking while waiting for more input
+                        break;
+                    }
                     CoderResult::InputEmpty => {
-                        // read all the bytes available in src, read more
+                        // read all the bytes available in src without producing output, read more
                         continue;
                     }
                     CoderResult::OutputFull => {
@@ -84,6 +98,42 @@ where
     }
 }
 
+impl<R> Read for TextReader<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.cap {
+            let n = buf.len().min(self.cap - self.pos);
+            buf[..n].copy_from_slice(&self.buffer[self.pos..self.pos + n]);
+            self.pos += n;
+            return Ok(n);
+        }
+        self.decode(buf)
+    }
+}
+
+impl<R> BufRead for TextReader<R>
+where
+    R: BufRead,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.cap {
+            let mut buffer = mem::take(&mut self.buffer);
+            buffer.resize(BUFFER_SIZE, 0);
+            let result = self.decode(&mut buffer);
+            self.buffer = buffer;
+            self.pos = 0;
+            self.cap = result?;
+        }
+        Ok(&self.buffer[self.pos..self.cap])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.cap);
+    }
+}
+
 #[test]
 fn test_stream_decoder_utf8() {
     let mut reader = TextReader::new("québec".as_bytes(), crate::charsets::UTF_8);
@@ -106,10 +156,7 @@ fn test_stream_decoder_latin1() {
 
 #[test]
 fn test_string_reader_large_buffer_latin1() {
-    let mut buf = vec![];
-    for _ in 0..10_000 {
-        buf.push(201);
-    }
+    let buf = vec![201; 10_000];
     let mut reader = TextReader::new(&buf[..], crate::charsets::WINDOWS_1252);
 
     let mut text = String::new();
@@ -119,3 +166,41 @@ fn test_string_reader_large_buffer_latin1() {
         assert_eq!(c, 'É');
     }
 }
+
+#[test]
+fn test_stream_decoder_small_reads() {
+    use std::io::BufReader;
+
+    // A reader which returns one byte at a time.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    let mut reader = TextReader::new(BufReader::new(OneByte("québec".as_bytes())), crate::charsets::UTF_8);
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+
+    assert_eq!(text, "québec");
+}
+
+#[test]
+fn test_lines() {
+    let text = "première ligne\r\ndeuxième ligne\n".repeat(1000);
+    let (encoded, _, _) = crate::charsets::WINDOWS_1252.encode(&text);
+    let reader = TextReader::new(&encoded[..], crate::charsets::WINDOWS_1252);
+
+    let lines: Vec<String> = reader.lines().collect::<io::Result<_>>().unwrap();
+    assert_eq!(lines.len(), 2000);
+    assert_eq!(lines[0], "première ligne");
+    assert_eq!(lines[1999], "deuxième ligne");
+}
@@ -0,0 +1,342 @@
+//! Pool of idle connections which can be reused by further requests.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::resolver::Resolve;
+use crate::settings::Settings;
+use crate::streams::Stream;
+
+const DEFAULT_MAX_IDLE_PER_HOST: usize = 8;
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Margin taken on the idle timeout announced by the server, so that connections are not reused while the
+/// server is closing them.
+const KEEP_ALIVE_MARGIN: Duration = Duration::from_secs(1);
+
+/// Identifies the connections which can be used for a request: same scheme, host, port, proxy and Unix
+/// domain socket, opened with the same network interface, local address, resolver and socket options.
+///
+/// Resolvers are compared by identity: the connections opened with a resolver set on a `Session` are shared
+/// by its requests, while a resolver set on a single request never reuses another connection.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+    proxy: Option<String>,
+    unix_socket: Option<PathBuf>,
+    interface: Option<String>,
+    local_address: Option<IpAddr>,
+    resolver: Option<ResolverId>,
+    tos: Option<u8>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    send_buffer_size: Option<usize>,
+    recv_buffer_size: Option<usize>,
+}
+
+impl PoolKey {
+    pub(crate) fn new(url: &Url, proxy: Option<&Url>, unix_socket: Option<PathBuf>) -> Option<PoolKey> {
+        // The URLs of Unix domain sockets do not need a port.
+        let port = url
+            .port_or_known_default()
+            .or_else(|| unix_socket.as_ref().map(|_| 0))?;
+        Some(PoolKey {
+            scheme: url.scheme().to_string(),
+            host: url.host_str()?.to_ascii_lowercase(),
+            port,
+            proxy: proxy.map(|proxy| proxy.as_str().to_string()),
+            unix_socket,
+            interface: None,
+            local_address: None,
+            resolver: None,
+            tos: None,
+            tcp_nodelay: false,
+            tcp_keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        })
+    }
+
+    /// Add the settings used to open the connections to the key, so that a connection is only reused by
+    /// requests which would open the same one.
+    pub(crate) fn with_settings(mut self, settings: &Settings) -> PoolKey {
+        self.interface = settings.interface.clone();
+        self.local_address = settings.local_address;
+        self.resolver = settings.resolver.clone().map(ResolverId);
+        self.tos = settings.tos;
+        self.tcp_nodelay = settings.tcp_nodelay;
+        self.tcp_keepalive = settings.tcp_keepalive;
+        self.send_buffer_size = settings.send_buffer_size;
+        self.recv_buffer_size = settings.recv_buffer_size;
+        self
+    }
+}
+
+/// A resolver compared by identity. The key keeps the resolver alive, so that its address is not reused by
+/// another one.
+#[derive(Debug, Clone)]
+struct ResolverId(Arc<dyn Resolve>);
+
+impl ResolverId {
+    fn address(&self) -> *const u8 {
+        Arc::as_ptr(&self.0) as *const u8
+    }
+}
+
+impl PartialEq for ResolverId {
+    fn eq(&self, other: &ResolverId) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for ResolverId {}
+
+impl Hash for ResolverId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
+
+/// The parameters of the `Keep-Alive` header of a response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct KeepAlive {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) max: Option<u32>,
+}
+
+impl KeepAlive {
+    /// Parse a `Keep-Alive` header such as `timeout=5, max=100`. Unknown parameters are ignored.
+    pub(crate) fn parse(value: &str) -> KeepAlive {
+        let mut keep_alive = KeepAlive::default();
+        for param in value.split(',') {
+            let mut parts = param.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts.next().unwrap_or("").trim().trim_matches('"');
+            if name.eq_ignore_ascii_case("timeout") {
+                keep_alive.timeout = value.parse().ok().map(Duration::from_secs);
+            } else if name.eq_ignore_ascii_case("max") {
+                keep_alive.max = value.parse().ok();
+            }
+        }
+        keep_alive
+    }
+}
+
+/// A connection taken out of the pool, or given back to it.
+pub(crate) struct Pooled {
+    pub(crate) stream: Stream,
+    pub(crate) created: Instant,
+    pub(crate) requests: u32,
+}
+
+struct Idle {
+    conn: Pooled,
+    expires: Instant,
+}
+
+/// A pool of connections kept alive between requests, shared between requests.
+///
+/// When a `Request` uses a `ConnectionPool`, it sends a `Connection: keep-alive` header, and its connection
+/// is given back to the pool once the body of the response has been read entirely. Further requests to the
+/// same scheme, host and port, through the same proxy, reuse the idle connections of the pool instead of
+/// opening new ones, which saves the TCP and TLS handshakes.
+///
+/// A connection is not given back to the pool when the server sends a `Connection: close` header, when the
+/// body of the response is delimited by the end of the connection, or when the response is dropped before
+/// its body is read. The `timeout` and `max` parameters of the `Keep-Alive` header of the responses are
+/// honored. If a reused connection turns out to be closed by the server, the request is sent again on a
+/// new connection when it is safe to do so.
+///
+/// Cloning a `ConnectionPool` is cheap, the clones share the same connections.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    idle: Arc<Mutex<HashMap<PoolKey, Vec<Idle>>>>,
+    max_idle_per_host: usize,
+    idle_timeout: Duration,
+    max_requests_per_connection: Option<u32>,
+    max_connection_age: Option<Duration>,
+}
+
+impl std::fmt::Debug for ConnectionPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ConnectionPool")
+            .field("idle_connections", &self.idle_connections())
+            .field("max_idle_per_host", &self.max_idle_per_host)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_requests_per_connection", &self.max_requests_per_connection)
+            .field("max_connection_age", &self.max_connection_age)
+            .finish()
+    }
+}
+
+impl Default for ConnectionPool {
+    fn default() -> ConnectionPool {
+        ConnectionPool {
+            idle: Arc::default(),
+            max_idle_per_host: DEFAULT_MAX_IDLE_PER_HOST,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_requests_per_connection: None,
+            max_connection_age: None,
+        }
+    }
+}
+
+impl ConnectionPool {
+    /// Create a new, empty `ConnectionPool`.
+    pub fn new() -> ConnectionPool {
+        ConnectionPool::default()
+    }
+
+    /// Set the maximum number of idle connections kept for each host.
+    ///
+    /// The oldest idle connections are closed when there are too many. This value defaults to 8.
+    pub fn max_idle_per_host(mut self, max_idle_per_host: usize) -> ConnectionPool {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    /// Set how long an idle connection is kept in the pool.
+    ///
+    /// A shorter timeout announced by the server in a `Keep-Alive` header takes precedence. This value
+    /// defaults to 90 seconds.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> ConnectionPool {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set the maximum number of requests sent on a connection, after which it is closed.
+    ///
+    /// By default, there is no limit.
+    pub fn max_requests_per_connection(mut self, max_requests: u32) -> ConnectionPool {
+        self.max_requests_per_connection = Some(max_requests);
+        self
+    }
+
+    /// Set the maximum age of a connection, after which it is not reused anymore.
+    ///
+    /// This is useful to spread the requests over the servers behind a load balancer, or to pick up DNS
+    /// changes. By default, there is no limit.
+    pub fn max_connection_age(mut self, max_age: Duration) -> ConnectionPool {
+        self.max_connection_age = Some(max_age);
+        self
+    }
+
+    /// Get the number of idle connections in the pool.
+    pub fn idle_connections(&self) -> usize {
+        self.idle.lock().unwrap().values().map(Vec::len).sum()
+    }
+
+    /// Close all the idle connections of the pool.
+    pub fn close_idle_connections(&self) {
+        self.idle.lock().unwrap().clear();
+    }
+
+    fn is_expired(&self, conn: &Pooled) -> bool {
+        self.max_requests_per_connection.is_some_and(|max| conn.requests >= max)
+            || self.max_connection_age.is_some_and(|age| conn.created.elapsed() >= age)
+    }
+
+    /// Take the most recently used idle connection for the given key, dropping the expired ones.
+    pub(crate) fn checkout(&self, key: &PoolKey) -> Option<Pooled> {
+        let mut idle = self.idle.lock().unwrap();
+        let conns = idle.get_mut(key)?;
+        let now = Instant::now();
+        let mut found = None;
+        while let Some(entry) = conns.pop() {
+            if entry.expires > now && !self.is_expired(&entry.conn) {
+                found = Some(entry.conn);
+                break;
+            }
+        }
+        if conns.is_empty() {
+            idle.remove(key);
+        }
+        found
+    }
+
+    /// Give back a connection after a response was read entirely.
+    pub(crate) fn put(&self, key: PoolKey, conn: Pooled, keep_alive: KeepAlive) {
+        if self.max_idle_per_host == 0 || keep_alive.max == Some(0) || self.is_expired(&conn) {
+            return;
+        }
+        let timeout = match keep_alive.timeout {
+            Some(timeout) => self.idle_timeout.min(timeout.saturating_sub(KEEP_ALIVE_MARGIN)),
+            None => self.idle_timeout,
+        };
+        let entry = Idle {
+            conn,
+            expires: Instant::now() + timeout,
+        };
+
+        let mut idle = self.idle.lock().unwrap();
+        let conns = idle.entry(key).or_default();
+        conns.push(entry);
+        if conns.len() > self.max_idle_per_host {
+            conns.remove(0);
+        }
+    }
+}
+
+#[test]
+fn test_parse_keep_alive() {
+    assert_eq!(
+        KeepAlive::parse("timeout=5, max=100"),
+        KeepAlive {
+            timeout: Some(Duration::from_secs(5)),
+            max: Some(100),
+        }
+    );
+    assert_eq!(
+        KeepAlive::parse("Max=0"),
+        KeepAlive {
+            timeout: None,
+            max: Some(0),
+        }
+    );
+    assert_eq!(KeepAlive::parse("foo, timeout=x"), KeepAlive::default());
+}
+
+#[test]
+fn test_pool_key() {
+    let a = PoolKey::new(&Url::parse("http://Example.com/foo").unwrap(), None, None).unwrap();
+    let b = PoolKey::new(&Url::parse("http://example.com:80/bar").unwrap(), None, None).unwrap();
+    let c = PoolKey::new(&Url::parse("https://example.com/").unwrap(), None, None).unwrap();
+    let d = PoolKey::new(&Url::parse("http://example.com/").unwrap(), None, Some("/tmp/s".into())).unwrap();
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_ne!(a, d);
+
+    let settings = Settings::new();
+    assert_eq!(a.clone().with_settings(&settings), b.clone().with_settings(&settings));
+    assert_ne!(
+        a.clone().with_settings(&settings),
+        a.clone().with_settings(&settings.clone().interface("eth1"))
+    );
+    assert_ne!(
+        a.clone().with_settings(&settings),
+        a.clone().with_settings(&settings.clone().tos(0x10))
+    );
+    assert_ne!(
+        a.clone().with_settings(&settings),
+        a.clone()
+            .with_settings(&settings.clone().local_address("127.0.0.1".parse().unwrap()))
+    );
+
+    let settings = settings.resolver(crate::StaticResolver::new());
+    assert_eq!(
+        a.clone().with_settings(&settings),
+        a.clone().with_settings(&settings.clone())
+    );
+    assert_ne!(
+        a.clone().with_settings(&settings),
+        a.with_settings(&settings.resolver(crate::StaticResolver::new()))
+    );
+}
@@ -0,0 +1,149 @@
+//! Serialization of values to query parameters.
+
+use std::fmt;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::Error as _;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::Result;
+
+/// The fields of a map, in the order in which they were serialized.
+struct Fields(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Fields, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map or a struct")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Fields, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+fn scalar(key: &str, value: Value) -> Result<Option<String>> {
+    Ok(match value {
+        Value::Null => None,
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::String(value) => Some(value),
+        Value::Array(_) | Value::Object(_) => {
+            return Err(serde_json::Error::custom(format!("query parameter {} is not a scalar value", key)).into())
+        }
+    })
+}
+
+/// Convert a serializable map or struct to a list of query parameters.
+///
+/// The fields are kept in order. The fields which are `None` are skipped, and the items of sequences are
+/// repeated with the same key. Nested maps and structs are not supported.
+pub(crate) fn to_query_pairs<T>(value: &T) -> Result<Vec<(String, String)>>
+where
+    T: Serialize + ?Sized,
+{
+    // The JSON text keeps the order of the fields, unlike `serde_json::Value`.
+    let json = serde_json::to_string(value)?;
+    let Fields(fields) = serde_json::from_str(&json)
+        .map_err(|err| serde_json::Error::custom(format!("query parameters must be a map or a struct: {}", err)))?;
+
+    let mut pairs = Vec::new();
+    for (key, value) in fields {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    if let Some(item) = scalar(&key, item)? {
+                        pairs.push((key.clone(), item));
+                    }
+                }
+            }
+            value => {
+                if let Some(value) = scalar(&key, value)? {
+                    pairs.push((key, value));
+                }
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::to_query_pairs;
+
+    #[derive(Serialize)]
+    struct Search<'a> {
+        q: &'a str,
+        page: u32,
+        exact: bool,
+        lang: Option<&'a str>,
+        tags: Vec<&'a str>,
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_struct() {
+        let search = Search {
+            q: "rust http",
+            page: 2,
+            exact: false,
+            lang: None,
+            tags: vec!["a", "b"],
+        };
+        assert_eq!(
+            to_query_pairs(&search).unwrap(),
+            pairs(&[
+                ("q", "rust http"),
+                ("page", "2"),
+                ("exact", "false"),
+                ("tags", "a"),
+                ("tags", "b")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = BTreeMap::new();
+        map.insert("b", 1.5);
+        map.insert("a", 2.0);
+        assert_eq!(to_query_pairs(&map).unwrap(), pairs(&[("a", "2.0"), ("b", "1.5")]));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(to_query_pairs(&42).is_err());
+        assert!(to_query_pairs(&[("a", "b")]).is_err());
+
+        let mut nested = BTreeMap::new();
+        nested.insert("a", BTreeMap::<&str, &str>::new());
+        assert!(to_query_pairs(&nested).is_err());
+    }
+}
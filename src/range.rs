@@ -0,0 +1,169 @@
+//! Range requests and resumable downloads.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+
+use http::header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use http::StatusCode;
+
+use crate::error::{ErrorKind, InvalidResponseKind, Result};
+use crate::request::RequestBuilder;
+
+/// Parse a `Content-Range` header of the form `bytes start-end/length`.
+pub(crate) fn parse_content_range(headers: &HeaderMap) -> Option<(u64, u64, u64)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let value = value.trim().strip_prefix("bytes ")?;
+    let (range, length) = value.split_at(value.find('/')?);
+    let (start, end) = range.split_at(range.find('-')?);
+    let (start, end, length) = (start.parse().ok()?, end[1..].parse().ok()?, length[1..].parse().ok()?);
+    if start > end || end >= length {
+        return None;
+    }
+    Some((start, end, length))
+}
+
+/// Parse the length of the resource in the `Content-Range` header of a `416 Range Not Satisfiable`
+/// response, of the form `bytes */length`.
+fn parse_unsatisfied_range(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    value.trim().strip_prefix("bytes */")?.parse().ok()
+}
+
+/// Format the value of a `Range` header requesting the given bytes.
+///
+/// # Panics
+/// This function will panic if the range is empty.
+pub(crate) fn format_range<R>(range: R) -> String
+where
+    R: RangeBounds<u64>,
+{
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => Some(end),
+        Bound::Excluded(&end) => Some(end.checked_sub(1).expect("the range must not be empty")),
+        Bound::Unbounded => None,
+    };
+    match end {
+        Some(end) => {
+            assert!(start <= end, "the range must not be empty");
+            format!("bytes={}-{}", start, end)
+        }
+        None => format!("bytes={}-", start),
+    }
+}
+
+/// Get the path of the file which keeps the validator of a partial download.
+fn validator_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".resume");
+    PathBuf::from(name)
+}
+
+/// Get the validator to send in the `If-Range` header: a strong entity tag, or the last modification date.
+fn validator(headers: &HeaderMap) -> Option<&str> {
+    let etag = headers
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"));
+    etag.or_else(|| headers.get(LAST_MODIFIED).and_then(|value| value.to_str().ok()))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Download the resource to the given file, resuming a previous partial download.
+pub(crate) fn download_resumable(builder: RequestBuilder, path: &Path) -> Result<u64> {
+    let validator_path = validator_path(path);
+    let partial = match (fs::metadata(path), fs::read_to_string(&validator_path)) {
+        (Ok(metadata), Ok(validator)) if metadata.len() > 0 => Some((metadata.len(), validator)),
+        _ => None,
+    };
+
+    #[cfg(feature = "compress")]
+    let builder = builder.allow_compression(false);
+    let builder = match &partial {
+        Some((offset, validator)) => {
+            debug!("resuming the download of {} at byte {}", path.display(), offset);
+            builder
+                .header(RANGE, format_range(offset..))
+                .try_header(IF_RANGE, validator.trim())?
+        }
+        None => builder,
+    };
+    let resp = builder.send()?;
+
+    match (resp.status(), partial) {
+        (StatusCode::PARTIAL_CONTENT, Some((offset, _))) => {
+            let (start, _, length) = parse_content_range(resp.headers()).ok_or(InvalidResponseKind::ContentRange)?;
+            if start != offset {
+                return Err(InvalidResponseKind::ContentRange.into());
+            }
+            let file = OpenOptions::new().append(true).open(path)?;
+            let written = resp.write_to(file)?;
+            if offset + written == length {
+                remove_if_exists(&validator_path)?;
+            }
+            Ok(offset + written)
+        }
+        (StatusCode::RANGE_NOT_SATISFIABLE, Some((offset, _))) => {
+            if parse_unsatisfied_range(resp.headers()) == Some(offset) {
+                debug!("the download of {} was already complete", path.display());
+                remove_if_exists(&validator_path)?;
+                return Ok(offset);
+            }
+            // The partial file does not match the resource, the download starts over on the next call.
+            remove_if_exists(&validator_path)?;
+            fs::remove_file(path)?;
+            Err(InvalidResponseKind::ContentRange.into())
+        }
+        (status, _) if status.is_success() && status != StatusCode::PARTIAL_CONTENT => {
+            match validator(resp.headers()) {
+                Some(validator) => fs::write(&validator_path, validator)?,
+                None => remove_if_exists(&validator_path)?,
+            }
+            let written = resp.write_to(File::create(path)?)?;
+            remove_if_exists(&validator_path)?;
+            Ok(written)
+        }
+        (StatusCode::PARTIAL_CONTENT, None) => Err(InvalidResponseKind::ContentRange.into()),
+        (status, _) => Err(ErrorKind::StatusCode(status).into()),
+    }
+}
+
+#[test]
+fn test_parse_content_range() {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_RANGE, "bytes 0-99/1234".parse().unwrap());
+    assert_eq!(parse_content_range(&headers), Some((0, 99, 1234)));
+    headers.insert(CONTENT_RANGE, "bytes 100-99/1234".parse().unwrap());
+    assert_eq!(parse_content_range(&headers), None);
+    headers.insert(CONTENT_RANGE, "bytes */1234".parse().unwrap());
+    assert_eq!(parse_content_range(&headers), None);
+    assert_eq!(parse_unsatisfied_range(&headers), Some(1234));
+}
+
+#[test]
+fn test_format_range() {
+    assert_eq!(format_range(0..100), "bytes=0-99");
+    assert_eq!(format_range(10..=10), "bytes=10-10");
+    assert_eq!(format_range(500..), "bytes=500-");
+    assert_eq!(format_range(..100), "bytes=0-99");
+    assert_eq!(format_range(..), "bytes=0-");
+}
+
+#[test]
+#[should_panic]
+fn test_format_empty_range() {
+    format_range(10..10);
+}
@@ -0,0 +1,112 @@
+//! Rate limiting of requests with a token bucket.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::date::parse_http_date;
+
+/// The longest `Retry-After` delay which is waited for before sending a request again.
+pub(crate) const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+struct Bucket {
+    /// Tokens available. It goes below zero when requests are waiting for tokens.
+    tokens: f64,
+    last: Instant,
+}
+
+/// `RateLimiter` limits the rate at which requests are sent, using a token bucket.
+///
+/// A `RateLimiter` allows `requests` requests per period, which can be sent in a burst, and then one
+/// request each time a token is added back to the bucket. A request which exceeds the rate waits until a
+/// token is available, the waiting requests are served in order.
+///
+/// Cloning a `RateLimiter` is cheap, the clones share the same bucket. It can be shared by several
+/// requests with `RequestBuilder::rate_limiter`, and is shared by the requests of a `Session` set up
+/// with `Session::rate_limit`.
+#[derive(Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+    capacity: f64,
+    /// Time needed to add a token to the bucket.
+    interval: Duration,
+}
+
+impl RateLimiter {
+    /// Create a new `RateLimiter` allowing `requests` requests per `period`.
+    ///
+    /// # Panics
+    /// This method will panic if `requests` is zero.
+    pub fn new(requests: u32, period: Duration) -> RateLimiter {
+        assert!(requests > 0, "the rate limit must allow at least one request");
+        RateLimiter {
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: f64::from(requests),
+                last: Instant::now(),
+            })),
+            capacity: f64::from(requests),
+            interval: period / requests,
+        }
+    }
+
+    /// Take a token from the bucket, and return how long to wait before sending the request.
+    pub(crate) fn reserve(&self) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.last = now;
+        bucket.tokens = (bucket.tokens + elapsed / self.interval.as_secs_f64()).min(self.capacity);
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            self.interval.mul_f64(-bucket.tokens)
+        }
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("capacity", &self.capacity)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+/// Parse the value of a `Retry-After` header, either a number of seconds or an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = parse_http_date(value)?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[test]
+fn test_reserve() {
+    let limiter = RateLimiter::new(2, Duration::from_secs(10));
+    assert_eq!(limiter.reserve(), Duration::from_secs(0));
+    assert_eq!(limiter.reserve(), Duration::from_secs(0));
+
+    // The next tokens are added every 5 seconds, the waiting requests are served in order.
+    let first = limiter.reserve();
+    assert!(first > Duration::from_millis(4900) && first <= Duration::from_secs(5));
+    let second = limiter.reserve();
+    assert!(second > Duration::from_millis(9900) && second <= Duration::from_secs(10));
+}
+
+#[test]
+fn test_parse_retry_after() {
+    assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+    assert_eq!(
+        parse_retry_after("Thu, 01 Jan 1970 00:00:00 GMT"),
+        Some(Duration::from_secs(0))
+    );
+    let later = parse_retry_after(&crate::date::format_http_date(
+        SystemTime::now() + Duration::from_secs(60),
+    ));
+    assert!(later.is_some_and(|delay| delay > Duration::from_secs(55) && delay <= Duration::from_secs(60)));
+    assert_eq!(parse_retry_after("soon"), None);
+}
@@ -0,0 +1,97 @@
+//! Policies deciding which redirections are followed.
+
+use std::fmt;
+use std::sync::Arc;
+
+use http::StatusCode;
+use url::Url;
+
+/// The decision of a `RedirectPolicy` about a redirection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectAction {
+    /// Follow the redirection.
+    Follow,
+    /// Do not follow the redirection, and return the redirection response.
+    Stop,
+    /// Do not follow the redirection, and fail with a `RedirectionRefused` error.
+    Error,
+}
+
+type PolicyFn = dyn Fn(&Url, &Url, StatusCode) -> RedirectAction + Send + Sync;
+
+/// A callback deciding whether the redirections of a request are followed.
+///
+/// The callback receives the URL which was requested, the URL of the redirection, and the status of the
+/// redirection response. It is called for each redirection, before the `max_redirections` limit is checked,
+/// so it can also be used to log the redirections which are followed.
+///
+/// Cloning a `RedirectPolicy` is cheap, the clones share the same callback.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> attohttpc::Result {
+/// use attohttpc::{RedirectAction, RedirectPolicy};
+///
+/// let policy = RedirectPolicy::new(|previous, next, status| {
+///     println!("{} {} -> {}", status, previous, next);
+///     RedirectAction::Follow
+/// });
+/// let resp = attohttpc::get("http://example.com").redirect_policy(policy).send()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RedirectPolicy(Arc<PolicyFn>);
+
+impl RedirectPolicy {
+    /// Create a new `RedirectPolicy` calling the given function.
+    pub fn new<F>(f: F) -> RedirectPolicy
+    where
+        F: Fn(&Url, &Url, StatusCode) -> RedirectAction + Send + Sync + 'static,
+    {
+        RedirectPolicy(Arc::new(f))
+    }
+
+    /// Create a `RedirectPolicy` which only follows the redirections to the same host, and stops at the
+    /// other ones.
+    pub fn same_host() -> RedirectPolicy {
+        RedirectPolicy::new(|previous, next, _| {
+            if previous.host() == next.host() {
+                RedirectAction::Follow
+            } else {
+                RedirectAction::Stop
+            }
+        })
+    }
+
+    pub(crate) fn check(&self, previous: &Url, next: &Url, status: StatusCode) -> RedirectAction {
+        (self.0)(previous, next, status)
+    }
+}
+
+impl fmt::Debug for RedirectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RedirectPolicy")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::StatusCode;
+    use url::Url;
+
+    use super::{RedirectAction, RedirectPolicy};
+
+    #[test]
+    fn test_same_host() {
+        let policy = RedirectPolicy::same_host();
+        let previous = Url::parse("http://example.com/a").unwrap();
+        let same = Url::parse("https://example.com:8443/b").unwrap();
+        let other = Url::parse("http://other.com/a").unwrap();
+        assert_eq!(
+            policy.check(&previous, &same, StatusCode::FOUND),
+            RedirectAction::Follow
+        );
+        assert_eq!(policy.check(&previous, &other, StatusCode::FOUND), RedirectAction::Stop);
+    }
+}
@@ -0,0 +1,210 @@
+//! Resolution of host names to socket addresses.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// A resolver of host names, used to find the addresses to connect to.
+///
+/// The addresses are tried in the order in which they are returned.
+pub trait Resolve: Debug + Send + Sync {
+    /// Resolve the given host to a list of socket addresses using the given port.
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+/// The resolver of the system, using `getaddrinfo` or its equivalent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        Ok((host, port).to_socket_addrs()?.collect())
+    }
+}
+
+/// A resolver which returns fixed addresses for some hosts, like a hosts file, and uses another resolver
+/// for the other hosts.
+///
+/// The host names are matched without regard to case. This is useful to send requests to a test server
+/// without changing their URLs, or to pin the address of a host.
+#[derive(Debug, Clone)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+    fallback: Arc<dyn Resolve>,
+}
+
+impl StaticResolver {
+    /// Create a new `StaticResolver` which uses the resolver of the system for the hosts it does not know.
+    pub fn new() -> StaticResolver {
+        StaticResolver::with_fallback(SystemResolver)
+    }
+
+    /// Create a new `StaticResolver` which uses the given resolver for the hosts it does not know.
+    pub fn with_fallback<R>(fallback: R) -> StaticResolver
+    where
+        R: Resolve + 'static,
+    {
+        StaticResolver {
+            hosts: HashMap::new(),
+            fallback: Arc::new(fallback),
+        }
+    }
+
+    /// Add an address of the given host. The addresses of a host are returned in the order they were added.
+    pub fn host<H>(mut self, host: H, addr: IpAddr) -> StaticResolver
+    where
+        H: Into<String>,
+    {
+        let mut host = host.into();
+        host.make_ascii_lowercase();
+        self.hosts.entry(host).or_default().push(addr);
+        self
+    }
+}
+
+impl Default for StaticResolver {
+    fn default() -> StaticResolver {
+        StaticResolver::new()
+    }
+}
+
+impl Resolve for StaticResolver {
+    fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        match self.hosts.get(&host.to_ascii_lowercase()) {
+            Some(addrs) => Ok(addrs.iter().map(|addr| SocketAddr::new(*addr, port)).collect()),
+            None => self.fallback.resolve(host, port),
+        }
+    }
+}
+
+/// Parse the host as an IP address, without using a resolver.
+///
+/// IPv6 addresses may be enclosed in brackets, as they are in URLs.
+pub(crate) fn parse_ip(host: &str) -> Option<IpAddr> {
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+/// Run the resolver on a worker thread and wait for at most `timeout`.
+///
+/// Resolvers such as `getaddrinfo` cannot be interrupted, so the thread is left to finish on its own
+/// when the timeout expires.
+fn resolve_with_timeout(
+    resolver: Arc<dyn Resolve>,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> io::Result<Vec<SocketAddr>> {
+    let (tx, rx) = mpsc::channel();
+    let owned_host = host.to_string();
+    thread::Builder::new()
+        .name("attohttpc-resolver".into())
+        .spawn(move || {
+            let _ = tx.send(resolver.resolve(&owned_host, port));
+        })?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("timed out while resolving {}", host),
+        )),
+        Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("resolver thread panicked")),
+    }
+}
+
+/// Resolve the host using the given resolver, or the resolver of the system if there is none.
+///
+/// If a timeout is given, the resolution fails with a `TimedOut` error when it takes longer.
+pub(crate) fn resolve(
+    resolver: Option<&Arc<dyn Resolve>>,
+    host: &str,
+    port: u16,
+    timeout: Option<Duration>,
+) -> io::Result<Vec<SocketAddr>> {
+    if let Some(ip) = parse_ip(host) {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let addrs = match (resolver, timeout) {
+        (Some(resolver), None) => resolver.resolve(host, port)?,
+        (None, None) => SystemResolver.resolve(host, port)?,
+        (resolver, Some(timeout)) => {
+            let resolver = resolver.cloned().unwrap_or_else(|| Arc::new(SystemResolver));
+            resolve_with_timeout(resolver, host, port, timeout)?
+        }
+    };
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no addresses found for {}", host),
+        ));
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+struct Unreachable;
+
+#[cfg(test)]
+impl Resolve for Unreachable {
+    fn resolve(&self, _: &str, _: u16) -> io::Result<Vec<SocketAddr>> {
+        panic!("the resolver should not be used for ip addresses")
+    }
+}
+
+#[test]
+fn test_resolve_ip_literal() {
+    let resolver: Arc<dyn Resolve> = Arc::new(Unreachable);
+    let addrs = resolve(Some(&resolver), "127.0.0.1", 80, None).unwrap();
+    assert_eq!(addrs, vec!["127.0.0.1:80".parse().unwrap()]);
+    let addrs = resolve(Some(&resolver), "[::1]", 443, Some(Duration::from_secs(1))).unwrap();
+    assert_eq!(addrs, vec!["[::1]:443".parse().unwrap()]);
+}
+
+#[test]
+fn test_resolve_timeout() {
+    #[derive(Debug)]
+    struct Slow;
+
+    impl Resolve for Slow {
+        fn resolve(&self, _: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+            thread::sleep(Duration::from_millis(500));
+            Ok(vec![SocketAddr::new([127, 0, 0, 1].into(), port)])
+        }
+    }
+
+    let resolver: Arc<dyn Resolve> = Arc::new(Slow);
+    let err = resolve(Some(&resolver), "slow.test", 80, Some(Duration::from_millis(20))).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    let addrs = resolve(Some(&resolver), "slow.test", 80, Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(addrs, vec!["127.0.0.1:80".parse().unwrap()]);
+}
+
+#[test]
+fn test_static_resolver() {
+    #[derive(Debug)]
+    struct Fallback;
+
+    impl Resolve for Fallback {
+        fn resolve(&self, _: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+            Ok(vec![SocketAddr::new([10, 0, 0, 1].into(), port)])
+        }
+    }
+
+    let resolver = StaticResolver::with_fallback(Fallback)
+        .host("Example.com", [127, 0, 0, 1].into())
+        .host("example.com", "::1".parse().unwrap());
+    assert_eq!(
+        resolver.resolve("EXAMPLE.com", 80).unwrap(),
+        vec!["127.0.0.1:80".parse().unwrap(), "[::1]:80".parse().unwrap()]
+    );
+    assert_eq!(
+        resolver.resolve("other.com", 443).unwrap(),
+        vec!["10.0.0.1:443".parse().unwrap()]
+    );
+}
@@ -0,0 +1,129 @@
+//! Secret values which should not be leaked in logs.
+
+use std::fmt::{self, Debug, Write};
+use std::mem;
+use std::ptr;
+use std::result;
+
+use http::header::HeaderValue;
+
+use crate::request::HttpTryInto;
+
+/// A header value which must be kept secret, such as an API key or a password.
+///
+/// The `HeaderValue` created from a `Secret` is marked as sensitive, which means that it is redacted when
+/// the headers are printed with `Debug` and when they are written to a `WireLog`. The `Secret` itself is also
+/// redacted in its `Debug` output.
+///
+/// The buffer of the `Secret` is overwritten with zeros when it is dropped. The copies of the value which are
+/// needed to send it are not: the `HeaderValue`, which lives as long as the request, and the head of the
+/// request written to the connection. The values built by `basic_auth`, `bearer_auth` and from the `.netrc`
+/// file are formatted and encoded in buffers which are overwritten as well, but the arguments given to these
+/// methods and the contents of the `.netrc` file are not.
+///
+/// ```
+/// # use attohttpc::{header::AUTHORIZATION, Secret};
+/// let req = attohttpc::get("https://my-api.org/")
+///     .header(AUTHORIZATION, Secret::new("Bearer my-api-key"))
+///     .prepare();
+/// assert!(req.headers()[AUTHORIZATION].is_sensitive());
+/// ```
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    /// Create a new `Secret` from the given value.
+    pub fn new(value: impl Into<Vec<u8>>) -> Secret {
+        Secret(value.into())
+    }
+
+    /// Get the bytes of this `Secret`.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Format the arguments in a `Secret`, without leaving copies of the value in the buffers which are
+    /// outgrown.
+    pub(crate) fn format(args: fmt::Arguments) -> Secret {
+        let mut secret = Secret(Vec::new());
+        SecretWriter(&mut secret)
+            .write_fmt(args)
+            .expect("formatting a Secret failed");
+        secret
+    }
+
+    /// Create the value of an `Authorization` header for the `Basic` scheme from the `user:password`
+    /// credentials.
+    pub(crate) fn basic_authorization(credentials: &Secret) -> Secret {
+        const PREFIX: &[u8] = b"Basic ";
+        // The buffer has the exact size of the encoded value, it is never reallocated.
+        let mut value = Secret(vec![0; PREFIX.len() + credentials.0.len().div_ceil(3) * 4]);
+        value.0[..PREFIX.len()].copy_from_slice(PREFIX);
+        base64::encode_config_slice(&credentials.0, base64::STANDARD, &mut value.0[PREFIX.len()..]);
+        value
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if self.0.capacity() - self.0.len() < bytes.len() {
+            let mut grown = Vec::with_capacity((self.0.len() + bytes.len()).max(2 * self.0.capacity()));
+            grown.extend_from_slice(&self.0);
+            // The outgrown buffer is overwritten when it is dropped.
+            drop(Secret(mem::replace(&mut self.0, grown)));
+        }
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+struct SecretWriter<'a>(&'a mut Secret);
+
+impl Write for SecretWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        for byte in self.0.iter_mut() {
+            // Volatile writes prevent the compiler from optimizing away writes to memory which is freed.
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+impl HttpTryInto<HeaderValue> for Secret {
+    fn try_into(self) -> result::Result<HeaderValue, http::Error> {
+        let mut value = HeaderValue::from_bytes(&self.0)?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_secret_debug() {
+    let secret = Secret::new("hunter2");
+    assert_eq!(format!("{:?}", secret), "Secret(***)");
+}
+
+#[test]
+fn test_secret_basic_authorization() {
+    let credentials = Secret::format(format_args!("{}:{}", "Aladdin", "open sesame"));
+    assert_eq!(credentials.expose(), b"Aladdin:open sesame");
+    let value = Secret::basic_authorization(&credentials);
+    assert_eq!(value.expose(), b"Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    assert_eq!(Secret::basic_authorization(&Secret::new("a:")).expose(), b"Basic YTo=");
+}
+
+#[test]
+fn test_secret_header_value_sensitive() {
+    let value: HeaderValue = Secret::new("hunter2").try_into().unwrap();
+    assert!(value.is_sensitive());
+    assert_eq!(value, "hunter2");
+    assert_eq!(format!("{:?}", value), "Sensitive");
+}
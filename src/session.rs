@@ -0,0 +1,493 @@
+//! Sessions sharing defaults, connections and cookies between requests.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::header::{HeaderMap, HeaderValue, IntoHeaderName, USER_AGENT};
+use http::Method;
+use url::Url;
+
+use crate::address_health::AddressHealth;
+use crate::cache::CacheStore;
+#[cfg(feature = "cookies")]
+use crate::cookies::CookieJar;
+use crate::error::{ErrorKind, Result};
+use crate::middleware::Middleware;
+use crate::pool::ConnectionPool;
+use crate::rate_limit::RateLimiter;
+use crate::request::{header_append, header_insert, HttpTryInto, RequestBuilder};
+use crate::settings::Settings;
+use crate::streams::{ByteCounters, UNIX_SCHEME};
+
+/// A `Session` holds the defaults shared by a group of requests, such as headers, timeouts or a proxy.
+///
+/// The requests created by a `Session` start with its headers and its `Settings`, which they can override.
+/// A new `Session` also has its own `ConnectionPool`, `AddressHealth` and, when the `cookies` feature is
+/// enabled, `CookieJar`, so that its requests reuse connections, avoid the addresses which failed, and keep
+/// the cookies set by the servers.
+///
+/// Cloning a `Session` is cheap, the clones share the same connections, cookies and byte counters.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> attohttpc::Result {
+/// use std::time::Duration;
+///
+/// let session = attohttpc::Session::new()
+///     .header("X-Api-Key", "secret")
+///     .timeout(Duration::from_secs(30));
+/// let resp = session.get("https://example.com/items").send()?;
+/// println!("{}", resp.text()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session {
+    base_url: Option<Url>,
+    headers: HeaderMap,
+    settings: Settings,
+    counters: Arc<ByteCounters>,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    /// Create a new `Session` with the default `Settings`.
+    pub fn new() -> Session {
+        Session::with_settings(Settings::default())
+    }
+
+    /// Create a new `Session` with the given `Settings`.
+    ///
+    /// A `ConnectionPool`, an `AddressHealth` and a `CookieJar` are created when the settings do not have
+    /// them already.
+    pub fn with_settings(mut settings: Settings) -> Session {
+        let counters = Arc::new(ByteCounters::default());
+        settings.counters = Some(counters.clone());
+        settings.connection_pool.get_or_insert_with(ConnectionPool::new);
+        settings.address_health.get_or_insert_with(AddressHealth::new);
+        #[cfg(feature = "cookies")]
+        settings.cookies.get_or_insert_with(CookieJar::new);
+        Session {
+            base_url: None,
+            headers: HeaderMap::new(),
+            settings,
+            counters,
+        }
+    }
+
+    /// Set the base URL against which the URLs of the requests of this `Session` are resolved.
+    ///
+    /// The URLs are resolved like the links of a web page: `/v1/users` replaces the whole path of the base
+    /// URL, while `users` replaces its last segment, so the path of a base URL like `https://example.com/v1/`
+    /// should end with a slash. Absolute URLs are used as is.
+    ///
+    /// # Panics
+    /// This method will panic if the base URL is invalid.
+    pub fn base_url<U>(self, base_url: U) -> Session
+    where
+        U: AsRef<str>,
+    {
+        self.try_base_url(base_url).expect("invalid base url")
+    }
+
+    /// Set the base URL against which the URLs of the requests of this `Session` are resolved.
+    ///
+    /// An `InvalidBaseUrl` error is returned if the base URL is not an absolute `http`, `https` or `http+unix`
+    /// URL.
+    pub fn try_base_url<U>(mut self, base_url: U) -> Result<Session>
+    where
+        U: AsRef<str>,
+    {
+        let url = Url::parse(base_url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        if !matches!(url.scheme(), "http" | "https" | UNIX_SCHEME) || url.host_str().is_none() {
+            return Err(ErrorKind::InvalidBaseUrl.into());
+        }
+        self.base_url = Some(url);
+        Ok(self)
+    }
+
+    /// Modify a default header of the requests of this `Session`.
+    ///
+    /// If the header is already present, the value will be replaced. The requests can replace the value with
+    /// their own.
+    ///
+    /// # Panics
+    /// This method will panic if the value is invalid.
+    pub fn header<H, V>(self, header: H, value: V) -> Session
+    where
+        H: IntoHeaderName,
+        V: HttpTryInto<HeaderValue>,
+    {
+        self.try_header(header, value).expect("invalid header value")
+    }
+
+    /// Append a default header to the requests of this `Session`.
+    ///
+    /// # Panics
+    /// This method will panic if the value is invalid.
+    pub fn header_append<H, V>(self, header: H, value: V) -> Session
+    where
+        H: IntoHeaderName,
+        V: HttpTryInto<HeaderValue>,
+    {
+        self.try_header_append(header, value).expect("invalid header value")
+    }
+
+    /// Modify a default header of the requests of this `Session`.
+    ///
+    /// If the header is already present, the value will be replaced. The requests can replace the value with
+    /// their own.
+    pub fn try_header<H, V>(mut self, header: H, value: V) -> Result<Session>
+    where
+        H: IntoHeaderName,
+        V: HttpTryInto<HeaderValue>,
+    {
+        header_insert(&mut self.headers, header, value)?;
+        Ok(self)
+    }
+
+    /// Append a default header to the requests of this `Session`.
+    pub fn try_header_append<H, V>(mut self, header: H, value: V) -> Result<Session>
+    where
+        H: IntoHeaderName,
+        V: HttpTryInto<HeaderValue>,
+    {
+        header_append(&mut self.headers, header, value)?;
+        Ok(self)
+    }
+
+    /// Set the `User-Agent` header of the requests of this `Session`.
+    ///
+    /// Without it, the requests are sent with a `User-Agent` header holding the name and version of this crate.
+    ///
+    /// # Panics
+    /// This method will panic if the value is invalid.
+    pub fn user_agent<V>(self, user_agent: V) -> Session
+    where
+        V: HttpTryInto<HeaderValue>,
+    {
+        self.header(USER_AGENT, user_agent)
+    }
+
+    /// Set the maximum number of redirections the requests of this `Session` can perform.
+    pub fn max_redirections(mut self, max_redirections: u32) -> Session {
+        self.settings.max_redirections = max_redirections;
+        self
+    }
+
+    /// Sets if the requests of this `Session` should follow redirects, 3xx codes.
+    pub fn follow_redirects(mut self, follow_redirects: bool) -> Session {
+        self.settings.follow_redirects = follow_redirects;
+        self
+    }
+
+    /// Sets a connect timeout for the requests of this `Session`.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Session {
+        self.settings.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a read timeout for the requests of this `Session`.
+    pub fn read_timeout(mut self, timeout: Duration) -> Session {
+        self.settings.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a timeout for the whole of each request of this `Session`.
+    pub fn timeout(mut self, timeout: Duration) -> Session {
+        self.settings.timeout = Some(timeout);
+        self
+    }
+
+    /// Abort the requests of this `Session` when their transfer rate stays below `bytes_per_sec` bytes per
+    /// second during `time`.
+    pub fn low_speed_limit(mut self, bytes_per_sec: u64, time: Duration) -> Session {
+        self.settings.low_speed_limit = Some((bytes_per_sec, time));
+        self
+    }
+
+    /// Limit the rate of the requests of this `Session` to `requests` requests per `period`.
+    ///
+    /// The requests which exceed the rate wait before being sent. The limit is shared by the clones of this
+    /// `Session`. See `RateLimiter` for more details.
+    ///
+    /// # Panics
+    /// This method will panic if `requests` is zero.
+    pub fn rate_limit(mut self, requests: u32, period: Duration) -> Session {
+        self.settings.rate_limiter = Some(RateLimiter::new(requests, period));
+        self
+    }
+
+    /// Set how many times the requests of this `Session` are sent again when the server answers with
+    /// `429 Too Many Requests` and a `Retry-After` header.
+    pub fn retry_too_many_requests(mut self, retries: u32) -> Session {
+        self.settings.too_many_requests_retries = retries;
+        self
+    }
+
+    /// Cache the responses of the requests of this `Session` in the given store, and revalidate them with
+    /// conditional requests. See `RequestBuilder::cache` for more details.
+    pub fn cache<C>(mut self, store: C) -> Session
+    where
+        C: CacheStore + 'static,
+    {
+        self.settings.cache = Some(Arc::new(store));
+        self
+    }
+
+    /// Add a middleware called around the requests of this `Session`, for instance to add authentication
+    /// headers or to record metrics.
+    ///
+    /// The `before_send` hooks of the middlewares are called in the order they were added, and their
+    /// `after_receive` hooks in the reverse order. See `Middleware` for more details.
+    pub fn middleware<M>(mut self, middleware: M) -> Session
+    where
+        M: Middleware + 'static,
+    {
+        self.settings.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Send the requests of this `Session` through the given proxy.
+    ///
+    /// # Panics
+    /// This method will panic if the proxy URL is invalid.
+    pub fn proxy<U>(self, proxy_url: U) -> Session
+    where
+        U: AsRef<str>,
+    {
+        self.try_proxy(proxy_url).expect("invalid proxy url")
+    }
+
+    /// Send the requests of this `Session` through the given proxy.
+    pub fn try_proxy<U>(mut self, proxy_url: U) -> Result<Session>
+    where
+        U: AsRef<str>,
+    {
+        self.settings = self.settings.try_proxy(proxy_url)?;
+        Ok(self)
+    }
+
+    /// Get the `Settings` of the requests of this `Session`.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Get the default headers of the requests of this `Session`.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the `CookieJar` of this `Session`.
+    ///
+    /// This method only exists when the `cookies` feature is enabled.
+    #[cfg(feature = "cookies")]
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.settings.cookies.as_ref()
+    }
+
+    /// Close the idle connections kept by this `Session`.
+    ///
+    /// This is useful after a change of network, or when the servers are known to have changed. The addresses
+    /// which failed recently are still tried last, use `reset_address_health` to forget them too.
+    pub fn close_idle_connections(&self) {
+        if let Some(pool) = &self.settings.connection_pool {
+            pool.close_idle_connections();
+        }
+    }
+
+    /// Forget the addresses which failed recently, so that they are tried again in their usual order.
+    ///
+    /// Like `close_idle_connections`, this is useful after a change of network, when the addresses which
+    /// could not be reached may work again.
+    pub fn reset_address_health(&self) {
+        if let Some(address_health) = &self.settings.address_health {
+            address_health.clear();
+        }
+    }
+
+    /// Get the number of raw bytes sent by the requests of this `Session`.
+    pub fn bytes_sent(&self) -> u64 {
+        self.counters.sent()
+    }
+
+    /// Get the number of raw bytes received by the requests of this `Session`.
+    ///
+    /// The bytes of a response body are counted as they are read.
+    pub fn bytes_received(&self) -> u64 {
+        self.counters.received()
+    }
+
+    /// Create a new request with the given method and URL, using the defaults of this `Session`.
+    ///
+    /// The URL is resolved against the base URL of this `Session`, if it has one.
+    ///
+    /// # Panics
+    /// Panics if the URL is invalid or if the method is CONNECT.
+    pub fn request<U>(&self, method: Method, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.try_request(method, url).expect("invalid url or method")
+    }
+
+    /// Try to create a new request with the given method and URL, using the defaults of this `Session`.
+    ///
+    /// If the URL is invalid, an error is returned. If the method is CONNECT, an error is also returned.
+    pub fn try_request<U>(&self, method: Method, url: U) -> Result<RequestBuilder>
+    where
+        U: AsRef<str>,
+    {
+        let builder = match &self.base_url {
+            Some(base_url) => {
+                let url = base_url.join(url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+                RequestBuilder::try_with_settings(method, url, self.settings.clone())?
+            }
+            None => RequestBuilder::try_with_settings(method, url, self.settings.clone())?,
+        };
+        Ok(builder.default_headers(&self.headers))
+    }
+
+    /// Create a new `GET` request using the defaults of this `Session`.
+    pub fn get<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::GET, url)
+    }
+
+    /// Create a new `POST` request using the defaults of this `Session`.
+    pub fn post<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::POST, url)
+    }
+
+    /// Create a new `PUT` request using the defaults of this `Session`.
+    pub fn put<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::PUT, url)
+    }
+
+    /// Create a new `DELETE` request using the defaults of this `Session`.
+    pub fn delete<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::DELETE, url)
+    }
+
+    /// Create a new `HEAD` request using the defaults of this `Session`.
+    pub fn head<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::HEAD, url)
+    }
+
+    /// Create a new `OPTIONS` request using the defaults of this `Session`.
+    pub fn options<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::OPTIONS, url)
+    }
+
+    /// Create a new `PATCH` request using the defaults of this `Session`.
+    pub fn patch<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::PATCH, url)
+    }
+
+    /// Create a new `TRACE` request using the defaults of this `Session`.
+    pub fn trace<U>(&self, url: U) -> RequestBuilder
+    where
+        U: AsRef<str>,
+    {
+        self.request(Method::TRACE, url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{ACCEPT, USER_AGENT};
+
+    use super::Session;
+
+    #[test]
+    fn test_default_headers() {
+        let session = Session::new()
+            .header(USER_AGENT, "agent/1.0")
+            .header_append(ACCEPT, "text/html")
+            .header_append(ACCEPT, "text/plain");
+
+        let req = session.get("http://localhost/").prepare();
+        assert_eq!(req.headers()[USER_AGENT], "agent/1.0");
+        assert_eq!(req.headers().get_all(ACCEPT).iter().count(), 2);
+
+        // The headers of the request replace the defaults.
+        let req = session.get("http://localhost/").header(USER_AGENT, "other").prepare();
+        assert_eq!(req.headers()[USER_AGENT], "other");
+        assert_eq!(req.headers().get_all(USER_AGENT).iter().count(), 1);
+
+        let req = Session::new()
+            .user_agent("agent/2.0")
+            .get("http://localhost/")
+            .prepare();
+        assert_eq!(req.headers()[USER_AGENT], "agent/2.0");
+    }
+
+    #[test]
+    fn test_base_url() {
+        let session = Session::new().base_url("http://localhost:8080/api/v1/?key=abc");
+        let url = |path| session.get(path).prepare().url().as_str().to_string();
+        assert_eq!(url("users"), "http://localhost:8080/api/v1/users");
+        assert_eq!(
+            url("users/1?full=true"),
+            "http://localhost:8080/api/v1/users/1?full=true"
+        );
+        assert_eq!(url("/health"), "http://localhost:8080/health");
+        assert_eq!(url("../v2/users"), "http://localhost:8080/api/v2/users");
+        assert_eq!(url("https://example.com/x"), "https://example.com/x");
+
+        assert!(Session::new().try_base_url("/api").is_err());
+        assert!(Session::new().try_base_url("mailto:user@example.com").is_err());
+        assert!(Session::new().try_base_url("file:///tmp").is_err());
+    }
+
+    #[test]
+    fn test_shared_stores() {
+        let session = Session::new();
+        assert!(session.settings().connection_pool.is_some());
+        assert!(session.settings().address_health.is_some());
+        #[cfg(feature = "cookies")]
+        assert!(session.cookie_jar().is_some());
+
+        let req = session.get("http://localhost/").prepare();
+        assert_eq!(req.headers()[http::header::CONNECTION], "keep-alive");
+    }
+
+    #[test]
+    fn test_reset_address_health() {
+        let session = Session::new();
+        let address_health = session.settings().address_health.as_ref().unwrap();
+        let addr = "127.0.0.1:80".parse().unwrap();
+        address_health.mark_failed(addr);
+        assert!(!address_health.is_healthy(&addr));
+
+        session.close_idle_connections();
+        assert!(!address_health.is_healthy(&addr));
+        session.reset_address_health();
+        assert!(address_health.is_healthy(&addr));
+    }
+}
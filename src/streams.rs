@@ -1,88 +1,1330 @@
-#[cfg(test)]
-use std::io::Cursor;
-use std::io::{self, Read, Write};
-use std::net::TcpStream;
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+mod tls;
 
-#[cfg(feature = "tls")]
-use native_tls::{HandshakeError, TlsConnector, TlsStream};
+#[cfg(target_os = "linux")]
+use std::fs::File;
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+use std::io::BufReader;
+use std::io::{self, Cursor, Read, Write};
+use std::mem;
+use std::net::{SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, SockAddr, SockRef, Socket, TcpKeepalive, Type};
 use url::Url;
 
-use crate::{ErrorKind, Result};
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+use self::tls::TlsStream;
+use crate::observer::Event;
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+use crate::parsing::response::parse_response_head;
+use crate::pool::{ConnectionPool, KeepAlive, PoolKey, Pooled};
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+use crate::proxy::proxy_authorization;
+use crate::resolver::resolve;
+use crate::settings::Settings;
+use crate::socks;
+use crate::timings::{add_time, Timings};
+use crate::wire_log::WireLogger;
+use crate::wire_tap::{Direction, WireTap};
+use crate::{Error, ErrorKind, Result};
 
-pub enum BaseStream {
-    Plain(TcpStream),
-    #[cfg(feature = "tls")]
-    Tls(TlsStream<TcpStream>),
-    #[cfg(test)]
-    Mock(Cursor<Vec<u8>>),
+/// Counters of the raw bytes sent and received while performing a request.
+///
+/// The bytes are also added to the counters of the parent, such as the counters of a `Session`.
+#[derive(Debug, Default)]
+pub struct ByteCounters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    parent: Option<Arc<ByteCounters>>,
 }
 
-impl BaseStream {
-    pub fn connect(url: &Url) -> Result<BaseStream> {
-        let host = url.host_str().ok_or(ErrorKind::InvalidUrlHost)?;
-        let port = url.port_or_known_default().ok_or(ErrorKind::InvalidUrlPort)?;
+impl ByteCounters {
+    pub fn with_parent(parent: Option<Arc<ByteCounters>>) -> ByteCounters {
+        ByteCounters {
+            parent,
+            ..ByteCounters::default()
+        }
+    }
+
+    fn add_sent(&self, n: u64) {
+        self.sent.fetch_add(n, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_sent(n);
+        }
+    }
+
+    fn add_received(&self, n: u64) {
+        self.received.fetch_add(n, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_received(n);
+        }
+    }
 
-        debug!("trying to connect to {}:{}", host, port);
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+/// Streams whose reads can time out.
+pub(crate) trait ReadTimeout {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
 
-        Ok(match url.scheme() {
-            "http" => BaseStream::Plain(TcpStream::connect((host, port))?),
-            #[cfg(feature = "tls")]
-            "https" => BaseStream::connect_tls(host, port)?,
-            _ => return Err(ErrorKind::InvalidBaseUrl.into()),
+#[cfg(unix)]
+impl ReadTimeout for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl ReadTimeout for Cursor<Vec<u8>> {
+    fn set_read_timeout(&self, _: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tracks the rate of the transfers on a connection, to abort the transfers which stay below the limit set
+/// with `low_speed_limit`.
+#[derive(Debug)]
+struct LowSpeed {
+    /// Minimum number of bytes to transfer during each period.
+    min_bytes: u64,
+    period: Duration,
+    start: Instant,
+    transferred: u64,
+}
+
+impl LowSpeed {
+    fn new(settings: &Settings) -> Option<LowSpeed> {
+        settings.low_speed_limit.map(|(bytes_per_sec, period)| LowSpeed {
+            min_bytes: (bytes_per_sec as f64 * period.as_secs_f64()) as u64,
+            period,
+            start: Instant::now(),
+            transferred: 0,
         })
     }
 
-    #[cfg(feature = "tls")]
-    fn connect_tls(host: &str, port: u16) -> Result<BaseStream> {
-        let connector = TlsConnector::new()?;
-        let stream = TcpStream::connect((host, port))?;
-        let tls_stream = match connector.connect(host, stream) {
-            Ok(stream) => stream,
-            Err(HandshakeError::Failure(err)) => return Err(err.into()),
-            Err(HandshakeError::WouldBlock(_)) => panic!("socket configured in non-blocking mode"),
-        };
-        Ok(BaseStream::Tls(tls_stream))
+    /// Record the bytes transferred, and check the rate at the end of each period.
+    fn add(&mut self, n: usize) -> io::Result<()> {
+        self.transferred += n as u64;
+        if self.start.elapsed() >= self.period {
+            if self.transferred < self.min_bytes {
+                return Err(too_slow());
+            }
+            self.start = Instant::now();
+            self.transferred = 0;
+        }
+        Ok(())
     }
+}
 
-    #[cfg(test)]
-    pub fn mock(bytes: Vec<u8>) -> BaseStream {
-        BaseStream::Mock(Cursor::new(bytes))
+fn too_slow() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, Error::from(ErrorKind::TransferTooSlow))
+}
+
+/// Wrapper around a stream which counts the bytes going through it.
+///
+/// It sits under the TLS layer, so that the bytes counted are the bytes sent over the wire. When a deadline
+/// is set, each read is bounded by the time left before the deadline. When a low speed limit is set, each
+/// read is bounded by its period, a read which receives nothing during a whole period is too slow.
+#[derive(Debug)]
+pub(crate) struct Counted<S> {
+    inner: S,
+    counters: Arc<ByteCounters>,
+    read_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    low_speed: Option<LowSpeed>,
+}
+
+impl<S: ReadTimeout> Counted<S> {
+    fn new(inner: S, counters: Arc<ByteCounters>, settings: &Settings) -> io::Result<Counted<S>> {
+        inner.set_read_timeout(settings.read_timeout)?;
+        Ok(Counted {
+            inner,
+            counters,
+            read_timeout: settings.read_timeout,
+            deadline: settings.deadline,
+            low_speed: LowSpeed::new(settings),
+        })
     }
 }
 
-impl Read for BaseStream {
+/// The settings of a `Counted` stream which do not depend on the type of the inner stream.
+trait CountedStream {
+    fn set_counters(&mut self, counters: Arc<ByteCounters>);
+
+    fn set_timeouts(&mut self, settings: &Settings) -> io::Result<()>;
+}
+
+impl<S: ReadTimeout> CountedStream for Counted<S> {
+    fn set_counters(&mut self, counters: Arc<ByteCounters>) {
+        self.counters = counters;
+    }
+
+    fn set_timeouts(&mut self, settings: &Settings) -> io::Result<()> {
+        self.inner.set_read_timeout(settings.read_timeout)?;
+        self.read_timeout = settings.read_timeout;
+        self.deadline = settings.deadline;
+        self.low_speed = LowSpeed::new(settings);
+        Ok(())
+    }
+}
+
+impl<S: Read + ReadTimeout> Read for Counted<S> {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut timeout = self.read_timeout;
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // Like a read which times out, so that the TLS layer does not consider the error fatal.
+            if remaining == Duration::from_secs(0) {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            timeout = Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)));
+        }
+        if let Some(low_speed) = &self.low_speed {
+            timeout = Some(timeout.map_or(low_speed.period, |timeout| timeout.min(low_speed.period)));
+        }
+        if self.deadline.is_some() || self.low_speed.is_some() {
+            self.inner.set_read_timeout(timeout)?;
+        }
+        let started = Instant::now();
+        let n = match self.inner.read(buf) {
+            Ok(n) => n,
+            Err(err) => {
+                let timed_out = matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut);
+                return match &self.low_speed {
+                    Some(low_speed) if timed_out && started.elapsed() >= low_speed.period => Err(too_slow()),
+                    _ => Err(err),
+                };
+            }
+        };
+        self.counters.add_received(n as u64);
+        if let Some(low_speed) = &mut self.low_speed {
+            low_speed.add(n)?;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Counted<TcpStream> {
+    /// Send up to `len` bytes of the file from its current position with `sendfile`, without copying them
+    /// through userspace.
+    fn send_file(&mut self, file: &File, len: usize) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+
+        let ret = unsafe { libc::sendfile(self.inner.as_raw_fd(), file.as_raw_fd(), std::ptr::null_mut(), len) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let n = ret as usize;
+        self.counters.add_sent(n as u64);
+        if let Some(low_speed) = &mut self.low_speed {
+            low_speed.add(n)?;
+        }
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Counted<S> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.counters.add_sent(n as u64);
+        if let Some(low_speed) = &mut self.low_speed {
+            low_speed.add(n)?;
+        }
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) enum Stream {
+    Plain(Counted<TcpStream>),
+    #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+    Tls(TlsStream<Counted<TcpStream>>),
+    /// TLS stream tunneled through a TLS connection to an HTTPS proxy.
+    #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+    TlsTunnel(TlsStream<TlsStream<Counted<TcpStream>>>),
+    #[cfg(unix)]
+    Unix(Counted<UnixStream>),
+    /// Body of a cached response.
+    Memory(Counted<Cursor<Vec<u8>>>),
+    /// The connection was given back to the pool.
+    Released,
+}
+
+impl Stream {
+    fn counted_mut(&mut self) -> Option<&mut dyn CountedStream> {
         match self {
-            BaseStream::Plain(s) => s.read(buf),
-            #[cfg(feature = "tls")]
-            BaseStream::Tls(s) => s.read(buf),
-            #[cfg(test)]
-            BaseStream::Mock(s) => s.read(buf),
+            Stream::Plain(s) => Some(s),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::Tls(s) => Some(s.get_mut()),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::TlsTunnel(s) => Some(s.get_mut().get_mut()),
+            #[cfg(unix)]
+            Stream::Unix(s) => Some(s),
+            _ => None,
         }
     }
 }
 
+/// Where a connection goes once its response was read entirely.
+struct PoolSlot {
+    pool: ConnectionPool,
+    key: PoolKey,
+    created: Instant,
+    requests: u32,
+    reusable: bool,
+    keep_alive: KeepAlive,
+}
+
+pub struct BaseStream {
+    stream: Stream,
+    counters: Arc<ByteCounters>,
+    /// Time spent opening the connection, empty for a connection taken from the pool.
+    timings: Timings,
+    wire_tap: Option<WireTap>,
+    wire_log: Option<WireLogger>,
+    /// Bytes received while polling for an early response, which are read before the stream.
+    early: Vec<u8>,
+    pool: Option<PoolSlot>,
+    /// Deadline of the request, after which the reads fail with a `Timeout` error.
+    deadline: Option<Instant>,
+}
+
+/// The scheme of URLs whose host is the percent-encoded path of a Unix domain socket.
+pub(crate) const UNIX_SCHEME: &str = "http+unix";
+
+/// Get the path of the Unix domain socket to connect to, given by the host of an `http+unix` URL or by the
+/// settings.
+#[cfg(unix)]
+pub(crate) fn unix_socket_path(url: &Url, settings: &Settings) -> Option<PathBuf> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    if url.scheme() == UNIX_SCHEME {
+        let path: Vec<u8> = url::percent_encoding::percent_decode(url.host_str()?.as_bytes()).collect();
+        return Some(PathBuf::from(OsStr::from_bytes(&path)));
+    }
+    settings.unix_socket.clone()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn unix_socket_path(_: &Url, _: &Settings) -> Option<PathBuf> {
+    None
+}
+
+fn host_port(url: &Url) -> Result<(&str, u16)> {
+    let host = url.host_str().ok_or(ErrorKind::InvalidUrlHost)?;
+    let port = url.port_or_known_default().ok_or(ErrorKind::InvalidUrlPort)?;
+    Ok((host, port))
+}
+
+/// Enable TCP Fast Open on a socket which is not connected yet.
+///
+/// The data of the first write is then sent in the SYN packet when the server supports it.
+#[cfg(target_os = "linux")]
+fn set_tcp_fast_open(socket: &Socket) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let enable: libc::c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN_CONNECT,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_tcp_fast_open(_: &Socket) -> io::Result<()> {
+    Ok(())
+}
+
+/// Bind the socket to the network interface with the given name, using `SO_BINDTODEVICE`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn bind_interface(socket: &Socket, _: &SocketAddr, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+/// Bind the socket to the network interface with the given name, using `IP_BOUND_IF`.
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+fn bind_interface(socket: &Socket, addr: &SocketAddr, interface: &str) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::num::NonZeroU32;
+
+    let name = CString::new(interface).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface"))?;
+    let index = NonZeroU32::new(unsafe { libc::if_nametoindex(name.as_ptr()) })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("unknown interface {}", interface)))?;
+    match addr {
+        SocketAddr::V4(_) => socket.bind_device_by_index_v4(Some(index)),
+        SocketAddr::V6(_) => socket.bind_device_by_index_v6(Some(index)),
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "linux", target_os = "ios", target_os = "macos")))]
+fn bind_interface(_: &Socket, _: &SocketAddr, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is not supported on this platform",
+    ))
+}
+
+/// Mark the packets of the socket with the given TOS value, or traffic class for IPv6.
+fn set_tos(socket: &Socket, addr: &SocketAddr, tos: u8) -> io::Result<()> {
+    match addr {
+        SocketAddr::V4(_) => socket.set_tos(u32::from(tos)),
+        #[cfg(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        SocketAddr::V6(_) => socket.set_tclass_v6(u32::from(tos)),
+        #[cfg(not(any(
+            target_os = "android",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        SocketAddr::V6(_) => {
+            debug!("the traffic class of ipv6 sockets is not supported on this platform");
+            Ok(())
+        }
+    }
+}
+
+/// Create a socket with the options of the settings and connect it to the address.
+fn connect_socket(addr: &SocketAddr, settings: &Settings, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, Some(Protocol::TCP))?;
+    if settings.tcp_fast_open {
+        if let Err(err) = set_tcp_fast_open(&socket) {
+            debug!("could not enable tcp fast open: {}", err);
+        }
+    }
+    if let Some(interface) = &settings.interface {
+        bind_interface(&socket, addr, interface)?;
+    }
+    if let Some(local_address) = settings.local_address {
+        if local_address.is_ipv4() != addr.is_ipv4() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the local address {} cannot reach {}", local_address, addr),
+            ));
+        }
+        socket.bind(&SockAddr::from(SocketAddr::new(local_address, 0)))?;
+    }
+    if let Some(tos) = settings.tos {
+        set_tos(&socket, addr, tos)?;
+    }
+    if settings.tcp_nodelay {
+        socket.set_nodelay(true)?;
+    }
+    if let Some(time) = settings.tcp_keepalive {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
+    }
+    if let Some(size) = settings.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = settings.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+
+    let addr = SockAddr::from(*addr);
+    match timeout {
+        Some(timeout) => socket.connect_timeout(&addr, timeout)?,
+        None => socket.connect(&addr)?,
+    }
+    Ok(socket.into())
+}
+
+/// Connect to the first address which accepts the connection.
+///
+/// Each attempt is bounded by the attempt timeout of the settings, and all the attempts by the deadline.
+/// The outcome of each attempt is recorded in the address health store of the settings.
+fn connect_addrs(addrs: &[SocketAddr], settings: &Settings, deadline: Option<Instant>) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addrs {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining == Some(Duration::from_secs(0)) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out while connecting"));
+        }
+        let timeout = match (remaining, settings.connect_attempt_timeout) {
+            (Some(remaining), Some(attempt)) => Some(remaining.min(attempt)),
+            (remaining, attempt) => remaining.or(attempt),
+        };
+
+        debug!("trying address {}", addr);
+        settings.observe(Event::ConnectStart { addr: *addr });
+        let started = Instant::now();
+        let result = connect_socket(addr, settings, timeout);
+        settings.observe(Event::ConnectEnd {
+            addr: *addr,
+            error: result.as_ref().err(),
+            elapsed: started.elapsed(),
+        });
+        match result {
+            Ok(stream) => {
+                if let Some(health) = &settings.address_health {
+                    health.mark_healthy(addr);
+                }
+                return Ok(stream);
+            }
+            Err(err) => {
+                debug!("could not connect to {}: {}", addr, err);
+                if let Some(health) = &settings.address_health {
+                    health.mark_failed(*addr);
+                }
+                last_err = Some(err);
+            }
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no addresses to connect to")))
+}
+
+fn connect_tcp(
+    host: &str,
+    port: u16,
+    settings: &Settings,
+    counters: Arc<ByteCounters>,
+    timings: &mut Timings,
+) -> Result<Counted<TcpStream>> {
+    debug!("trying to connect to {}:{}", host, port);
+    let timeout = settings.within_deadline(settings.connect_timeout);
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    settings.observe(Event::DnsStart { host });
+    let started = Instant::now();
+    let mut addrs = resolve(settings.resolver.as_ref(), host, port, timeout)?;
+    let elapsed = started.elapsed();
+    add_time(&mut timings.dns, elapsed);
+    settings.observe(Event::DnsEnd {
+        host,
+        addrs: &addrs,
+        elapsed,
+    });
+    if let Some(health) = &settings.address_health {
+        health.sort(&mut addrs);
+    }
+    let started = Instant::now();
+    let stream = connect_addrs(&addrs, settings, deadline);
+    add_time(&mut timings.connect, started.elapsed());
+    let stream = stream.map_err(|err| match err.kind() {
+        io::ErrorKind::ConnectionRefused => Error::from(ErrorKind::ConnectionRefused),
+        _ => err.into(),
+    })?;
+    // The read timeout and the deadline also bound the reads of the TLS handshake and of the proxy handshakes.
+    Ok(Counted::new(stream, counters, settings)?)
+}
+
+/// Convert the errors caused by a read timeout to `TimedOut` errors. Depending on the platform, the reads
+/// which time out fail with `WouldBlock` or `TimedOut` errors.
+fn timed_out(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::WouldBlock => io::Error::new(io::ErrorKind::TimedOut, "timed out while reading"),
+        _ => err,
+    }
+}
+
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+fn tls_handshake<S>(
+    host: &str,
+    stream: S,
+    settings: &Settings,
+    timings: &mut Timings,
+    tls_early_data: bool,
+) -> Result<TlsStream<S>>
+where
+    S: Read + Write,
+{
+    settings.observe(Event::TlsHandshakeStart { host });
+    let started = Instant::now();
+    let stream = tls::handshake(host, stream, tls_early_data)?;
+    let elapsed = started.elapsed();
+    add_time(&mut timings.tls, elapsed);
+    settings.observe(Event::TlsHandshakeEnd { host, elapsed });
+    Ok(stream)
+}
+
+/// Ask the proxy on the other end of `stream` to open a tunnel to the given host and port.
+///
+/// The `authorization` is sent in a `Proxy-Authorization` header.
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+fn tunnel<S>(stream: &mut S, host: &str, port: u16, authorization: Option<&str>) -> Result
+where
+    S: Read + Write,
+{
+    debug!("opening tunnel to {}:{}", host, port);
+    let mut head = format!("CONNECT {}:{} HTTP/1.1\r\nHost: {}:{}\r\n", host, port, host, port);
+    if let Some(authorization) = authorization {
+        head.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.flush()?;
+
+    // The head of the response is read one byte at a time, to make sure that no bytes sent through the
+    // tunnel are consumed.
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") && !head.ends_with(b"\n\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        head.push(byte[0]);
+    }
+
+    let (_, status, _) = parse_response_head(&mut BufReader::new(&head[..]))?;
+    if !status.is_success() {
+        return Err(ErrorKind::ProxyConnect(status).into());
+    }
+    Ok(())
+}
+
+impl BaseStream {
+    /// Connect to the server of the given URL.
+    ///
+    /// If `alt_svc` is given, the connection is made to this host and port instead, but TLS still
+    /// authenticates the host of the URL. If `proxy` is given, the connection goes through this proxy. The
+    /// resolver of the settings is used. If `tls_early_data` is set, the request may be sent as TLS early data.
+    pub fn connect(
+        url: &Url,
+        proxy: Option<&Url>,
+        settings: &Settings,
+        alt_svc: Option<(&str, u16)>,
+        counters: Arc<ByteCounters>,
+        tls_early_data: bool,
+    ) -> Result<BaseStream> {
+        let mut timings = Timings::default();
+        let stream = match (unix_socket_path(url, settings), proxy) {
+            (Some(path), _) => BaseStream::connect_unix(url, &path, settings, counters.clone())?,
+            (None, Some(proxy)) => {
+                BaseStream::connect_proxy(url, proxy, settings, counters.clone(), &mut timings, tls_early_data)
+                    .map_err(|err| match err.into_kind() {
+                        ErrorKind::Io(err) => timed_out(err).into(),
+                        kind => Error::from(kind),
+                    })?
+            }
+            (None, None) => {
+                BaseStream::connect_direct(url, alt_svc, settings, counters.clone(), &mut timings, tls_early_data)?
+            }
+        };
+        Ok(BaseStream {
+            stream,
+            counters,
+            timings,
+            wire_tap: None,
+            wire_log: None,
+            early: Vec::new(),
+            pool: None,
+            deadline: settings.deadline,
+        })
+    }
+
+    /// Take an idle connection from the pool, skipping the connections which were closed by the server.
+    pub fn checkout(pool: &ConnectionPool, key: &PoolKey, counters: Arc<ByteCounters>) -> Option<BaseStream> {
+        while let Some(mut conn) = pool.checkout(key) {
+            if let Some(counted) = conn.stream.counted_mut() {
+                counted.set_counters(counters.clone());
+            }
+            let mut stream = BaseStream {
+                stream: conn.stream,
+                counters: counters.clone(),
+                timings: Timings::default(),
+                wire_tap: None,
+                wire_log: None,
+                early: Vec::new(),
+                pool: Some(PoolSlot {
+                    pool: pool.clone(),
+                    key: key.clone(),
+                    created: conn.created,
+                    requests: conn.requests + 1,
+                    reusable: true,
+                    keep_alive: KeepAlive::default(),
+                }),
+                deadline: None,
+            };
+            // Nothing is expected on an idle connection, data or the end of the stream means that the server
+            // closed it.
+            match stream.poll_response() {
+                Ok(false) => {
+                    debug!("reusing an idle connection");
+                    return Some(stream);
+                }
+                _ => debug!("dropping an idle connection closed by the server"),
+            }
+        }
+        None
+    }
+
+    #[cfg_attr(not(any(feature = "tls", feature = "tls-rustls")), allow(unused_variables))]
+    fn connect_direct(
+        url: &Url,
+        alt_svc: Option<(&str, u16)>,
+        settings: &Settings,
+        counters: Arc<ByteCounters>,
+        timings: &mut Timings,
+        tls_early_data: bool,
+    ) -> Result<Stream> {
+        let (host, port) = host_port(url)?;
+        let (connect_host, connect_port) = alt_svc.unwrap_or((host, port));
+        match url.scheme() {
+            "http" => Ok(Stream::Plain(connect_tcp(
+                connect_host,
+                connect_port,
+                settings,
+                counters,
+                timings,
+            )?)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            "https" => {
+                let stream = connect_tcp(connect_host, connect_port, settings, counters, timings)?;
+                Ok(Stream::Tls(tls_handshake(
+                    host,
+                    stream,
+                    settings,
+                    timings,
+                    tls_early_data,
+                )?))
+            }
+            _ => Err(ErrorKind::InvalidBaseUrl.into()),
+        }
+    }
+
+    /// Connect to the Unix domain socket at the given path. Only plain HTTP is supported.
+    #[cfg(unix)]
+    fn connect_unix(url: &Url, path: &Path, settings: &Settings, counters: Arc<ByteCounters>) -> Result<Stream> {
+        if url.scheme() == "https" {
+            return Err(ErrorKind::InvalidBaseUrl.into());
+        }
+        debug!("trying to connect to unix socket {}", path.display());
+        Ok(Stream::Unix(Counted::new(
+            UnixStream::connect(path)?,
+            counters,
+            settings,
+        )?))
+    }
+
+    #[cfg(not(unix))]
+    fn connect_unix(_: &Url, _: &Path, _: &Settings, _: Arc<ByteCounters>) -> Result<Stream> {
+        Err(io::Error::from(io::ErrorKind::Unsupported).into())
+    }
+
+    /// Connect to the target URL through the proxy.
+    ///
+    /// HTTPS targets are reached using a tunnel created with the CONNECT method. HTTP targets are reached by
+    /// forwarding the request to the proxy, the request must then be written with an absolute URL. When
+    /// the proxy URL uses `https`, the connection to the proxy itself uses TLS. The credentials of the proxy
+    /// URL are sent to the proxy when opening a tunnel.
+    fn connect_proxy(
+        url: &Url,
+        proxy: &Url,
+        settings: &Settings,
+        counters: Arc<ByteCounters>,
+        timings: &mut Timings,
+        tls_early_data: bool,
+    ) -> Result<Stream> {
+        if socks::is_socks(proxy) {
+            return BaseStream::connect_socks(url, proxy, settings, counters, timings, tls_early_data);
+        }
+        let (proxy_host, proxy_port) = host_port(proxy)?;
+        let stream = connect_tcp(proxy_host, proxy_port, settings, counters, timings)?;
+
+        match (proxy.scheme(), url.scheme()) {
+            ("http", "http") => Ok(Stream::Plain(stream)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            ("http", "https") => {
+                let (host, port) = host_port(url)?;
+                let mut stream = stream;
+                tunnel(&mut stream, host, port, proxy_authorization(proxy).as_deref())?;
+                Ok(Stream::Tls(tls_handshake(
+                    host,
+                    stream,
+                    settings,
+                    timings,
+                    tls_early_data,
+                )?))
+            }
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            ("https", "http") => Ok(Stream::Tls(tls_handshake(
+                proxy_host, stream, settings, timings, false,
+            )?)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            ("https", "https") => {
+                let (host, port) = host_port(url)?;
+                let mut stream = tls_handshake(proxy_host, stream, settings, timings, false)?;
+                tunnel(&mut stream, host, port, proxy_authorization(proxy).as_deref())?;
+                Ok(Stream::TlsTunnel(tls_handshake(
+                    host,
+                    stream,
+                    settings,
+                    timings,
+                    tls_early_data,
+                )?))
+            }
+            ("http", _) => Err(ErrorKind::InvalidBaseUrl.into()),
+            _ => Err(ErrorKind::InvalidProxyUrl.into()),
+        }
+    }
+
+    /// Connect to the target URL through a SOCKS5 proxy, which relays the connection to the server.
+    #[cfg_attr(not(any(feature = "tls", feature = "tls-rustls")), allow(unused_variables))]
+    fn connect_socks(
+        url: &Url,
+        proxy: &Url,
+        settings: &Settings,
+        counters: Arc<ByteCounters>,
+        timings: &mut Timings,
+        tls_early_data: bool,
+    ) -> Result<Stream> {
+        let proxy_host = proxy.host_str().ok_or(ErrorKind::InvalidProxyUrl)?;
+        let proxy_port = proxy.port().unwrap_or(socks::DEFAULT_PORT);
+        let mut stream = connect_tcp(proxy_host, proxy_port, settings, counters, timings)?;
+
+        let (host, port) = host_port(url)?;
+        socks::connect(&mut stream, host, port, proxy, settings)?;
+        match url.scheme() {
+            "http" => Ok(Stream::Plain(stream)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            "https" => Ok(Stream::Tls(tls_handshake(
+                host,
+                stream,
+                settings,
+                timings,
+                tls_early_data,
+            )?)),
+            _ => Err(ErrorKind::InvalidBaseUrl.into()),
+        }
+    }
+
+    /// Create a stream which reads the given bytes.
+    pub fn memory(bytes: Vec<u8>) -> BaseStream {
+        let counters = Arc::new(ByteCounters::default());
+        BaseStream {
+            stream: Stream::Memory(Counted {
+                inner: Cursor::new(bytes),
+                counters: counters.clone(),
+                read_timeout: None,
+                deadline: None,
+                low_speed: None,
+            }),
+            counters,
+            timings: Timings::default(),
+            wire_tap: None,
+            wire_log: None,
+            early: Vec::new(),
+            pool: None,
+            deadline: None,
+        }
+    }
+
+    /// Give this connection back to the given pool once its response was read entirely.
+    pub fn set_pool(&mut self, pool: ConnectionPool, key: PoolKey) {
+        self.pool = Some(PoolSlot {
+            pool,
+            key,
+            created: Instant::now(),
+            requests: 1,
+            reusable: true,
+            keep_alive: KeepAlive::default(),
+        });
+    }
+
+    /// Check if this connection was taken from a pool, and may thus have been closed by the server.
+    pub fn is_reused(&self) -> bool {
+        self.pool.as_ref().is_some_and(|slot| slot.requests > 1)
+    }
+
+    /// Record whether the response allows this connection to be reused, and the parameters of its
+    /// `Keep-Alive` header.
+    pub fn set_keep_alive(&mut self, reusable: bool, keep_alive: KeepAlive) {
+        if let Some(slot) = &mut self.pool {
+            slot.reusable = reusable;
+            slot.keep_alive = keep_alive;
+        }
+    }
+
+    /// Give this connection back to its pool, if it can be reused. Nothing can be read from or written to
+    /// the stream afterwards.
+    pub fn release(&mut self) {
+        let slot = match self.pool.take() {
+            Some(slot) if slot.reusable && self.early.is_empty() => slot,
+            _ => return,
+        };
+        debug!("giving the connection back to the pool");
+        let stream = mem::replace(&mut self.stream, Stream::Released);
+        let conn = Pooled {
+            stream,
+            created: slot.created,
+            requests: slot.requests,
+        };
+        slot.pool.put(slot.key, conn, slot.keep_alive);
+    }
+
+    /// Give the bytes written to and read from this stream to the given `WireTap`.
+    pub fn set_wire_tap(&mut self, wire_tap: Option<WireTap>) {
+        self.wire_tap = wire_tap;
+    }
+
+    /// Write a dump of the exchange on this stream to the given `WireLogger`.
+    pub fn set_wire_log(&mut self, wire_log: Option<WireLogger>) {
+        self.wire_log = wire_log;
+    }
+
+    fn socket(&self) -> Option<SockRef<'_>> {
+        match &self.stream {
+            Stream::Plain(s) => Some(SockRef::from(&s.inner)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::Tls(s) => Some(SockRef::from(&s.get_ref().inner)),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::TlsTunnel(s) => Some(SockRef::from(&s.get_ref().get_ref().inner)),
+            #[cfg(unix)]
+            Stream::Unix(s) => Some(SockRef::from(&s.inner)),
+            _ => None,
+        }
+    }
+
+    /// Set the timeout of the writes on this stream.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self.socket() {
+            Some(socket) => socket.set_write_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the timeout of each read on this stream, the deadline after which all the reads fail and the low
+    /// speed limit of the settings.
+    pub fn set_timeouts(&mut self, settings: &Settings) -> io::Result<()> {
+        self.deadline = settings.deadline;
+        match self.stream.counted_mut() {
+            Some(counted) => counted.set_timeouts(settings),
+            None => Ok(()),
+        }
+    }
+
+    /// Check if the deadline of this stream has passed.
+    pub fn is_expired(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Check without blocking if the server sent a response, for instance before the end of the request. The
+    /// data which is received is kept and returned by the next reads.
+    ///
+    /// Interim responses, such as `100 Continue`, are skipped since the server still expects the request. The
+    /// end of the stream also counts as a response, since there is nothing more to wait for.
+    pub fn poll_response(&mut self) -> io::Result<bool> {
+        loop {
+            match early_data(&self.early) {
+                EarlyData::Interim(len) => {
+                    debug!("skipping an interim response");
+                    self.early.drain(..len);
+                    continue;
+                }
+                EarlyData::Final if !self.early.is_empty() => return Ok(true),
+                _ => {}
+            }
+            match self.socket() {
+                Some(socket) => socket.set_nonblocking(true)?,
+                None => return Ok(false),
+            }
+            // Reading through the TLS layer skips the records which do not contain data, such as session
+            // tickets.
+            let mut buf = [0; 4096];
+            let result = self.read_socket(&mut buf);
+            if let Some(socket) = self.socket() {
+                socket.set_nonblocking(false)?;
+            }
+            match result {
+                Ok(0) => return Ok(true),
+                Ok(n) => self.early.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Wait until the server sends data. The data which is received is kept and returned by the next reads.
+    pub fn wait_for_data(&mut self) -> io::Result<()> {
+        if self.early.is_empty() {
+            let mut buf = [0; 4096];
+            let n = self.read(&mut buf)?;
+            self.early.extend_from_slice(&buf[..n]);
+        }
+        Ok(())
+    }
+
+    /// Send up to `len` bytes of the file from its current position, without copying them through userspace.
+    ///
+    /// Returns `None` if the data must be written through a buffer: over TLS, or when it is recorded by a
+    /// `WireTap` or a `WireLog`.
+    #[cfg(target_os = "linux")]
+    pub fn send_file(&mut self, file: &File, len: usize) -> Option<io::Result<usize>> {
+        match &mut self.stream {
+            Stream::Plain(s) if self.wire_tap.is_none() && self.wire_log.is_none() => Some(s.send_file(file, len)),
+            _ => None,
+        }
+    }
+
+    /// Get the time spent opening this connection.
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    /// Get the counters of bytes sent and received on this stream.
+    pub fn counters(&self) -> &Arc<ByteCounters> {
+        &self.counters
+    }
+
+    /// Read from the stream, without converting the errors of read timeouts.
+    fn read_stream(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.early.is_empty() {
+            let n = buf.len().min(self.early.len());
+            buf[..n].copy_from_slice(&self.early[..n]);
+            self.early.drain(..n);
+            return Ok(n);
+        }
+        self.read_socket(buf)
+    }
+
+    /// Read from the connection, ignoring the data received early.
+    fn read_socket(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.stream {
+            Stream::Plain(s) => s.read(buf),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::Tls(s) => s.read(buf),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::TlsTunnel(s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+            Stream::Memory(s) => s.read(buf),
+            Stream::Released => Ok(0),
+        }?;
+        if let Some(wire_tap) = &self.wire_tap {
+            wire_tap.tap(Direction::Received, &buf[..n]);
+        }
+        if let Some(wire_log) = &mut self.wire_log {
+            wire_log.log(Direction::Received, &buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// What the data received before the end of a request starts with.
+enum EarlyData {
+    /// A complete interim response, with its length.
+    Interim(usize),
+    /// The beginning of a response, which may still be an interim response.
+    Partial,
+    /// A final response, or data which is not a response.
+    Final,
+}
+
+fn early_data(data: &[u8]) -> EarlyData {
+    // `101 Switching Protocols` is a final response for this client.
+    let is_interim = |code: Option<u16>| matches!(code, Some(100..=199)) && code != Some(101);
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut response = httparse::Response::new(&mut headers);
+    match response.parse(data) {
+        Ok(httparse::Status::Complete(len)) if is_interim(response.code) => EarlyData::Interim(len),
+        Ok(httparse::Status::Partial) if response.code.is_none() || is_interim(response.code) => EarlyData::Partial,
+        _ => EarlyData::Final,
+    }
+}
+
+impl Read for BaseStream {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_stream(buf).map_err(|err| match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut if self.is_expired() => {
+                io::Error::new(io::ErrorKind::TimedOut, Error::from(ErrorKind::Timeout))
+            }
+            _ => timed_out(err),
+        })
+    }
+}
+
 impl Write for BaseStream {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self {
-            BaseStream::Plain(s) => s.write(buf),
-            #[cfg(feature = "tls")]
-            BaseStream::Tls(s) => s.write(buf),
-            #[cfg(test)]
-            _ => Ok(0),
+        let n = match &mut self.stream {
+            Stream::Plain(s) => s.write(buf),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::Tls(s) => s.write(buf),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::TlsTunnel(s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+            Stream::Memory(_) => Ok(0),
+            Stream::Released => Err(io::ErrorKind::NotConnected.into()),
+        }?;
+        if let Some(wire_tap) = &self.wire_tap {
+            wire_tap.tap(Direction::Sent, &buf[..n]);
         }
+        if let Some(wire_log) = &mut self.wire_log {
+            wire_log.log(Direction::Sent, &buf[..n]);
+        }
+        Ok(n)
     }
 
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
-        match self {
-            BaseStream::Plain(s) => s.flush(),
-            #[cfg(feature = "tls")]
-            BaseStream::Tls(s) => s.flush(),
-            #[cfg(test)]
+        match &mut self.stream {
+            Stream::Plain(s) => s.flush(),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::Tls(s) => s.flush(),
+            #[cfg(any(feature = "tls", feature = "tls-rustls"))]
+            Stream::TlsTunnel(s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
             _ => Ok(()),
         }
     }
 }
+
+#[test]
+fn test_wire_tap() {
+    use std::sync::Mutex;
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let tapped = received.clone();
+
+    let mut stream = BaseStream::memory(b"hello world".to_vec());
+    stream.set_wire_tap(Some(WireTap::new(move |direction, data| {
+        assert_eq!(direction, Direction::Received);
+        tapped.lock().unwrap().extend_from_slice(data);
+    })));
+
+    let mut buf = [0; 5];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&received.lock().unwrap()[..], b"hello");
+    stream.read_to_end(&mut Vec::new()).unwrap();
+    assert_eq!(&received.lock().unwrap()[..], b"hello world");
+}
+
+#[test]
+fn test_connect_failover() {
+    use std::net::TcpListener;
+
+    use crate::address_health::AddressHealth;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap();
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let health = AddressHealth::new();
+    let settings = Settings::new()
+        .connect_attempt_timeout(Duration::from_secs(1))
+        .address_health(health.clone());
+    connect_addrs(&[closed, open], &settings, None).unwrap();
+    assert!(!health.is_healthy(&closed));
+    assert!(health.is_healthy(&open));
+
+    assert!(connect_addrs(&[closed], &settings, None).is_err());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_connect_interface() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    assert!(connect_addrs(&[addr], &Settings::new().interface("lo"), None).is_ok());
+    assert!(connect_addrs(&[addr], &Settings::new().interface("nonexistent0"), None).is_err());
+}
+
+#[test]
+fn test_connect_local_address() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let local = "127.0.0.1".parse().unwrap();
+    let stream = connect_addrs(&[addr], &Settings::new().local_address(local), None).unwrap();
+    assert_eq!(stream.local_addr().unwrap().ip(), local);
+
+    let local = "::1".parse().unwrap();
+    let err = connect_addrs(&[addr], &Settings::new().local_address(local), None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_connect_tos() {
+    use socket2::SockRef;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let stream = connect_addrs(&[addr], &Settings::new().dscp(46), None).unwrap();
+    assert_eq!(SockRef::from(&stream).tos().unwrap(), 46 << 2);
+}
+
+#[test]
+fn test_connect_socket_options() {
+    use socket2::SockRef;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let stream = connect_addrs(&[addr], &Settings::new(), None).unwrap();
+    assert!(!stream.nodelay().unwrap());
+    assert!(!SockRef::from(&stream).keepalive().unwrap());
+
+    let settings = Settings::new()
+        .tcp_nodelay(true)
+        .tcp_keepalive(Duration::from_secs(30))
+        .send_buffer_size(64 * 1024)
+        .recv_buffer_size(64 * 1024);
+    let stream = connect_addrs(&[addr], &settings, None).unwrap();
+    let socket = SockRef::from(&stream);
+    assert!(stream.nodelay().unwrap());
+    assert!(socket.keepalive().unwrap());
+    assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+}
+
+#[test]
+fn test_connect_tcp_fast_open() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let settings = Settings::new().tcp_fast_open(true);
+    let mut stream = connect_addrs(&[addr], &settings, None).unwrap();
+    stream.write_all(b"hello").unwrap();
+
+    let mut buf = [0; 5];
+    listener.accept().unwrap().0.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+#[cfg(all(test, any(feature = "tls", feature = "tls-rustls")))]
+mod tests {
+    use std::io::{self, Cursor, Read, Write};
+
+    use super::tunnel;
+    use crate::ErrorKind;
+
+    struct MockProxy {
+        response: Cursor<Vec<u8>>,
+        request: Vec<u8>,
+    }
+
+    impl MockProxy {
+        fn new(response: &[u8]) -> MockProxy {
+            MockProxy {
+                response: Cursor::new(response.to_vec()),
+                request: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockProxy {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.response.read(buf)
+        }
+    }
+
+    impl Write for MockProxy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.request.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tunnel_established() {
+        let mut proxy = MockProxy::new(b"HTTP/1.1 200 Connection established\r\n\r\ntunneled");
+        tunnel(&mut proxy, "foo.bar", 443, None).unwrap();
+        assert_eq!(
+            proxy.request,
+            b"CONNECT foo.bar:443 HTTP/1.1\r\nHost: foo.bar:443\r\n\r\n"
+        );
+
+        let mut rest = String::new();
+        proxy.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "tunneled");
+    }
+
+    #[test]
+    fn test_tunnel_authorization() {
+        let mut proxy = MockProxy::new(b"HTTP/1.1 200 Connection established\r\n\r\n");
+        tunnel(&mut proxy, "foo.bar", 443, Some("Basic dXNlcjpwYXNz")).unwrap();
+        assert_eq!(
+            proxy.request,
+            &b"CONNECT foo.bar:443 HTTP/1.1\r\nHost: foo.bar:443\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_tunnel_refused() {
+        let mut proxy = MockProxy::new(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+        match tunnel(&mut proxy, "foo.bar", 443, None).unwrap_err().into_kind() {
+            ErrorKind::ProxyConnect(status) => assert_eq!(status.as_u16(), 407),
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
+}
+
+#[test]
+fn test_low_speed() {
+    let settings = Settings::default().low_speed_limit(1000, Duration::from_millis(50));
+    let mut low_speed = LowSpeed::new(&settings).unwrap();
+    assert_eq!(low_speed.min_bytes, 50);
+    low_speed.add(40).unwrap();
+    std::thread::sleep(Duration::from_millis(60));
+    // The period ended with enough bytes, a new period starts.
+    low_speed.add(20).unwrap();
+    assert_eq!(low_speed.transferred, 0);
+    low_speed.add(10).unwrap();
+    std::thread::sleep(Duration::from_millis(60));
+    let err = low_speed.add(10).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    match Error::from(err).kind() {
+        ErrorKind::TransferTooSlow => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
@@ -0,0 +1,384 @@
+//! TLS streams, provided by `native-tls` with the `tls` feature or by `rustls` with the `tls-rustls` feature.
+//!
+//! When both features are enabled, `rustls` is used.
+use std::io;
+
+#[cfg(all(feature = "tls", not(feature = "tls-rustls")))]
+pub use self::native_impl::{handshake, TlsStream};
+#[cfg(feature = "tls-rustls")]
+pub use self::rustls_impl::{handshake, TlsStream};
+
+#[cfg(all(feature = "tls", not(feature = "tls-rustls")))]
+mod native_impl {
+    use std::io::{self, Read, Write};
+
+    use native_tls::{HandshakeError, TlsConnector};
+
+    use crate::Result;
+
+    pub struct TlsStream<S>(native_tls::TlsStream<S>);
+
+    impl<S: Read + Write> TlsStream<S> {
+        pub fn get_ref(&self) -> &S {
+            self.0.get_ref()
+        }
+
+        pub fn get_mut(&mut self) -> &mut S {
+            self.0.get_mut()
+        }
+    }
+
+    impl<S: Read + Write> Read for TlsStream<S> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<S: Read + Write> Write for TlsStream<S> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    /// Perform the TLS handshake with the given host over `stream`.
+    ///
+    /// native-tls does not support early data, `early_data` is ignored.
+    pub fn handshake<S: Read + Write>(host: &str, stream: S, _early_data: bool) -> Result<TlsStream<S>> {
+        let connector = TlsConnector::new()?;
+        match connector.connect(host, stream) {
+            Ok(stream) => Ok(TlsStream(stream)),
+            Err(HandshakeError::Failure(err)) => Err(err.into()),
+            // The socket is blocking, the handshake is only interrupted when a read times out.
+            Err(HandshakeError::WouldBlock(_)) => Err(super::handshake_timed_out().into()),
+        }
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+mod rustls_impl {
+    use std::convert::TryFrom;
+    use std::io::{self, Read, Write};
+    use std::sync::{Arc, OnceLock};
+
+    use rustls::pki_types::ServerName;
+    use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+    use crate::{Error, ErrorKind, Result};
+
+    pub struct TlsStream<S: Read + Write> {
+        /// The connection state of rustls is large, it is boxed to keep the size of `Stream` small.
+        stream: Box<StreamOwned<ClientConnection, S>>,
+        /// The data sent as early data while the handshake is not finished, which is sent again if the server
+        /// rejects it.
+        early_data: Option<Vec<u8>>,
+    }
+
+    impl<S: Read + Write> TlsStream<S> {
+        pub fn get_ref(&self) -> &S {
+            self.stream.get_ref()
+        }
+
+        pub fn get_mut(&mut self) -> &mut S {
+            self.stream.get_mut()
+        }
+
+        /// Send the pending TLS records.
+        fn write_tls(&mut self) -> io::Result<()> {
+            while self.stream.conn.wants_write() {
+                self.stream.conn.write_tls(&mut self.stream.sock)?;
+            }
+            Ok(())
+        }
+
+        /// Finish the handshake which was left pending to send early data, and send the early data again if
+        /// the server rejected it.
+        fn finish_handshake(&mut self) -> io::Result<()> {
+            let StreamOwned { conn, sock } = &mut *self.stream;
+            while conn.is_handshaking() {
+                conn.complete_io(sock).map_err(tls_error)?;
+            }
+            if let Some(sent) = self.early_data.take() {
+                if !sent.is_empty() && !conn.is_early_data_accepted() {
+                    debug!("the server rejected the early data, sending it again");
+                    conn.writer().write_all(&sent)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<S: Read + Write> Read for TlsStream<S> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.early_data.is_some() {
+                self.finish_handshake()?;
+            }
+            match self.stream.read(buf) {
+                // Many servers close the connection without sending a close_notify alert. The end of the body
+                // is known from its framing, so this is treated as the end of the stream like native-tls does.
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+                res => res,
+            }
+        }
+    }
+
+    impl<S: Read + Write> Write for TlsStream<S> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.early_data.is_some() {
+                let written = match self.stream.conn.early_data() {
+                    Some(mut early_data) => early_data.write(buf)?,
+                    None => 0,
+                };
+                if written > 0 {
+                    if let Some(sent) = &mut self.early_data {
+                        sent.extend_from_slice(&buf[..written]);
+                    }
+                    // The data was accepted, the records which could not be sent yet are sent by the next
+                    // writes, flushes or reads.
+                    match self.write_tls() {
+                        Err(err) if !matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                            return Err(err)
+                        }
+                        _ => return Ok(written),
+                    }
+                }
+                // The data does not fit in the early data allowed by the server.
+                self.finish_handshake()?;
+            }
+            self.stream.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            // Flushing the stream would wait for the end of the handshake.
+            if self.early_data.is_some() {
+                self.write_tls()?;
+                return self.stream.sock.flush();
+            }
+            self.stream.flush()
+        }
+    }
+
+    /// The configurations are shared by all the connections, which lets rustls resume the sessions it cached.
+    /// The configuration which enables early data shares the cache of the other one.
+    fn config(early_data: bool) -> Result<Arc<ClientConfig>> {
+        static CONFIGS: OnceLock<(Arc<ClientConfig>, Arc<ClientConfig>)> = OnceLock::new();
+        let configs = match CONFIGS.get() {
+            Some(configs) => configs,
+            None => {
+                let roots = RootCertStore {
+                    roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+                };
+                let provider = Arc::new(rustls::crypto::ring::default_provider());
+                let config = ClientConfig::builder_with_provider(provider)
+                    .with_safe_default_protocol_versions()?
+                    .with_root_certificates(roots)
+                    .with_no_client_auth();
+                let mut with_early_data = config.clone();
+                with_early_data.enable_early_data = true;
+                CONFIGS.get_or_init(|| (Arc::new(config), Arc::new(with_early_data)))
+            }
+        };
+        Ok(if early_data {
+            configs.1.clone()
+        } else {
+            configs.0.clone()
+        })
+    }
+
+    /// Perform the TLS handshake with the given host over `stream`.
+    ///
+    /// If `early_data` is set and a session with the host can be resumed with early data, the handshake is
+    /// finished by the first read and the data written until then is sent as early data.
+    pub fn handshake<S: Read + Write>(host: &str, stream: S, early_data: bool) -> Result<TlsStream<S>> {
+        handshake_with(config(early_data)?, host, stream)
+    }
+
+    fn handshake_with<S: Read + Write>(config: Arc<ClientConfig>, host: &str, mut stream: S) -> Result<TlsStream<S>> {
+        // IPv6 addresses are bracketed in URLs.
+        let name = host.trim_start_matches('[').trim_end_matches(']');
+        let name = ServerName::try_from(name.to_owned())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid server name"))?;
+        let mut conn = ClientConnection::new(config, name)?;
+        if conn.early_data().is_some() {
+            debug!("sending early data to {}", host);
+            return Ok(TlsStream {
+                stream: Box::new(StreamOwned::new(conn, stream)),
+                early_data: Some(Vec::new()),
+            });
+        }
+        while conn.is_handshaking() {
+            conn.complete_io(&mut stream).map_err(|err| match err.kind() {
+                // The socket is blocking, the handshake is only interrupted when a read times out.
+                io::ErrorKind::WouldBlock => super::handshake_timed_out(),
+                _ => tls_error(err),
+            })?;
+        }
+        Ok(TlsStream {
+            stream: Box::new(StreamOwned::new(conn, stream)),
+            early_data: None,
+        })
+    }
+
+    /// Convert the TLS errors which rustls returns inside of `io::Error`s to errors of this crate.
+    fn tls_error(err: io::Error) -> io::Error {
+        if err.get_ref().is_some_and(|inner| inner.is::<rustls::Error>()) {
+            let kind = err.kind();
+            let inner = err.into_inner().unwrap().downcast::<rustls::Error>().unwrap();
+            return io::Error::new(kind, Error::from(ErrorKind::Rustls(*inner)));
+        }
+        err
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::sync::Arc;
+        use std::thread;
+        use std::time::Duration;
+
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+        use rustls::{ClientConfig, RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+
+        use super::handshake_with;
+
+        // Certificates of a test authority and of `localhost`, signed by this authority.
+        const CA: &[u8] = include_bytes!("../../tests/certs/ca.der");
+        const CERT: &[u8] = include_bytes!("../../tests/certs/localhost.der");
+        const KEY: &[u8] = include_bytes!("../../tests/certs/localhost.key.der");
+
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+        fn client_config() -> Arc<ClientConfig> {
+            let mut roots = RootCertStore::empty();
+            roots.add(CertificateDer::from(CA)).unwrap();
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let mut config = ClientConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            config.enable_early_data = true;
+            Arc::new(config)
+        }
+
+        fn server_config() -> ServerConfig {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KEY));
+            let mut config = ServerConfig::builder_with_provider(provider)
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(vec![CertificateDer::from(CERT)], key)
+                .unwrap();
+            config.max_early_data_size = 16 * 1024;
+            config
+        }
+
+        /// Answer a request on each of the connections, which use the given configurations. Returns whether each
+        /// request was received as early data.
+        fn start_server(configs: Vec<ServerConfig>) -> (u16, thread::JoinHandle<Vec<bool>>) {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let server = thread::spawn(move || {
+                let mut received_early = Vec::new();
+                for config in configs {
+                    let (sock, _) = listener.accept().unwrap();
+                    sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+                    let mut stream = StreamOwned::new(ServerConnection::new(Arc::new(config)).unwrap(), sock);
+                    while stream.conn.is_handshaking() {
+                        stream.conn.complete_io(&mut stream.sock).unwrap();
+                    }
+                    let mut request = Vec::new();
+                    if let Some(mut early_data) = stream.conn.early_data() {
+                        early_data.read_to_end(&mut request).unwrap();
+                    }
+                    let early = !request.is_empty();
+                    let mut buf = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let n = stream.read(&mut buf).unwrap();
+                        assert!(n > 0);
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                        .unwrap();
+                    stream.conn.send_close_notify();
+                    stream.flush().unwrap();
+                    assert_eq!(request, REQUEST);
+                    received_early.push(early);
+                }
+                received_early
+            });
+            (port, server)
+        }
+
+        fn get(config: &Arc<ClientConfig>, port: u16) -> Vec<u8> {
+            let sock = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            sock.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut stream = handshake_with(config.clone(), "localhost", sock).unwrap();
+            stream.write_all(REQUEST).unwrap();
+            stream.flush().unwrap();
+            let mut response = Vec::new();
+            stream.read_to_end(&mut response).unwrap();
+            response
+        }
+
+        #[test]
+        fn test_early_data_accepted() {
+            let config = server_config();
+            let (port, server) = start_server(vec![config.clone(), config]);
+            let config = client_config();
+            assert!(get(&config, port).ends_with(b"ok"));
+            // The second connection resumes the session of the first one.
+            assert!(get(&config, port).ends_with(b"ok"));
+            assert_eq!(server.join().unwrap(), vec![false, true]);
+        }
+
+        #[test]
+        fn test_early_data_rejected() {
+            let accepting = server_config();
+            // The session can still be resumed, the configurations share the session cache.
+            let mut rejecting = accepting.clone();
+            rejecting.max_early_data_size = 0;
+            let (port, server) = start_server(vec![accepting, rejecting]);
+            let config = client_config();
+            assert!(get(&config, port).ends_with(b"ok"));
+            assert!(get(&config, port).ends_with(b"ok"));
+            assert_eq!(server.join().unwrap(), vec![false, false]);
+        }
+    }
+}
+
+fn handshake_timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "timed out during the TLS handshake")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::handshake;
+
+    #[test]
+    fn test_handshake_with_plain_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 512];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n");
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let err = handshake("localhost", stream, false).err().unwrap();
+        assert!(err.is_tls(), "unexpected error {:?}", err);
+        server.join().unwrap();
+    }
+}
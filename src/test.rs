@@ -0,0 +1,426 @@
+//! Utilities for testing code which uses this crate.
+//!
+//! The `TestServer` is a tiny HTTP server running in a background thread. It answers requests with the
+//! canned responses registered for their method and path, and records the requests it receives so that
+//! tests can make assertions about them.
+//!
+//! Responses can be scripted to fail in realistic ways using `Fault`s, for instance to test how an
+//! application handles timeouts or retries.
+//!
+//! This module only exists when the `test-server` feature is enabled.
+//!
+//! # Example
+//! ```
+//! use attohttpc::test::{CannedResponse, TestServer};
+//! use attohttpc::{Method, StatusCode};
+//!
+//! # fn main() -> attohttpc::Result {
+//! let server = TestServer::new()?;
+//! server.route(Method::GET, "/hello", CannedResponse::new(StatusCode::OK).body("world"));
+//!
+//! let resp = attohttpc::get(server.url("/hello")).send()?;
+//! assert_eq!(resp.text()?, "world");
+//!
+//! let requests = server.requests();
+//! assert_eq!(requests.len(), 1);
+//! assert_eq!(requests[0].path(), "/hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH};
+use http::{Method, StatusCode};
+
+/// A failure injected by the `TestServer` while sending a `CannedResponse`.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Wait for the given duration before sending the response.
+    Latency(Duration),
+    /// Send the response in pieces of the given size, waiting for the given duration between pieces.
+    PartialWrites(usize, Duration),
+    /// Close the connection after sending the given number of bytes of the response.
+    Reset(usize),
+    /// Send the given bytes instead of the response, for instance to test malformed framing.
+    Raw(Vec<u8>),
+}
+
+/// A response sent by the `TestServer`.
+#[derive(Debug, Clone)]
+pub struct CannedResponse {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    faults: Vec<Fault>,
+}
+
+impl CannedResponse {
+    /// Create a new `CannedResponse` with the given status and an empty body.
+    pub fn new(status: StatusCode) -> CannedResponse {
+        CannedResponse {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            faults: Vec::new(),
+        }
+    }
+
+    /// Add a header to this `CannedResponse`.
+    ///
+    /// The `Content-Length` header is always sent and does not need to be added.
+    pub fn header<K, V>(mut self, name: K, value: V) -> CannedResponse
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the body of this `CannedResponse`.
+    pub fn body<B>(mut self, body: B) -> CannedResponse
+    where
+        B: Into<Vec<u8>>,
+    {
+        self.body = body.into();
+        self
+    }
+
+    /// Inject a fault while sending this `CannedResponse`.
+    ///
+    /// Faults are applied in the order in which they are added.
+    pub fn fault(mut self, fault: Fault) -> CannedResponse {
+        self.faults.push(fault);
+        self
+    }
+
+    /// Write this response to the stream. Returns false if the connection must be closed.
+    fn write_to(&self, mut stream: &TcpStream, method: &Method) -> io::Result<bool> {
+        let mut buf = Vec::new();
+        write!(
+            buf,
+            "HTTP/1.1 {} {}\r\n",
+            self.status.as_u16(),
+            self.status.canonical_reason().unwrap_or("")
+        )?;
+        for (name, value) in &self.headers {
+            write!(buf, "{}: {}\r\n", name, value)?;
+        }
+        write!(buf, "Content-Length: {}\r\n\r\n", self.body.len())?;
+        if *method != Method::HEAD {
+            buf.extend(&self.body);
+        }
+
+        let mut piece = (buf.len().max(1), Duration::from_secs(0));
+        let mut reset = false;
+        for fault in &self.faults {
+            match fault {
+                Fault::Latency(delay) => thread::sleep(*delay),
+                Fault::PartialWrites(size, delay) => piece = ((*size).max(1), *delay),
+                Fault::Reset(len) => {
+                    buf.truncate(*len);
+                    reset = true;
+                }
+                Fault::Raw(bytes) => buf = bytes.clone(),
+            }
+        }
+
+        for (i, chunk) in buf.chunks(piece.0).enumerate() {
+            if i > 0 {
+                thread::sleep(piece.1);
+            }
+            stream.write_all(chunk)?;
+            stream.flush()?;
+        }
+
+        if reset {
+            stream.shutdown(Shutdown::Both)?;
+        }
+        Ok(!reset)
+    }
+}
+
+/// A request received by the `TestServer`.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Get the method of this request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Get the target of this request, as written in the request line.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the headers of this request.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of this request.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+#[derive(Default)]
+struct State {
+    routes: Mutex<HashMap<(Method, String), VecDeque<CannedResponse>>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    stopped: AtomicBool,
+}
+
+/// A scripted HTTP server bound to a local port.
+///
+/// Requests for which no response was registered are answered with a `404 Not Found`. The server is
+/// stopped when the `TestServer` is dropped.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+}
+
+impl TestServer {
+    /// Start a new `TestServer` on a free port of the loopback interface.
+    pub fn new() -> io::Result<TestServer> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(State::default());
+
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_state.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = handle_connection(stream, &thread_state) {
+                            debug!("test server connection error: {}", err);
+                        }
+                    }
+                    Err(err) => debug!("test server accept error: {}", err),
+                }
+            }
+        });
+
+        Ok(TestServer { addr, state })
+    }
+
+    /// Get the port the server is listening on.
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Get the URL of the given path on this server.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Register the response sent to the requests with the given method and path.
+    ///
+    /// The path must match the target of the request exactly, including the query string.
+    pub fn route<P>(&self, method: Method, path: P, response: CannedResponse)
+    where
+        P: Into<String>,
+    {
+        self.route_script(method, path, vec![response]);
+    }
+
+    /// Register a script of responses sent to the requests with the given method and path.
+    ///
+    /// Each request receives the next response of the script, and the last response is repeated once
+    /// the script is over. This is useful to test retries, for instance with a failing response followed
+    /// by a successful one.
+    ///
+    /// # Panics
+    /// Panics if the script is empty.
+    pub fn route_script<P>(&self, method: Method, path: P, responses: Vec<CannedResponse>)
+    where
+        P: Into<String>,
+    {
+        assert!(!responses.is_empty(), "empty response script");
+        self.state
+            .routes
+            .lock()
+            .unwrap()
+            .insert((method, path.into()), responses.into());
+    }
+
+    /// Get the requests received by this server so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::SeqCst);
+        // Wake the server thread up so that it notices it must stop.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid request")
+}
+
+fn read_request<R>(reader: &mut R) -> io::Result<Option<RecordedRequest>>
+where
+    R: BufRead,
+{
+    let mut buf = Vec::new();
+    loop {
+        let start = buf.len();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if start > 0 && (&buf[start..] == b"\r\n" || &buf[start..] == b"\n") {
+            break;
+        }
+    }
+
+    let mut raw_headers = [httparse::EMPTY_HEADER; 64];
+    let mut request = httparse::Request::new(&mut raw_headers);
+    match request.parse(&buf) {
+        Ok(httparse::Status::Complete(_)) => {}
+        _ => return Err(invalid_data()),
+    }
+
+    let method = Method::from_bytes(request.method.unwrap_or("").as_bytes()).map_err(|_| invalid_data())?;
+    let path = request.path.unwrap_or("").to_string();
+    let mut headers = HeaderMap::new();
+    for header in request.headers.iter() {
+        headers.append(
+            HeaderName::from_bytes(header.name.as_bytes()).map_err(|_| invalid_data())?,
+            HeaderValue::from_bytes(header.value).map_err(|_| invalid_data())?,
+        );
+    }
+
+    let length = match headers.get(CONTENT_LENGTH) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(invalid_data)?,
+        None => 0,
+    };
+    let mut body = Vec::new();
+    reader.take(length).read_to_end(&mut body)?;
+
+    Ok(Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+fn handle_connection(stream: TcpStream, state: &State) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    while let Some(request) = read_request(&mut reader)? {
+        let close = request
+            .headers
+            .get(CONNECTION)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+
+        let response = match state
+            .routes
+            .lock()
+            .unwrap()
+            .get_mut(&(request.method.clone(), request.path.clone()))
+        {
+            Some(script) if script.len() > 1 => script.pop_front().unwrap(),
+            Some(script) => script[0].clone(),
+            None => CannedResponse::new(StatusCode::NOT_FOUND),
+        };
+        let method = request.method.clone();
+        state.requests.lock().unwrap().push(request);
+
+        if !response.write_to(&stream, &method)? || close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_server_routes_and_records() {
+    let server = TestServer::new().unwrap();
+    server.route(
+        Method::POST,
+        "/echo?x=1",
+        CannedResponse::new(StatusCode::CREATED)
+            .header("X-Test", "yes")
+            .body("done"),
+    );
+
+    let resp = crate::post(server.url("/echo?x=1")).text("hello").send().unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["x-test"], "yes");
+    assert_eq!(resp.text().unwrap(), "done");
+
+    let resp = crate::get(server.url("/missing")).send().unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method(), Method::POST);
+    assert_eq!(requests[0].path(), "/echo?x=1");
+    assert_eq!(requests[0].headers()["content-length"], "5");
+    assert_eq!(requests[0].body(), b"hello");
+    assert_eq!(requests[1].path(), "/missing");
+}
+
+#[test]
+fn test_server_faults() {
+    use std::time::Instant;
+
+    let server = TestServer::new().unwrap();
+    server.route_script(
+        Method::GET,
+        "/flaky",
+        vec![
+            CannedResponse::new(StatusCode::OK)
+                .body("hello world")
+                .fault(Fault::Reset(40)),
+            CannedResponse::new(StatusCode::OK)
+                .body("hello world")
+                .fault(Fault::Latency(Duration::from_millis(50)))
+                .fault(Fault::PartialWrites(3, Duration::from_millis(1))),
+        ],
+    );
+    server.route(
+        Method::GET,
+        "/malformed",
+        CannedResponse::new(StatusCode::OK).fault(Fault::Raw(b"HTTP/1.1 OK\r\n\r\n".to_vec())),
+    );
+
+    // The body is cut short by the reset.
+    let resp = crate::get(server.url("/flaky")).send().unwrap();
+    assert!(resp.bytes().is_err());
+
+    let start = Instant::now();
+    let resp = crate::get(server.url("/flaky")).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello world");
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    assert!(crate::get(server.url("/malformed")).send().is_err());
+}
@@ -0,0 +1,51 @@
+//! Timings of the requests.
+
+use std::time::Duration;
+
+/// The time spent in the steps of a request, given by `Response::timings`.
+///
+/// The name resolution, connection and TLS handshake timings concern the connection on which the response
+/// was received. They are `None` when the step did not happen, for instance when a connection was reused
+/// from the pool. When a proxy is used, they include the steps needed to reach the proxy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub(crate) dns: Option<Duration>,
+    pub(crate) connect: Option<Duration>,
+    pub(crate) tls: Option<Duration>,
+    pub(crate) first_byte: Duration,
+    pub(crate) total: Duration,
+}
+
+impl Timings {
+    /// Get the time spent resolving host names.
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Get the time spent connecting, including the attempts on the addresses which failed.
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// Get the time spent in TLS handshakes.
+    pub fn tls(&self) -> Option<Duration> {
+        self.tls
+    }
+
+    /// Get the time elapsed between the moment the request started to be written and the reception of the
+    /// first byte of the response.
+    pub fn time_to_first_byte(&self) -> Duration {
+        self.first_byte
+    }
+
+    /// Get the time elapsed between the moment the request was sent and the reception of the head of the
+    /// response, including the redirections which were followed and the requests which were sent again.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+}
+
+/// Add the duration to the time spent in a step.
+pub(crate) fn add_time(step: &mut Option<Duration>, duration: Duration) {
+    *step = Some(step.unwrap_or_default() + duration);
+}
@@ -0,0 +1,440 @@
+//! Expansion of URI templates, as described in RFC 6570.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{ErrorKind, Result};
+
+/// The value of a variable of a `UriTemplate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateValue {
+    /// A string value.
+    String(String),
+    /// A list of values, such as `["red", "green"]`.
+    List(Vec<String>),
+    /// An associative array of values, such as `{"semi": ";", "dot": "."}`, in order.
+    Map(Vec<(String, String)>),
+}
+
+impl From<&str> for TemplateValue {
+    fn from(value: &str) -> TemplateValue {
+        TemplateValue::String(value.to_string())
+    }
+}
+
+impl From<String> for TemplateValue {
+    fn from(value: String) -> TemplateValue {
+        TemplateValue::String(value)
+    }
+}
+
+macro_rules! impl_from_display {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for TemplateValue {
+                fn from(value: $ty) -> TemplateValue {
+                    TemplateValue::String(value.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_display!(bool, char, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl<S> From<Vec<S>> for TemplateValue
+where
+    S: Into<String>,
+{
+    fn from(values: Vec<S>) -> TemplateValue {
+        TemplateValue::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for TemplateValue
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from(values: BTreeMap<K, V>) -> TemplateValue {
+        TemplateValue::Map(values.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    first: &'static str,
+    separator: char,
+    named: bool,
+    if_empty: &'static str,
+    allow_reserved: bool,
+}
+
+impl Operator {
+    fn parse(c: Option<char>) -> Option<Operator> {
+        let (first, separator, named, if_empty, allow_reserved) = match c {
+            None => ("", ',', false, "", false),
+            Some('+') => ("", ',', false, "", true),
+            Some('#') => ("#", ',', false, "", true),
+            Some('.') => (".", '.', false, "", false),
+            Some('/') => ("/", '/', false, "", false),
+            Some(';') => (";", ';', true, "", false),
+            Some('?') => ("?", '&', true, "=", false),
+            Some('&') => ("&", '&', true, "=", false),
+            Some(_) => return None,
+        };
+        Some(Operator {
+            first,
+            separator,
+            named,
+            if_empty,
+            allow_reserved,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct VarSpec {
+    name: String,
+    prefix: Option<usize>,
+    explode: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Expression(Operator, Vec<VarSpec>),
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() => {
+                i += 3;
+                continue;
+            }
+            b'.' if i > 0 && i + 1 < bytes.len() && bytes[i - 1] != b'.' => {}
+            c if c.is_ascii_alphanumeric() || c == b'_' => {}
+            _ => return false,
+        }
+        i += 1;
+    }
+    !name.is_empty()
+}
+
+fn parse_varspec(spec: &str) -> Option<VarSpec> {
+    let (name, prefix, explode) = if let Some(name) = spec.strip_suffix('*') {
+        (name, None, true)
+    } else if let Some(colon) = spec.find(':') {
+        let prefix = &spec[colon + 1..];
+        if prefix.is_empty() || prefix.len() > 4 || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let prefix: usize = prefix.parse().ok()?;
+        if prefix == 0 || prefix >= 10000 {
+            return None;
+        }
+        (&spec[..colon], Some(prefix), false)
+    } else {
+        (spec, None, false)
+    };
+    if !is_valid_name(name) {
+        return None;
+    }
+    Some(VarSpec {
+        name: name.to_string(),
+        prefix,
+        explode,
+    })
+}
+
+fn parse_template(template: &str) -> Option<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        match rest.find(['{', '}']) {
+            Some(i) if rest[i..].starts_with('}') => return None,
+            Some(i) => {
+                if i > 0 {
+                    parts.push(Part::Literal(rest[..i].to_string()));
+                }
+                let end = i + rest[i..].find('}')?;
+                let expression = &rest[i + 1..end];
+                let mut chars = expression.chars();
+                let first = chars.clone().next()?;
+                let (operator, vars) = match Operator::parse(Some(first)) {
+                    Some(operator) => {
+                        chars.next();
+                        (operator, chars.as_str())
+                    }
+                    None if "=,!@|".contains(first) => return None,
+                    None => (Operator::parse(None)?, expression),
+                };
+                let specs = vars.split(',').map(parse_varspec).collect::<Option<Vec<_>>>()?;
+                parts.push(Part::Expression(operator, specs));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                parts.push(Part::Literal(rest.to_string()));
+                rest = "";
+            }
+        }
+    }
+    Some(parts)
+}
+
+/// Percent-encode the value, keeping the unreserved characters, and the reserved characters and
+/// percent-encoded triplets if `allow_reserved` is set.
+fn encode(out: &mut String, value: &str, allow_reserved: bool) {
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let keep = b.is_ascii_alphanumeric()
+            || b"-._~".contains(&b)
+            || (allow_reserved && b":/?#[]@!$&'()*+,;=".contains(&b))
+            || (allow_reserved
+                && b == b'%'
+                && i + 2 < bytes.len()
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit());
+        if keep {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+        i += 1;
+    }
+}
+
+fn encode_literal(out: &mut String, literal: &str) {
+    encode(out, literal, true);
+}
+
+fn truncate(value: &str, prefix: Option<usize>) -> &str {
+    match prefix.and_then(|n| value.char_indices().nth(n)) {
+        Some((i, _)) => &value[..i],
+        None => value,
+    }
+}
+
+fn expand_expression(out: &mut String, op: Operator, specs: &[VarSpec], vars: &HashMap<String, TemplateValue>) {
+    let mut first = true;
+    for spec in specs {
+        let value = match vars.get(&spec.name) {
+            Some(TemplateValue::List(list)) if list.is_empty() => continue,
+            Some(TemplateValue::Map(map)) if map.is_empty() => continue,
+            Some(value) => value,
+            None => continue,
+        };
+
+        if first {
+            out.push_str(op.first);
+            first = false;
+        } else {
+            out.push(op.separator);
+        }
+
+        match value {
+            TemplateValue::String(s) => {
+                if op.named {
+                    out.push_str(&spec.name);
+                    if s.is_empty() {
+                        out.push_str(op.if_empty);
+                        continue;
+                    }
+                    out.push('=');
+                }
+                encode(out, truncate(s, spec.prefix), op.allow_reserved);
+            }
+            TemplateValue::List(list) if spec.explode => {
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        out.push(op.separator);
+                    }
+                    if op.named {
+                        out.push_str(&spec.name);
+                        if item.is_empty() {
+                            out.push_str(op.if_empty);
+                            continue;
+                        }
+                        out.push('=');
+                    }
+                    encode(out, item, op.allow_reserved);
+                }
+            }
+            TemplateValue::List(list) => {
+                if op.named {
+                    out.push_str(&spec.name);
+                    out.push('=');
+                }
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    encode(out, item, op.allow_reserved);
+                }
+            }
+            TemplateValue::Map(map) if spec.explode => {
+                for (i, (key, item)) in map.iter().enumerate() {
+                    if i > 0 {
+                        out.push(op.separator);
+                    }
+                    encode(out, key, op.allow_reserved);
+                    if op.named && item.is_empty() {
+                        out.push_str(op.if_empty);
+                        continue;
+                    }
+                    out.push('=');
+                    encode(out, item, op.allow_reserved);
+                }
+            }
+            TemplateValue::Map(map) => {
+                if op.named {
+                    out.push_str(&spec.name);
+                    out.push('=');
+                }
+                for (i, (key, item)) in map.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    encode(out, key, op.allow_reserved);
+                    out.push(',');
+                    encode(out, item, op.allow_reserved);
+                }
+            }
+        }
+    }
+}
+
+/// A URI template, as described in RFC 6570, such as `https://api.github.com/users/{user}/repos{?page,per_page}`.
+///
+/// All the levels of RFC 6570 are supported. The values of the variables are set with `set`, and the
+/// template is expanded into a URL with `expand`. Variables which are not set are omitted.
+///
+/// ```
+/// # use attohttpc::UriTemplate;
+/// let url = UriTemplate::new("https://api.example.com/users/{id}/repos{?page,per_page}")
+///     .set("id", 42)
+///     .set("page", 2)
+///     .expand();
+/// assert_eq!(url, "https://api.example.com/users/42/repos?page=2");
+/// ```
+///
+/// This type only exists when the `uri-template` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct UriTemplate {
+    parts: Vec<Part>,
+    vars: HashMap<String, TemplateValue>,
+}
+
+impl UriTemplate {
+    /// Parse the given URI template.
+    ///
+    /// # Panics
+    /// This method will panic if the template is invalid.
+    pub fn new<T>(template: T) -> UriTemplate
+    where
+        T: AsRef<str>,
+    {
+        UriTemplate::try_new(template).expect("invalid uri template")
+    }
+
+    /// Parse the given URI template.
+    pub fn try_new<T>(template: T) -> Result<UriTemplate>
+    where
+        T: AsRef<str>,
+    {
+        let parts = parse_template(template.as_ref()).ok_or(ErrorKind::InvalidUriTemplate)?;
+        Ok(UriTemplate {
+            parts,
+            vars: HashMap::new(),
+        })
+    }
+
+    /// Set the value of a variable of this template.
+    pub fn set<V>(mut self, name: &str, value: V) -> UriTemplate
+    where
+        V: Into<TemplateValue>,
+    {
+        self.vars.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Expand this template using the values of the variables.
+    pub fn expand(&self) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => encode_literal(&mut out, literal),
+                Part::Expression(op, specs) => expand_expression(&mut out, *op, specs, &self.vars),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+fn rfc_examples(template: &str) -> String {
+    let mut keys = BTreeMap::new();
+    keys.insert("semi", ";");
+    keys.insert("dot", ".");
+    keys.insert("comma", ",");
+
+    UriTemplate::new(template)
+        .set("var", "value")
+        .set("hello", "Hello World!")
+        .set("path", "/foo/bar")
+        .set("empty", "")
+        .set("x", 1024)
+        .set("y", 768)
+        .set("list", vec!["red", "green", "blue"])
+        .set("keys", keys)
+        .expand()
+}
+
+#[test]
+fn test_simple_expansion() {
+    assert_eq!(rfc_examples("{var}"), "value");
+    assert_eq!(rfc_examples("{hello}"), "Hello%20World%21");
+    assert_eq!(rfc_examples("{x,y}"), "1024,768");
+    assert_eq!(rfc_examples("{x,undef,y}"), "1024,768");
+    assert_eq!(rfc_examples("{var:3}"), "val");
+    assert_eq!(rfc_examples("{list}"), "red,green,blue");
+    assert_eq!(rfc_examples("{list*}"), "red,green,blue");
+    assert_eq!(rfc_examples("{keys}"), "comma,%2C,dot,.,semi,%3B");
+    assert_eq!(rfc_examples("{keys*}"), "comma=%2C,dot=.,semi=%3B");
+}
+
+#[test]
+fn test_reserved_and_fragment_expansion() {
+    assert_eq!(rfc_examples("{+path}/here"), "/foo/bar/here");
+    assert_eq!(rfc_examples("{+hello}"), "Hello%20World!");
+    assert_eq!(rfc_examples("{#var}"), "#value");
+    assert_eq!(rfc_examples("{#path:6}/here"), "#/foo/b/here");
+    assert_eq!(rfc_examples("X{#keys*}"), "X#comma=,,dot=.,semi=;");
+}
+
+#[test]
+fn test_label_path_and_parameter_expansion() {
+    assert_eq!(rfc_examples("X{.list*}"), "X.red.green.blue");
+    assert_eq!(rfc_examples("{/var,x}/here"), "/value/1024/here");
+    assert_eq!(rfc_examples("{/list*,path:4}"), "/red/green/blue/%2Ffoo");
+    assert_eq!(rfc_examples("{;x,y,empty}"), ";x=1024;y=768;empty");
+    assert_eq!(rfc_examples("{;list*}"), ";list=red;list=green;list=blue");
+    assert_eq!(rfc_examples("{?x,y,empty}"), "?x=1024&y=768&empty=");
+    assert_eq!(rfc_examples("{?keys*}"), "?comma=%2C&dot=.&semi=%3B");
+    assert_eq!(rfc_examples("?fixed=yes{&x}"), "?fixed=yes&x=1024");
+    assert_eq!(rfc_examples("{?list}"), "?list=red,green,blue");
+}
+
+#[test]
+fn test_invalid_templates() {
+    assert!(UriTemplate::try_new("/users/{id").is_err());
+    assert!(UriTemplate::try_new("/users/id}").is_err());
+    assert!(UriTemplate::try_new("/users/{}").is_err());
+    assert!(UriTemplate::try_new("/users/{=id}").is_err());
+    assert!(UriTemplate::try_new("/users/{id:0}").is_err());
+    assert!(UriTemplate::try_new("/users/{a b}").is_err());
+}
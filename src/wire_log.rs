@@ -0,0 +1,238 @@
+//! Dump of the requests and of the response heads, similar to `curl -v`.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use http::header::{HeaderMap, HeaderName};
+
+use crate::wire_tap::Direction;
+
+/// Headers whose values are always replaced by `[redacted]` when redaction is enabled.
+const REDACTED_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "set-cookie"];
+
+/// A sink which receives a readable dump of the exchanges with the server, similar to the output of
+/// `curl -v`.
+///
+/// The lines of the request head are prefixed with `> ` and the lines of the response head with `< `. The
+/// values of the `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` headers, and of the
+/// headers of the request marked as sensitive, such as those set with a `Secret`, are redacted unless
+/// `redact` is disabled. The bodies are only written when `bodies` is enabled, as lossy UTF-8.
+///
+/// Cloning a `WireLog` is cheap, the clones share the same sink.
+#[derive(Clone)]
+pub struct WireLog {
+    sink: Arc<Mutex<dyn Write + Send>>,
+    redact: bool,
+    bodies: bool,
+}
+
+impl WireLog {
+    /// Create a new `WireLog` writing to the given sink.
+    pub fn new<W>(sink: W) -> WireLog
+    where
+        W: Write + Send + 'static,
+    {
+        WireLog {
+            sink: Arc::new(Mutex::new(sink)),
+            redact: true,
+            bodies: false,
+        }
+    }
+
+    /// Create a new `WireLog` writing to the standard error.
+    pub fn stderr() -> WireLog {
+        WireLog::new(io::stderr())
+    }
+
+    /// Set if the values of the headers carrying credentials are redacted.
+    ///
+    /// This value defaults to true.
+    pub fn redact(mut self, redact: bool) -> WireLog {
+        self.redact = redact;
+        self
+    }
+
+    /// Set if the bodies of the requests and of the responses are written.
+    ///
+    /// This value defaults to false.
+    pub fn bodies(mut self, bodies: bool) -> WireLog {
+        self.bodies = bodies;
+        self
+    }
+
+    /// Start the dump of an exchange. The values of the headers marked as sensitive in the given request
+    /// headers are redacted.
+    pub(crate) fn start(&self, headers: &HeaderMap) -> WireLogger {
+        let mut sensitive: Vec<HeaderName> = headers
+            .iter()
+            .filter(|(_, value)| value.is_sensitive())
+            .map(|(name, _)| name.clone())
+            .collect();
+        sensitive.dedup();
+        WireLogger {
+            log: self.clone(),
+            sensitive,
+            sent: Message::default(),
+            received: Message::default(),
+        }
+    }
+
+    fn write(&self, data: &[u8]) {
+        if !data.is_empty() {
+            // The dump is best effort, it must not make the request fail.
+            let _ = self.sink.lock().unwrap().write_all(data);
+        }
+    }
+}
+
+impl fmt::Debug for WireLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WireLog")
+            .field("redact", &self.redact)
+            .field("bodies", &self.bodies)
+            .finish()
+    }
+}
+
+/// The state of the dump of a request or of a response.
+#[derive(Default)]
+struct Message {
+    /// The start of a line of the head which was not completely received yet.
+    line: Vec<u8>,
+    /// Whether the end of the head was reached.
+    in_body: bool,
+}
+
+/// The dump of an exchange: one request and its response.
+pub(crate) struct WireLogger {
+    log: WireLog,
+    /// The names of the headers of the request whose values are sensitive.
+    sensitive: Vec<HeaderName>,
+    sent: Message,
+    received: Message,
+}
+
+impl WireLogger {
+    pub(crate) fn log(&mut self, direction: Direction, data: &[u8]) {
+        let (message, prefix) = match direction {
+            Direction::Sent => (&mut self.sent, ">"),
+            Direction::Received => (&mut self.received, "<"),
+        };
+        let mut out = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            if message.in_body {
+                if self.log.bodies {
+                    out.extend_from_slice(String::from_utf8_lossy(rest).as_bytes());
+                }
+                break;
+            }
+            match rest.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    message.line.extend_from_slice(&rest[..=end]);
+                    rest = &rest[end + 1..];
+                    let line = String::from_utf8_lossy(&message.line).trim_end().to_string();
+                    message.line.clear();
+                    message.in_body = line.is_empty();
+                    let line = if self.log.redact {
+                        redact(&line, &self.sensitive)
+                    } else {
+                        line
+                    };
+                    out.extend_from_slice(format!("{} {}", prefix, line).trim_end().as_bytes());
+                    out.push(b'\n');
+                }
+                None => {
+                    message.line.extend_from_slice(rest);
+                    break;
+                }
+            }
+        }
+        self.log.write(&out);
+    }
+}
+
+/// Replace the value of the header of the line if it carries credentials or is one of the sensitive headers.
+fn redact(line: &str, sensitive: &[HeaderName]) -> String {
+    match line.split_once(':') {
+        Some((name, _))
+            if REDACTED_HEADERS
+                .iter()
+                .copied()
+                .chain(sensitive.iter().map(HeaderName::as_str))
+                .any(|h| name.trim().eq_ignore_ascii_case(h)) =>
+        {
+            format!("{}: [redacted]", name)
+        }
+        _ => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wire_log() {
+    let buf = SharedBuf::default();
+    let mut logger = WireLog::new(buf.clone()).start(&HeaderMap::new());
+    logger.log(Direction::Sent, b"GET / HTTP/1.1\r\nAuthori");
+    logger.log(Direction::Sent, b"zation: Basic abc\r\nHost: foo\r\n\r\nbody");
+    logger.log(
+        Direction::Received,
+        b"HTTP/1.1 200 OK\r\nSet-Cookie: a=b\r\nContent-Length: 5\r\n\r\nhello",
+    );
+    assert_eq!(
+        String::from_utf8(buf.0.lock().unwrap().clone()).unwrap(),
+        "> GET / HTTP/1.1\n> Authorization: [redacted]\n> Host: foo\n>\n\
+         < HTTP/1.1 200 OK\n< Set-Cookie: [redacted]\n< Content-Length: 5\n<\n"
+    );
+}
+
+#[test]
+fn test_wire_log_bodies() {
+    let buf = SharedBuf::default();
+    let mut logger = WireLog::new(buf.clone())
+        .redact(false)
+        .bodies(true)
+        .start(&HeaderMap::new());
+    logger.log(Direction::Sent, b"POST / HTTP/1.1\r\nCookie: a=b\r\n\r\nbody\n");
+    assert_eq!(
+        String::from_utf8(buf.0.lock().unwrap().clone()).unwrap(),
+        "> POST / HTTP/1.1\n> Cookie: a=b\n>\nbody\n"
+    );
+}
+
+#[test]
+fn test_wire_log_sensitive_headers() {
+    use http::header::HeaderValue;
+
+    let mut headers = HeaderMap::new();
+    let mut value = HeaderValue::from_static("my-api-key");
+    value.set_sensitive(true);
+    headers.insert("x-api-key", value);
+    headers.insert("x-request-id", HeaderValue::from_static("42"));
+
+    let buf = SharedBuf::default();
+    let mut logger = WireLog::new(buf.clone()).start(&headers);
+    logger.log(
+        Direction::Sent,
+        b"GET / HTTP/1.1\r\nX-Api-Key: my-api-key\r\nX-Request-Id: 42\r\n\r\n",
+    );
+    assert_eq!(
+        String::from_utf8(buf.0.lock().unwrap().clone()).unwrap(),
+        "> GET / HTTP/1.1\n> X-Api-Key: [redacted]\n> X-Request-Id: 42\n>\n"
+    );
+}
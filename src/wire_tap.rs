@@ -0,0 +1,47 @@
+//! Observation of the raw bytes exchanged with the server.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// The direction of the bytes given to a `WireTap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Bytes written to the connection.
+    Sent,
+    /// Bytes read from the connection.
+    Received,
+}
+
+type TapFn = dyn Fn(Direction, &[u8]) + Send + Sync;
+
+/// A callback which receives the exact bytes written to and read from the connection to the server.
+///
+/// When using TLS, the callback receives the bytes before they are encrypted and after they are decrypted.
+/// The bytes exchanged with a proxy to open a tunnel are not given to the callback. The callback is called
+/// from the thread performing the request, and should not block.
+///
+/// Cloning a `WireTap` is cheap, the clones share the same callback.
+#[derive(Clone)]
+pub struct WireTap(Arc<TapFn>);
+
+impl WireTap {
+    /// Create a new `WireTap` calling the given function.
+    pub fn new<F>(f: F) -> WireTap
+    where
+        F: Fn(Direction, &[u8]) + Send + Sync + 'static,
+    {
+        WireTap(Arc::new(f))
+    }
+
+    pub(crate) fn tap(&self, direction: Direction, data: &[u8]) {
+        if !data.is_empty() {
+            (self.0)(direction, data);
+        }
+    }
+}
+
+impl fmt::Debug for WireTap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("WireTap")
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use attohttpc::{MemoryCache, StatusCode};
+use rouille::{Response, Server};
+
+#[test]
+fn test_cache_etag() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server_hits = hits.clone();
+    let server = Server::new("127.0.0.1:0", move |request| {
+        server_hits.fetch_add(1, Ordering::SeqCst);
+        match request.url().as_str() {
+            "/private" => Response::text("secret")
+                .with_additional_header("ETag", "\"p\"")
+                .with_additional_header("Cache-Control", "no-store"),
+            _ if request.header("If-None-Match") == Some("\"v1\"") => Response::text("")
+                .with_status_code(304)
+                .with_additional_header("ETag", "\"v1\"")
+                .with_additional_header("X-Revalidated", "yes"),
+            _ => Response::text("hello").with_additional_header("ETag", "\"v1\""),
+        }
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let cache = MemoryCache::new();
+    let url = format!("http://127.0.0.1:{}/", port);
+    let resp = attohttpc::get(&url).cache(cache.clone()).send().unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert_eq!(cache.len(), 1);
+
+    let resp = attohttpc::get(&url).cache(cache.clone()).send().unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["X-Revalidated"], "yes");
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // The cache is not used when the user sends a conditional request.
+    let resp = attohttpc::get(&url)
+        .cache(cache.clone())
+        .header("If-None-Match", "\"v1\"")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/private", port))
+        .cache(cache.clone())
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "secret");
+    assert_eq!(cache.len(), 1);
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_cache_last_modified() {
+    const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+    let server = Server::new("127.0.0.1:0", |request| {
+        if request.header("If-Modified-Since") == Some(LAST_MODIFIED) {
+            Response::text("").with_status_code(304)
+        } else {
+            Response::text("content").with_additional_header("Last-Modified", LAST_MODIFIED)
+        }
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let session = attohttpc::Session::new().cache(MemoryCache::new());
+    for _ in 0..2 {
+        let resp = session.get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Last-Modified"], LAST_MODIFIED);
+        assert_eq!(resp.text().unwrap(), "content");
+    }
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "cookies")]
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use attohttpc::CookieJar;
+
+/// Start a server which sets a cookie and redirects on `/login`, and echoes the `Cookie` header it receives
+/// on any other path.
+fn start_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut path = String::new();
+            let mut cookie = String::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if path.is_empty() {
+                    path = line.split(' ').nth(1).unwrap_or("").to_string();
+                } else if line.to_ascii_lowercase().starts_with("cookie:") {
+                    cookie = line[7..].trim().to_string();
+                }
+            }
+
+            let response = if path == "/login" {
+                "HTTP/1.1 302 Found\r\nSet-Cookie: session=abc; Path=/\r\nLocation: /home\r\nContent-Length: 0\r\n\r\n"
+                    .to_string()
+            } else {
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", cookie.len(), cookie)
+            };
+            let _ = reader.get_mut().write_all(response.as_bytes());
+        }
+    });
+
+    port
+}
+
+#[test]
+fn test_cookie_sent_on_redirect() {
+    let port = start_server();
+    let jar = CookieJar::new();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/login", port))
+        .cookie_jar(jar.clone())
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "session=abc");
+    assert_eq!(jar.len(), 1);
+}
+
+#[test]
+fn test_cookie_sent_after_user_cookie() {
+    let port = start_server();
+    let jar = CookieJar::new();
+    jar.set_cookie(format!("http://127.0.0.1:{}/", port), "session=xyz")
+        .unwrap();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/home", port))
+        .header("Cookie", "theme=dark")
+        .cookie_jar(jar)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "theme=dark; session=xyz");
+}
+
+#[test]
+fn test_no_jar_no_cookie() {
+    let port = start_server();
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/login", port))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "");
+}
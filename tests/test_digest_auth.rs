@@ -0,0 +1,68 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use rouille::{Response, Server};
+
+#[test]
+fn test_digest_auth_retry() {
+    // The server challenges the requests without credentials, and answers with the credentials it received.
+    let server = Server::new("127.0.0.1:0", |request| match request.header("Authorization") {
+        Some(authorization) => Response::text(authorization),
+        None => Response::text("unauthorized")
+            .with_status_code(401)
+            .with_additional_header("WWW-Authenticate", r#"Basic realm="test""#)
+            .with_additional_header(
+                "WWW-Authenticate",
+                r#"Digest realm="test", qop="auth", algorithm=SHA-256, nonce="abc", opaque="xyz""#,
+            ),
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/path?q=1", port))
+        .text("body")
+        .digest_auth("user", "password")
+        .send()
+        .unwrap();
+    assert!(resp.is_success());
+    let authorization = resp.text().unwrap();
+    assert!(
+        authorization.starts_with(r#"Digest username="user", realm="test", nonce="abc", uri="/path?q=1", response=""#)
+    );
+    assert!(authorization.contains(", algorithm=SHA-256, qop=auth, nc=00000001, cnonce=\""));
+    assert!(authorization.ends_with(r#", opaque="xyz""#));
+
+    // Without credentials, the challenge is returned.
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::UNAUTHORIZED);
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_digest_auth_rejected() {
+    // The server rejects every answer, the challenge is only answered once.
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let server = Server::new("127.0.0.1:0", move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Response::text("unauthorized")
+            .with_status_code(401)
+            .with_additional_header("WWW-Authenticate", r#"Digest realm="test", nonce="abc""#)
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .digest_auth("user", "wrong")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::UNAUTHORIZED);
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
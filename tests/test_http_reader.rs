@@ -0,0 +1,99 @@
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use attohttpc::{ErrorKind, HttpReader};
+
+/// Serve the data with support for range requests, and count the requests.
+fn serve(data: Vec<u8>, ranges: bool) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    let mut parts = value.trim().split('-');
+                    let start: usize = parts.next().unwrap().parse().unwrap();
+                    let end: usize = parts.next().unwrap().parse().unwrap();
+                    range = Some((start, end.min(data.len() - 1)));
+                }
+            }
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let mut stream = reader.into_inner();
+            match range {
+                Some((start, end)) if ranges => {
+                    write!(
+                        stream,
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n",
+                        start,
+                        end,
+                        data.len(),
+                        end - start + 1
+                    )
+                    .unwrap();
+                    stream.write_all(&data[start..=end]).unwrap();
+                }
+                _ => {
+                    write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len()).unwrap();
+                    stream.write_all(&data).unwrap();
+                }
+            }
+        }
+    });
+
+    (port, requests)
+}
+
+#[test]
+fn test_http_reader_read_and_seek() {
+    let data: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+    let (port, requests) = serve(data.clone(), true);
+
+    let mut reader = HttpReader::open(format!("http://127.0.0.1:{}/file.zip", port))
+        .unwrap()
+        .block_size(1000)
+        .read_ahead(1);
+    assert_eq!(reader.len(), 10_000);
+
+    let mut buf = [0; 10];
+    reader.seek(SeekFrom::End(-10)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[9990..]);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+    reader.seek(SeekFrom::Start(1500)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[1500..1510]);
+    let count = requests.load(Ordering::SeqCst);
+
+    // The next block was read ahead.
+    reader.seek(SeekFrom::Start(2500)).unwrap();
+    reader.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf[..], &data[2500..2510]);
+    assert_eq!(requests.load(Ordering::SeqCst), count);
+
+    reader.seek(SeekFrom::Start(0)).unwrap();
+    let mut all = Vec::new();
+    reader.read_to_end(&mut all).unwrap();
+    assert_eq!(all, data);
+}
+
+#[test]
+fn test_http_reader_ranges_not_supported() {
+    let (port, _) = serve(vec![1, 2, 3], false);
+    let err = HttpReader::open(format!("http://127.0.0.1:{}/", port)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::RangeNotSupported));
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use attohttpc::{ErrorKind, Middleware, PreparedRequest, Response, Session};
+use rouille::Server;
+
+#[derive(Debug)]
+struct Auth(&'static str);
+
+impl Middleware for Auth {
+    fn before_send(&self, request: &mut PreparedRequest) -> attohttpc::Result {
+        request
+            .headers_mut()
+            .insert("Authorization", format!("Bearer {}", self.0).parse().unwrap());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Middleware for Recorder {
+    fn before_send(&self, request: &mut PreparedRequest) -> attohttpc::Result {
+        self.0.lock().unwrap().push(format!("send {}", request.url().path()));
+        Ok(())
+    }
+
+    fn after_receive(&self, response: &mut Response) -> attohttpc::Result {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("receive {}", response.status().as_u16()));
+        response.headers_mut().insert("X-Recorded", "1".parse().unwrap());
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Deny;
+
+impl Middleware for Deny {
+    fn before_send(&self, _: &mut PreparedRequest) -> attohttpc::Result {
+        Err(ErrorKind::InvalidBaseUrl.into())
+    }
+}
+
+#[test]
+fn test_middleware() {
+    let server = Server::new("127.0.0.1:0", |request| {
+        rouille::Response::text(request.header("Authorization").unwrap_or("none"))
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let recorder = Recorder::default();
+    let events = recorder.0.clone();
+    let session = Session::new()
+        .base_url(format!("http://127.0.0.1:{}/", port))
+        .middleware(Auth("token"))
+        .middleware(recorder);
+
+    let resp = session.get("/items").send().unwrap();
+    assert_eq!(resp.headers()["X-Recorded"], "1");
+    assert_eq!(resp.text().unwrap(), "Bearer token");
+    assert_eq!(*events.lock().unwrap(), ["send /items", "receive 200"]);
+
+    match session.clone().middleware(Deny).get("/items").send() {
+        Err(err) => assert!(matches!(err.kind(), ErrorKind::InvalidBaseUrl)),
+        Ok(_) => panic!("the request was not denied"),
+    }
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::io::Read;
+
+use attohttpc::{Multipart, Part};
+use rouille::input::multipart::get_multipart_input;
+use rouille::{Response, Server};
+
+#[test]
+fn test_multipart_upload() {
+    // The server describes each field it parsed on a line.
+    let server = Server::new("127.0.0.1:0", |request| {
+        let mut multipart = get_multipart_input(request).unwrap();
+        let mut fields = String::new();
+        while let Some(mut field) = multipart.next() {
+            let mut data = String::new();
+            field.data.read_to_string(&mut data).unwrap();
+            fields += &format!(
+                "{} {:?} {:?} {}\n",
+                field.headers.name,
+                field.headers.filename,
+                field.headers.content_type.map(|mime| mime.to_string()),
+                data
+            );
+        }
+        Response::text(fields)
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let path = std::env::temp_dir().join(format!("attohttpc-test-multipart-{}.txt", std::process::id()));
+    fs::write(&path, "x".repeat(200_000)).unwrap();
+
+    let form = Multipart::new()
+        .text("title", "hello world")
+        .file("upload", &path)
+        .unwrap()
+        .part(
+            Part::bytes("notes", "some notes")
+                .file_name("notes.txt")
+                .content_type("text/plain")
+                .unwrap(),
+        );
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .multipart(form)
+        .send()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(resp.is_success());
+
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    let content_type = if cfg!(feature = "mime-guess") {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    };
+    assert_eq!(
+        resp.text().unwrap(),
+        format!(
+            "title None None hello world\n\
+             upload Some({:?}) Some({:?}) {}\n\
+             notes Some(\"notes.txt\") Some(\"text/plain\") some notes\n",
+            file_name,
+            content_type,
+            "x".repeat(200_000)
+        )
+    );
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+
+use attohttpc::Event;
+use rouille::{Response, Server};
+
+#[test]
+fn test_observer() {
+    let server = Server::new("127.0.0.1:0", |_| Response::text("hello")).unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let resp = attohttpc::get(format!("http://localhost:{}/", port))
+        .observer(move |event| {
+            let name = match event {
+                Event::DnsStart { host } => format!("dns start {}", host),
+                Event::DnsEnd { host, addrs, .. } => {
+                    assert!(!addrs.is_empty());
+                    format!("dns end {}", host)
+                }
+                Event::ConnectStart { .. } => "connect start".to_string(),
+                // The connection to the address of the other family may be refused.
+                Event::ConnectEnd { error: Some(_), .. } => return,
+                Event::ConnectEnd { error: None, .. } => "connect end".to_string(),
+                Event::TlsHandshakeStart { .. } | Event::TlsHandshakeEnd { .. } => "tls".to_string(),
+                Event::RequestWritten { .. } => "request written".to_string(),
+                Event::FirstByte { elapsed, .. } => {
+                    assert!(elapsed.as_secs() < 5);
+                    "first byte".to_string()
+                }
+                Event::BodyComplete { bytes, .. } => format!("body complete {}", bytes),
+            };
+            recorded.lock().unwrap().push(name);
+        })
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+
+    let mut events = events.lock().unwrap().clone();
+    // Skip the attempts on the address which refused the connection.
+    events.dedup();
+    assert_eq!(
+        events,
+        [
+            "dns start localhost",
+            "dns end localhost",
+            "connect start",
+            "connect end",
+            "request written",
+            "first byte",
+            "body complete 5",
+        ]
+    );
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,344 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use attohttpc::{ConnectionPool, Settings, StaticResolver};
+
+/// Start a server which answers up to `max_requests` requests per connection with the given response, and
+/// counts the connections it accepts.
+fn start_server(response: &'static [u8], max_requests: usize) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                for _ in 0..max_requests {
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    reader.get_mut().write_all(response).unwrap();
+                }
+            });
+        }
+    });
+
+    (port, connections)
+}
+
+#[test]
+fn test_pool_reuses_connection() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new();
+
+    for _ in 0..3 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+        assert_eq!(pool.idle_connections(), 1);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    pool.close_idle_connections();
+    assert_eq!(pool.idle_connections(), 0);
+}
+
+#[test]
+fn test_pool_reuses_chunked_connection() {
+    let (port, connections) = start_server(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        100,
+    );
+    let pool = ConnectionPool::new();
+
+    for _ in 0..3 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[cfg(feature = "compress")]
+fn test_pool_reuses_gzip_connection() {
+    // The body is "hello" compressed with gzip, followed by bytes which are not part of the gzip stream.
+    let (port, connections) = start_server(
+        b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: 28\r\n\r\n\
+\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\x48\xcd\xc9\xc9\x07\x00\x86\xa6\x10\x36\x05\x00\x00\x00\r\n\n",
+        100,
+    );
+    let pool = ConnectionPool::new();
+
+    for _ in 0..3 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+        assert_eq!(pool.idle_connections(), 1);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_pool_separates_connection_settings() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new();
+
+    for tos in &[None, Some(0x10), None, Some(0x10)] {
+        let mut req = attohttpc::get(format!("http://127.0.0.1:{}/", port)).connection_pool(pool.clone());
+        if let Some(tos) = tos {
+            req = req.tos(*tos);
+        }
+        assert_eq!(req.send().unwrap().text().unwrap(), "hello");
+    }
+    // The connections opened with different settings are not shared.
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(pool.idle_connections(), 2);
+}
+
+#[test]
+fn test_pool_separates_local_addresses_and_resolvers() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new();
+    let url = format!("http://localhost:{}/", port);
+
+    let send = |req: attohttpc::RequestBuilder| assert_eq!(req.send().unwrap().text().unwrap(), "hello");
+    send(attohttpc::get(&url).connection_pool(pool.clone()));
+    send(
+        attohttpc::get(&url)
+            .connection_pool(pool.clone())
+            .local_address("127.0.0.1".parse().unwrap()),
+    );
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    // A request which pins the address of the host does not reuse a connection opened without it.
+    let resolver = StaticResolver::new().host("localhost", "127.0.0.1".parse().unwrap());
+    send(attohttpc::get(&url).connection_pool(pool.clone()).resolver(resolver));
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+
+    // The resolver of a session is shared by its requests.
+    let session = attohttpc::Session::with_settings(
+        Settings::new()
+            .connection_pool(pool.clone())
+            .resolver(StaticResolver::new().host("localhost", "127.0.0.1".parse().unwrap())),
+    );
+    send(session.get(&url));
+    send(session.get(&url));
+    assert_eq!(connections.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_pool_connection_close() {
+    let (port, connections) = start_server(
+        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        100,
+    );
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+        assert_eq!(pool.idle_connections(), 0);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_pool_http_10() {
+    let (port, connections) = start_server(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.version(), attohttpc::Version::HTTP_10);
+        assert_eq!(resp.text().unwrap(), "hello");
+        assert_eq!(pool.idle_connections(), 0);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_pool_http_10_keep_alive() {
+    let (port, connections) = start_server(
+        b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\nConnection: keep-alive\r\n\r\nhello",
+        100,
+    );
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_pool_request_connection_close() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .header(attohttpc::header::CONNECTION, "close")
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+        assert_eq!(pool.idle_connections(), 0);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_pool_keep_alive_max() {
+    let (port, connections) = start_server(
+        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nKeep-Alive: timeout=5, max=0\r\n\r\nhello",
+        100,
+    );
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_pool_server_closed_idle_connection() {
+    // The server announces a persistent connection, but closes it after the first response.
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 1);
+    let pool = ConnectionPool::new();
+
+    for _ in 0..3 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_pool_max_requests_per_connection() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 100);
+    let pool = ConnectionPool::new().max_requests_per_connection(2);
+
+    for _ in 0..4 {
+        let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_pool_timings() {
+    let (port, _) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", 10);
+    let pool = ConnectionPool::new();
+
+    let resp = attohttpc::get(format!("http://localhost:{}/", port))
+        .connection_pool(pool.clone())
+        .send()
+        .unwrap();
+    let timings = resp.timings();
+    assert!(timings.dns().is_some());
+    assert!(timings.connect().is_some());
+    assert_eq!(timings.tls(), None);
+    assert!(timings.time_to_first_byte() <= timings.total());
+    resp.text().unwrap();
+
+    // Nothing is spent opening a connection reused from the pool.
+    let resp = attohttpc::get(format!("http://localhost:{}/", port))
+        .connection_pool(pool)
+        .send()
+        .unwrap();
+    let timings = resp.timings();
+    assert_eq!(timings.dns(), None);
+    assert_eq!(timings.connect(), None);
+    assert!(timings.time_to_first_byte() <= timings.total());
+}
+
+#[test]
+fn test_pool_drops_connection_after_early_response() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+
+    // Each connection answers the first request without reading its body, then ignores what is sent.
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let response = b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\nConnection: keep-alive\r\n\r\n";
+                reader.get_mut().write_all(response).unwrap();
+                let _ = std::io::copy(&mut reader, &mut std::io::sink());
+            });
+        }
+    });
+
+    let pool = ConnectionPool::new();
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .connection_pool(pool.clone())
+        .bytes(vec![0; 16 * 1024 * 1024])
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::PAYLOAD_TOO_LARGE);
+    resp.text().unwrap();
+    // The server still expects the rest of the body, so the connection cannot be reused.
+    assert_eq!(pool.idle_connections(), 0);
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .connection_pool(pool)
+        .read_timeout(std::time::Duration::from_secs(5))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
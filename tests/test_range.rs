@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::PathBuf;
+
+use attohttpc::{ErrorKind, StatusCode};
+use rouille::{Request, Response, Server};
+
+const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+const ETAG: &str = "\"v1\"";
+
+/// Serve the content with support for the `Range` and `If-Range` headers.
+fn serve(request: &Request) -> Response {
+    if request.url() == "/missing" {
+        return Response::empty_404();
+    }
+    let range = request
+        .header("Range")
+        .filter(|_| request.header("If-Range").map_or(true, |validator| validator == ETAG))
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|range| range.split_once('-'));
+    match range {
+        Some((start, end)) => {
+            let start: usize = start.parse().unwrap();
+            let end: usize = end.parse().map_or(CONTENT.len(), |end: usize| end + 1);
+            if start >= CONTENT.len() {
+                return Response::empty_400()
+                    .with_status_code(416)
+                    .with_additional_header("Content-Range", format!("bytes */{}", CONTENT.len()));
+            }
+            Response::from_data("application/octet-stream", &CONTENT[start..end])
+                .with_status_code(206)
+                .with_additional_header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, end - 1, CONTENT.len()),
+                )
+                .with_additional_header("ETag", ETAG)
+        }
+        None => Response::from_data("application/octet-stream", CONTENT).with_additional_header("ETag", ETAG),
+    }
+}
+
+fn download_path(name: &str) -> (PathBuf, PathBuf) {
+    let path = std::env::temp_dir().join(format!("attohttpc-{}-{}", name, std::process::id()));
+    let mut resume = path.clone().into_os_string();
+    resume.push(".resume");
+    (path, resume.into())
+}
+
+#[test]
+fn test_range() {
+    let server = Server::new("127.0.0.1:0", serve).unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .range(2..5)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resp.headers()["Content-Range"], "bytes 2-4/36");
+    assert_eq!(resp.bytes().unwrap(), b"234");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .range(30..)
+        .send()
+        .unwrap();
+    assert_eq!(resp.bytes().unwrap(), b"uvwxyz");
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_download_resumable() {
+    let server = Server::new("127.0.0.1:0", serve).unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+    let url = format!("http://127.0.0.1:{}/", port);
+    let (path, resume) = download_path("download");
+
+    // A new download.
+    let _ = fs::remove_file(&path);
+    assert_eq!(attohttpc::get(&url).download_resumable(&path).unwrap(), 36);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    assert!(!resume.exists());
+
+    // An interrupted download is resumed.
+    fs::write(&path, &CONTENT[..10]).unwrap();
+    fs::write(&resume, ETAG).unwrap();
+    assert_eq!(attohttpc::get(&url).download_resumable(&path).unwrap(), 36);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    assert!(!resume.exists());
+
+    // The resource was modified since the partial download, it is downloaded again.
+    fs::write(&path, b"XXXXX").unwrap();
+    fs::write(&resume, "\"v0\"").unwrap();
+    assert_eq!(attohttpc::get(&url).download_resumable(&path).unwrap(), 36);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    assert!(!resume.exists());
+
+    // The download was complete.
+    fs::write(&resume, ETAG).unwrap();
+    assert_eq!(attohttpc::get(&url).download_resumable(&path).unwrap(), 36);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    assert!(!resume.exists());
+
+    let err = attohttpc::get(format!("{}missing", url))
+        .download_resumable(&path)
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::StatusCode(status) => assert_eq!(*status, StatusCode::NOT_FOUND),
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    fs::remove_file(&path).unwrap();
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -64,3 +64,152 @@ fn test_redirection_disallowed() {
 
     assert!(resp.status().is_redirection());
 }
+
+#[test]
+fn test_redirection_strips_credentials_across_origins() {
+    // The target server answers with the Authorization header it received.
+    let target = rouille::Server::new("127.0.0.1:0", |request| {
+        Response::text(request.header("Authorization").unwrap_or("none"))
+    })
+    .unwrap();
+    let target_port = target.server_addr().port();
+    let (target_handle, target_stop) = target.stoppable();
+
+    let origin = rouille::Server::new("127.0.0.1:0", move |request| match request.url().as_str() {
+        "/same" => Response::redirect_302("/echo"),
+        "/echo" => Response::text(request.header("Authorization").unwrap_or("none")),
+        _ => Response::redirect_302(format!("http://127.0.0.1:{}/", target_port)),
+    })
+    .unwrap();
+    let origin_port = origin.server_addr().port();
+    let (origin_handle, origin_stop) = origin.stoppable();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/same", origin_port))
+        .bearer_auth("token")
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "Bearer token");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/other", origin_port))
+        .bearer_auth("token")
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "none");
+
+    target_stop.send(()).unwrap();
+    target_handle.join().unwrap();
+    origin_stop.send(()).unwrap();
+    origin_handle.join().unwrap();
+}
+
+#[test]
+fn test_redirection_policy() {
+    use attohttpc::{RedirectAction, RedirectPolicy};
+    use std::sync::{Arc, Mutex};
+
+    let server = rouille::Server::new("127.0.0.1:0", |request| match request.url().as_str() {
+        "/a" => Response::redirect_302("/b"),
+        "/b" => Response::redirect_301("http://localhost:1/c"),
+        _ => Response::text("end"),
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    // The policy sees the redirection chain, and stops at the other host.
+    let chain = Arc::new(Mutex::new(Vec::new()));
+    let seen = chain.clone();
+    let policy = RedirectPolicy::new(move |previous, next, status| {
+        seen.lock()
+            .unwrap()
+            .push(format!("{} {} {}", status.as_u16(), previous.path(), next));
+        if next.host() == previous.host() {
+            RedirectAction::Follow
+        } else {
+            RedirectAction::Stop
+        }
+    });
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/a", port))
+        .redirect_policy(policy)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(resp.final_url().path(), "/b");
+    let history: Vec<_> = resp
+        .history()
+        .iter()
+        .map(|(status, url)| (status.as_u16(), url.path()))
+        .collect();
+    assert_eq!(history, [(302, "/a")]);
+    assert_eq!(
+        *chain.lock().unwrap(),
+        [
+            format!("302 /a http://127.0.0.1:{}/b", port),
+            "301 /b http://localhost:1/c".to_string(),
+        ]
+    );
+
+    let result = attohttpc::get(format!("http://127.0.0.1:{}/a", port))
+        .redirect_policy(RedirectPolicy::new(|_, _, _| RedirectAction::Error))
+        .send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        ErrorKind::RedirectionRefused => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_redirection_method() {
+    let server = rouille::Server::new("127.0.0.1:0", |request| {
+        let mut body = String::new();
+        if let Some(mut data) = request.data() {
+            std::io::Read::read_to_string(&mut data, &mut body).unwrap();
+        }
+        match request.url().as_str() {
+            "/302" => Response::redirect_302("/echo"),
+            "/303" => Response::redirect_303("/echo"),
+            "/307" => Response::redirect_307("/echo"),
+            "/308" => Response::redirect_308("/echo"),
+            _ => Response::text(format!("{} {}", request.method(), body)),
+        }
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", port, path);
+
+    let resp = attohttpc::post(url("/302")).text("body").send().unwrap();
+    assert_eq!(resp.final_url().path(), "/echo");
+    assert_eq!(resp.history().len(), 1);
+    assert_eq!(resp.history()[0].0, attohttpc::StatusCode::FOUND);
+    assert_eq!(resp.history()[0].1.as_str(), url("/302"));
+    assert_eq!(resp.text().unwrap(), "GET ");
+
+    let resp = attohttpc::put(url("/303")).text("body").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "GET ");
+
+    let resp = attohttpc::put(url("/302")).text("body").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "PUT body");
+
+    let resp = attohttpc::post(url("/307")).text("body").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "POST body");
+
+    let resp = attohttpc::post(url("/308")).text("body").send().unwrap();
+    assert_eq!(resp.final_url().as_str(), url("/echo"));
+    assert_eq!(resp.text().unwrap(), "POST body");
+
+    // A streamed body can only be sent once.
+    let result = attohttpc::post(url("/307")).body(&b"body"[..], Some(4)).send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        ErrorKind::BodyNotReplayable => (),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    let resp = attohttpc::post(url("/303")).body(&b"body"[..], Some(4)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "GET ");
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
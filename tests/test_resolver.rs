@@ -0,0 +1,22 @@
+use attohttpc::StaticResolver;
+use rouille::Response;
+
+#[test]
+fn test_static_resolver() {
+    let server = rouille::Server::new("127.0.0.1:0", |request| {
+        Response::text(request.header("Host").unwrap_or("none"))
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let resolver = StaticResolver::new().host("api.test", [127, 0, 0, 1].into());
+    let resp = attohttpc::get(format!("http://api.test:{}/", port))
+        .resolver(resolver)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("api.test:{}", port));
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
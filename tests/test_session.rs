@@ -0,0 +1,110 @@
+use attohttpc::Session;
+use rouille::{Response, Server};
+
+#[test]
+fn test_session_counters() {
+    let server = Server::new("127.0.0.1:0", |request| {
+        Response::text(request.header("X-Token").unwrap_or("none").to_string())
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let session = Session::new().header("X-Token", "abc");
+    let mut sent = 0;
+    let mut received = 0;
+    for _ in 0..2 {
+        let resp = session.get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+        sent += resp.bytes_sent();
+        let counted = resp.bytes_received();
+        assert_eq!(resp.text().unwrap(), "abc");
+        received += counted;
+    }
+    assert!(sent > 0);
+    assert_eq!(session.bytes_sent(), sent);
+    // The bodies read after the responses were counted are included in the session.
+    assert!(received > 0);
+    assert!(session.bytes_received() >= received);
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(feature = "cookies")]
+fn test_session_cookies() {
+    let server = Server::new("127.0.0.1:0", |request| match request.url().as_str() {
+        "/login" => Response::text("").with_additional_header("Set-Cookie", "session=xyz; Path=/"),
+        _ => Response::text(request.header("Cookie").unwrap_or("none").to_string()),
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let session = Session::new();
+    session.get(format!("http://127.0.0.1:{}/login", port)).send().unwrap();
+    let resp = session.get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "session=xyz");
+
+    // Requests outside of the session do not share its cookies.
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "none");
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_session_rate_limit() {
+    use std::time::{Duration, Instant};
+
+    let server = Server::new("127.0.0.1:0", |_| Response::text("ok")).unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    // Two requests in a burst, then one request every 100 milliseconds.
+    let session = Session::new().rate_limit(2, Duration::from_millis(200));
+    let start = Instant::now();
+    for _ in 0..4 {
+        let resp = session.get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "ok");
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_session_retry_too_many_requests() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let requests = AtomicUsize::new(0);
+    let server = Server::new("127.0.0.1:0", move |request| {
+        if request.url() == "/no-retry-after" {
+            return Response::text("slow down").with_status_code(429);
+        }
+        match requests.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Response::text("slow down")
+                .with_status_code(429)
+                .with_additional_header("Retry-After", "0"),
+            _ => Response::text("ok"),
+        }
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let session = Session::new().retry_too_many_requests(2);
+    let resp = session.get(format!("http://127.0.0.1:{}/", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    let resp = session
+        .get(format!("http://127.0.0.1:{}/no-retry-after", port))
+        .send()
+        .unwrap();
+    assert_eq!(resp.status().as_u16(), 429);
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,178 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use attohttpc::ErrorKind;
+
+/// Start a server which reads the head of the request, sends the given bytes and then stalls.
+fn start_stalling_server(response: &'static [u8]) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+            }
+            let _ = reader.get_mut().write_all(response);
+            thread::sleep(Duration::from_secs(5));
+        }
+    });
+
+    port
+}
+
+fn assert_timed_out(err: &io::Error) {
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn test_read_timeout_head() {
+    let port = start_stalling_server(b"");
+    let start = Instant::now();
+
+    let result = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .read_timeout(Duration::from_millis(200))
+        .send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        ErrorKind::Io(err) => assert_timed_out(err),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn test_read_timeout_body() {
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .read_timeout(Duration::from_millis(200))
+        .send()
+        .unwrap();
+    let (_, _, mut reader) = resp.split();
+    let mut body = Vec::new();
+    assert_timed_out(&reader.read_to_end(&mut body).unwrap_err());
+}
+
+#[test]
+fn test_timeout_head() {
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\n");
+    let start = Instant::now();
+
+    let result = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .timeout(Duration::from_millis(300))
+        .read_timeout(Duration::from_secs(10))
+        .send();
+    let err = result.map(|_| ()).unwrap_err();
+    match err.kind() {
+        ErrorKind::Timeout => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert!(err.is_timeout());
+    assert!(err.is_retryable());
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn test_connection_refused() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let err = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .send()
+        .map(|_| ())
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::ConnectionRefused => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert!(err.is_connect());
+    assert!(err.is_retryable());
+    assert!(!err.is_timeout());
+}
+
+#[test]
+fn test_timeout_body() {
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .timeout(Duration::from_millis(300))
+        .send()
+        .unwrap();
+    match resp.text().unwrap_err().kind() {
+        ErrorKind::Timeout => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+fn test_timeout_redirections() {
+    // Every response is a redirection, slowed down by the server.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+            let _ = reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 302 Found\r\nLocation: /\r\nContent-Length: 0\r\n\r\n");
+        }
+    });
+
+    let result = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .max_redirections(100)
+        .timeout(Duration::from_millis(350))
+        .send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        ErrorKind::Timeout => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+fn test_low_speed_limit_head() {
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\n");
+    let start = Instant::now();
+
+    let result = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .low_speed_limit(100, Duration::from_millis(300))
+        .send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        ErrorKind::TransferTooSlow => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn test_low_speed_limit_body() {
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .low_speed_limit(100, Duration::from_millis(300))
+        .send()
+        .unwrap();
+    match resp.text().unwrap_err().kind() {
+        ErrorKind::TransferTooSlow => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    // A complete response is not affected by the limit.
+    let port = start_stalling_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", port))
+        .low_speed_limit(1_000_000, Duration::from_millis(300))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+}
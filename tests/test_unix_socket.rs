@@ -0,0 +1,86 @@
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use attohttpc::{ConnectionPool, Session};
+
+/// Start a server on a new Unix domain socket, which answers each request with its request line and `Host`
+/// header, and counts the connections it accepts.
+fn start_server(name: &str) -> (PathBuf, Arc<AtomicUsize>) {
+    let path = std::env::temp_dir().join(format!("attohttpc-{}-{}.sock", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut request_line = String::new();
+                    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    let mut host = String::new();
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some(value) = line.strip_prefix("host: ") {
+                            host = value.trim().to_string();
+                        }
+                    }
+                    let body = format!("{} {}", request_line.trim(), host);
+                    let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+                    reader.get_mut().write_all(response.as_bytes()).unwrap();
+                }
+            });
+        }
+    });
+
+    (path, connections)
+}
+
+#[test]
+fn test_unix_socket() {
+    let (path, connections) = start_server("builder");
+    let pool = ConnectionPool::new();
+
+    for _ in 0..2 {
+        let resp = attohttpc::get("http://docker/v1.40/info?all=1")
+            .unix_socket(&path)
+            .connection_pool(pool.clone())
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "GET /v1.40/info?all=1 HTTP/1.1 docker");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_unix_socket_url() {
+    let (path, _) = start_server("url");
+    let encoded: String = url::form_urlencoded::byte_serialize(path.to_str().unwrap().as_bytes()).collect();
+
+    let resp = attohttpc::get(format!("http+unix://{}/info", encoded)).send().unwrap();
+    assert_eq!(resp.final_url().path(), "/info");
+    assert_eq!(resp.text().unwrap(), "GET /info HTTP/1.1 localhost");
+
+    let session = Session::new().base_url(format!("http+unix://{}/v1/", encoded));
+    let resp = session.get("containers").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "GET /v1/containers HTTP/1.1 localhost");
+
+    std::fs::remove_file(&path).unwrap();
+}
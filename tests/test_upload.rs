@@ -0,0 +1,337 @@
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_early_response_stops_upload() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+        }
+        // Refuse the body without reading it, and keep the connection open for a while.
+        let mut stream = reader.into_inner();
+        stream
+            .write_all(b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 8\r\nConnection: close\r\n\r\ntoo long")
+            .unwrap();
+        thread::sleep(Duration::from_millis(500));
+    });
+
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/upload", port))
+        .bytes(vec![0; 64 * 1024 * 1024])
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(resp.bytes_sent() < 64 * 1024 * 1024);
+    assert_eq!(resp.text().unwrap(), "too long");
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_continue_before_body() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    len = value.trim().parse().unwrap();
+                }
+            }
+            // Interim responses are sent before the body is read.
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\n")
+                .unwrap();
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            let text = format!("received {}", body.len());
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                text.len(),
+                text
+            )
+            .unwrap();
+        }
+    });
+
+    for len in &[10, 8 * 1024 * 1024] {
+        let resp = attohttpc::post(format!("http://127.0.0.1:{}/upload", port))
+            .bytes(vec![0; *len])
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), attohttpc::StatusCode::OK);
+        assert_eq!(resp.text().unwrap(), format!("received {}", len));
+    }
+
+    handle.join().unwrap();
+}
+
+/// Start a server which answers with the transfer coding and the contents of the body it receives.
+fn start_echo_server() -> (u16, thread::JoinHandle<()>, std::sync::mpsc::Sender<()>) {
+    let server = rouille::Server::new("127.0.0.1:0", |request| {
+        let chunked = request.header("Transfer-Encoding") == Some("chunked");
+        let mut body = String::new();
+        request.data().unwrap().read_to_string(&mut body).unwrap();
+        rouille::Response::text(format!("{} {}", chunked, body))
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+    (port, handle, stop)
+}
+
+#[test]
+fn test_reader_body_chunked() {
+    let (port, handle, stop) = start_echo_server();
+
+    let contents = "abc".repeat(50_000);
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .body(Cursor::new(contents.clone()), None)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("true {}", contents));
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_upload_chunk_size() {
+    let (port, handle, stop) = start_echo_server();
+
+    let contents = "abc".repeat(1000);
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .upload_chunk_size(7)
+        .text(contents.clone())
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("false {}", contents));
+
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .upload_chunk_size(7)
+        .body(Cursor::new(contents.clone()), None)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("true {}", contents));
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_reader_body_with_length() {
+    let (port, handle, stop) = start_echo_server();
+
+    // Only the given length is sent.
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .body(Cursor::new("hello world"), Some(5))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "false hello");
+
+    // A reader shorter than its length is an error.
+    let result = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .body(Cursor::new("hello"), Some(10))
+        .send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_prepared_request_sent_twice() {
+    let (port, handle, stop) = start_echo_server();
+
+    let mut prepared = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .text("hello")
+        .prepare();
+    assert_eq!(prepared.body(), b"hello");
+    let mut copy = prepared.try_clone().unwrap();
+    assert_eq!(prepared.send().unwrap().text().unwrap(), "false hello");
+    assert_eq!(prepared.send().unwrap().text().unwrap(), "false hello");
+    assert_eq!(copy.send().unwrap().text().unwrap(), "false hello");
+
+    // A reader body cannot be copied.
+    let prepared = attohttpc::post(format!("http://127.0.0.1:{}/", port))
+        .body(Cursor::new("hello"), None)
+        .prepare();
+    assert!(prepared.try_clone().is_none());
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_file_body() {
+    let (port, handle, stop) = start_echo_server();
+
+    let path = std::env::temp_dir().join(format!("attohttpc-upload-{}.txt", std::process::id()));
+    let contents = "abc".repeat(50_000);
+    std::fs::write(&path, &contents).unwrap();
+
+    let mut prepared = attohttpc::put(format!("http://127.0.0.1:{}/", port))
+        .file(&path)
+        .unwrap()
+        .prepare();
+    assert_eq!(prepared.headers()["Content-Length"], "150000");
+    // The file is opened again each time the request is sent.
+    let resp = prepared.send().unwrap();
+    assert!(resp.bytes_sent() > 150_000);
+    assert_eq!(resp.text().unwrap(), format!("false {}", contents));
+    assert_eq!(prepared.send().unwrap().text().unwrap(), format!("false {}", contents));
+
+    // The file is sent in small chunks, and through a buffer when a wire tap records the data.
+    let tapped = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = tapped.clone();
+    let resp = attohttpc::put(format!("http://127.0.0.1:{}/", port))
+        .upload_chunk_size(1000)
+        .file(&path)
+        .unwrap()
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("false {}", contents));
+    let resp = attohttpc::put(format!("http://127.0.0.1:{}/", port))
+        .wire_tap(move |direction, data| {
+            if direction == attohttpc::Direction::Sent {
+                counter.fetch_add(data.len(), std::sync::atomic::Ordering::SeqCst);
+            }
+        })
+        .file(&path)
+        .unwrap()
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("false {}", contents));
+    assert!(tapped.load(std::sync::atomic::Ordering::SeqCst) > 150_000);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(attohttpc::put(format!("http://127.0.0.1:{}/", port))
+        .file(&path)
+        .is_err());
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+/// A body which writes the same line many times, in small writes.
+struct Repeat(&'static str, usize, Option<u64>);
+
+impl attohttpc::Body for Repeat {
+    fn kind(&self) -> attohttpc::BodyKind {
+        attohttpc::BodyKind::Replayable
+    }
+
+    fn len(&self) -> Option<u64> {
+        self.2
+    }
+
+    fn write(&mut self, writer: &mut dyn Write) -> io::Result<()> {
+        for _ in 0..self.1 {
+            writer.write_all(self.0.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_custom_body() {
+    let (port, handle, stop) = start_echo_server();
+    let url = format!("http://127.0.0.1:{}/", port);
+
+    let resp = attohttpc::post(&url)
+        .custom_body(Repeat("abc", 50_000, None))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), format!("true {}", "abc".repeat(50_000)));
+
+    let mut prepared = attohttpc::post(&url)
+        .custom_body(Repeat("abc", 50_000, Some(150_000)))
+        .prepare();
+    assert_eq!(prepared.headers()["Content-Length"], "150000");
+    assert_eq!(
+        prepared.send().unwrap().text().unwrap(),
+        format!("false {}", "abc".repeat(50_000))
+    );
+    assert_eq!(
+        prepared.send().unwrap().text().unwrap(),
+        format!("false {}", "abc".repeat(50_000))
+    );
+
+    // The body must write exactly its length.
+    let result = attohttpc::post(&url).custom_body(Repeat("abc", 2, Some(10))).send();
+    match result.map(|_| ()).unwrap_err().kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+/// A body whose media type is not a valid header value.
+struct InvalidType;
+
+impl attohttpc::Body for InvalidType {
+    fn write(&mut self, _: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn content_type(&self) -> Option<&'static str> {
+        Some("text/plain\r\nX-Injected: 1")
+    }
+}
+
+#[test]
+fn test_custom_body_invalid_content_type() {
+    let result = attohttpc::post("http://127.0.0.1/").try_custom_body(InvalidType);
+    match result.map(|_| ()).unwrap_err().kind() {
+        attohttpc::ErrorKind::Http(_) => {}
+        kind => panic!("unexpected error kind {:?}", kind),
+    }
+}
+
+#[test]
+fn test_builtin_bodies() {
+    let (port, handle, stop) = start_echo_server();
+    let url = format!("http://127.0.0.1:{}/", port);
+
+    let mut prepared = attohttpc::post(&url)
+        .custom_body(attohttpc::Form::new(&[("name", "John Doe"), ("age", "42")]))
+        .prepare();
+    assert_eq!(prepared.headers()["Content-Type"], "application/x-www-form-urlencoded");
+    assert_eq!(prepared.send().unwrap().text().unwrap(), "false name=John+Doe&age=42");
+
+    let resp = attohttpc::post(&url)
+        .custom_body(attohttpc::ReaderBody::new(Cursor::new("hello world"), Some(5)))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "false hello");
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}
@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use attohttpc::{Secret, WireLog};
+use rouille::{Response, Server};
+
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wire_log_secret_header() {
+    let server = Server::new("127.0.0.1:0", |request| {
+        Response::text(request.header("x-api-key").unwrap_or_default())
+    })
+    .unwrap();
+    let port = server.server_addr().port();
+    let (handle, stop) = server.stoppable();
+
+    let buf = SharedBuf::default();
+    let resp = attohttpc::get(format!("http://localhost:{}/", port))
+        .header("X-Api-Key", Secret::new("my-api-key"))
+        .wire_log(WireLog::new(buf.clone()))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "my-api-key");
+
+    let dump = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert!(dump.contains("> x-api-key: [redacted]\n"), "{}", dump);
+    assert!(!dump.contains("my-api-key"));
+
+    stop.send(()).unwrap();
+    handle.join().unwrap();
+}